@@ -13,7 +13,7 @@ fn criterion_benchmark(c: &mut Criterion) {
     let mut cycle_count = 0;
     c.bench_function("nestest rom", |b| {
         b.iter(|| {
-            nes.clock();
+            nes.clock().unwrap();
             cycle_count += 1;
             if cycle_count % NESTEST_ROM_CLOCKS_TO_REACH_END == 0 {
                 nes.restart();
@@ -23,6 +23,16 @@ fn criterion_benchmark(c: &mut Criterion) {
             }
         })
     });
+
+    // Same as above, running the whole ROM with a single call
+    c.bench_function("nestest rom clock_n", |b| {
+        b.iter(|| {
+            nes.restart();
+            nes.insert_cartdrige(NESTEST_ROM_PATH).unwrap();
+            nes.set_program_counter_at(0xC000);
+            nes.clock_n(NESTEST_ROM_CLOCKS_TO_REACH_END as u64).unwrap();
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
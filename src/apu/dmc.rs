@@ -1,7 +1,4 @@
-use std::{cell::RefCell, rc::Rc};
-
-use crate::bus::Bus;
-use crate::cpu::{enums::Interrupt, Cpu};
+use crate::nes::DmcSampleCallback;
 use crate::state::Stateful;
 
 use super::state::DmcState;
@@ -11,9 +8,6 @@ const DMC_RATE: [u16; 16] = [
 ];
 
 pub struct Dmc {
-    p_bus: Option<Rc<RefCell<Bus>>>,
-    p_cpu: Option<Rc<RefCell<Cpu>>>,
-
     pub interrupt_flag: bool,
     irq_enabled: bool,
     loop_flag: bool,
@@ -32,14 +26,13 @@ pub struct Dmc {
     rate: u16,
 
     output_level: u8,
+
+    o_sample_callback: Option<DmcSampleCallback>,
 }
 
 impl Dmc {
     pub fn new() -> Self {
         Dmc {
-            p_bus: None,
-            p_cpu: None,
-
             interrupt_flag: false,
             irq_enabled: false,
             loop_flag: false,
@@ -58,18 +51,9 @@ impl Dmc {
             rate: 0,
 
             output_level: 0,
-        }
-    }
-
-    pub fn from_state(state: &DmcState) -> Self {
-        let mut dmc = Dmc::new();
-        dmc.set_state(state);
-        dmc
-    }
 
-    pub fn attach_bus_and_cpu(&mut self, p_bus: Rc<RefCell<Bus>>, p_cpu: Rc<RefCell<Cpu>>) {
-        self.p_bus = Some(p_bus);
-        self.p_cpu = Some(p_cpu);
+            o_sample_callback: None,
+        }
     }
 
     pub fn reset(&mut self) {
@@ -101,6 +85,21 @@ impl Dmc {
 
     pub fn set_output_level(&mut self, value: u8) {
         self.output_level = value & 0x7F;
+        self.call_sample_callback();
+    }
+
+    pub fn set_sample_callback(&mut self, o_callback: Option<DmcSampleCallback>) {
+        self.o_sample_callback = o_callback;
+    }
+
+    pub fn take_sample_callback(&mut self) -> Option<DmcSampleCallback> {
+        self.o_sample_callback.take()
+    }
+
+    fn call_sample_callback(&mut self) {
+        if let Some(callback) = &mut self.o_sample_callback {
+            callback(self.output_level);
+        }
     }
 
     pub fn set_sample_address(&mut self, value: u8) {
@@ -111,17 +110,22 @@ impl Dmc {
         self.sample_length = ((value as u16) << 4) | 0x0001;
     }
 
-    fn clock_reader(&mut self) {
+    // Address of the next sample byte, if the memory reader needs to fetch it
+    pub fn get_sample_address(&self) -> Option<u16> {
         if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
-            if let Some(bus) = &self.p_bus {
-                match bus.borrow_mut().read(self.current_address) {
-                    Ok(s) => self.sample_buffer = Some(s),
-                    Err(e) => panic!("{}", e),
-                }
-            } else {
-                panic!("No bus attached to the DMC");
-            }
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    // Fill the sample buffer with the byte read by the memory reader
+    pub fn load_sample(&mut self, sample: u8) {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            self.sample_buffer = Some(sample);
 
+            // The address wraps to 0x8000, where the mapper may have switched another bank
+            // The memory reader goes through the CPU bus, so the samples are read from the current banks
             if self.current_address < 0xFFFF {
                 self.current_address += 1;
             } else {
@@ -149,6 +153,7 @@ impl Dmc {
                 self.output_level -= 2;
             }
             self.output_shift_register >>= 1;
+            self.call_sample_callback();
         }
         self.bits_remaining -= 1;
         if self.bits_remaining == 0 {
@@ -164,20 +169,12 @@ impl Dmc {
     }
 
     pub fn clock(&mut self) {
-        if self.interrupt_flag {
-            if let Some(cpu) = &self.p_cpu {
-                cpu.borrow_mut().interrupt(Interrupt::Irq);
-            } else {
-                panic!("No CPU set for the DMC");
-            }
-        }
         if self.timer != 0 {
             self.timer -= 1;
         } else {
             self.timer = self.rate;
             self.clock_output();
         }
-        self.clock_reader();
     }
 
     pub fn get_output(&self) -> u8 {
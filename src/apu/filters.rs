@@ -1,5 +1,7 @@
-pub trait Filter {
+pub trait Filter: Send + Sync {
     fn process(&mut self, amplitude: f32) -> f32;
+    // Clear the memory of the previous samples
+    fn reset(&mut self);
 }
 
 pub struct LowPassFilter {
@@ -9,7 +11,7 @@ pub struct LowPassFilter {
 
 impl LowPassFilter {
     pub fn new(frequency: u32, sample_rate: f32) -> Self {
-        let rc = 1.0 / (2.0 * std::f32::consts::PI * frequency as f32);
+        let rc = 1.0 / (2.0 * core::f32::consts::PI * frequency as f32);
         let dt = 1.0 / sample_rate;
         let alpha = dt / (rc + dt);
 
@@ -26,6 +28,10 @@ impl Filter for LowPassFilter {
         self.previous_output = processed;
         processed
     }
+
+    fn reset(&mut self) {
+        self.previous_output = 0.0;
+    }
 }
 
 pub struct HighPassFilter {
@@ -36,7 +42,7 @@ pub struct HighPassFilter {
 
 impl HighPassFilter {
     pub fn new(frequency: u32, sample_rate: f32) -> Self {
-        let rc = 1.0 / (2.0 * std::f32::consts::PI * frequency as f32);
+        let rc = 1.0 / (2.0 * core::f32::consts::PI * frequency as f32);
         let dt = 1.0 / sample_rate;
         let alpha = dt / (rc + dt);
 
@@ -55,4 +61,9 @@ impl Filter for HighPassFilter {
         self.previous_output = processed;
         processed
     }
+
+    fn reset(&mut self) {
+        self.previous_output = 0.0;
+        self.previous_input = 0.0;
+    }
 }
@@ -10,6 +10,16 @@ pub struct LengthCounter {
     length_counter: u8,
     length_counter_halt: bool,
     enabled: bool,
+
+    // The writes to the halt flag and the reloads take effect at the end of the CPU cycle,
+    // after the length counter may have been clocked
+    #[serde(default)]
+    o_new_halt: Option<bool>,
+    #[serde(default)]
+    o_reload_value: Option<u8>,
+    // Value of the length counter when the reload was written
+    #[serde(default)]
+    value_before_reload: u8,
 }
 
 impl LengthCounter {
@@ -18,6 +28,9 @@ impl LengthCounter {
             length_counter: 0,
             length_counter_halt: true,
             enabled: true,
+            o_new_halt: None,
+            o_reload_value: None,
+            value_before_reload: 0,
         }
     }
 
@@ -29,12 +42,27 @@ impl LengthCounter {
     }
 
     pub fn set_lenght_counter_halt(&mut self, lenght_counter_halt: bool) {
-        self.length_counter_halt = lenght_counter_halt;
+        self.o_new_halt = Some(lenght_counter_halt);
     }
 
     pub fn set_length_counter(&mut self, value: u8) {
         if self.enabled {
-            self.length_counter = LENGHT_TABLE[value as usize];
+            self.o_reload_value = Some(LENGHT_TABLE[value as usize]);
+            self.value_before_reload = self.length_counter;
+        }
+    }
+
+    // Apply the writes of the current CPU cycle, once the length counter has been clocked
+    // A clock on the same cycle uses the previous halt flag, and a reload is ignored if the
+    // clock decremented the length counter
+    pub fn apply_writes(&mut self) {
+        if let Some(value) = self.o_reload_value.take() {
+            if self.enabled && self.length_counter == self.value_before_reload {
+                self.length_counter = value;
+            }
+        }
+        if let Some(halt) = self.o_new_halt.take() {
+            self.length_counter_halt = halt;
         }
     }
 
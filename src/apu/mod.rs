@@ -9,18 +9,19 @@ mod pulse;
 mod sweep;
 mod triangle;
 
-use std::{cell::RefCell, error::Error, rc::Rc};
+use core::error::Error;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    bus::Bus,
-    cpu::{enums::Interrupt, Cpu},
     errors::{InvalidAPURegisterReadError, InvalidAPURegisterWriteError},
+    nes::DmcSampleCallback,
     state::Stateful,
+    Region,
 };
 
 use self::state::ApuState;
+use crate::prelude::*;
 
 use {
     dmc::Dmc,
@@ -30,11 +31,30 @@ use {
     triangle::Triangle,
 };
 
-const STEP_1: u64 = 7457;
-const STEP_2: u64 = 14913;
-const STEP_3: u64 = 22371;
-const STEP_4: u64 = 29830;
-const STEP_5: u64 = 37281;
+// Frame counter steps (in CPU cycles)
+const NTSC_STEPS: [u64; 5] = [7457, 14913, 22371, 29830, 37281];
+const PAL_STEPS: [u64; 5] = [8313, 16627, 24939, 33254, 41565];
+
+/// Sound channels of the NES APU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApuChannel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+/// Raw outputs of the NES APU sound channels, before mixing and filtering.
+/// Pulse, triangle and noise outputs range from 0 to 15, DMC output from 0 to 127.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApuChannelOutputs {
+    pub pulse1: u8,
+    pub pulse2: u8,
+    pub triangle: u8,
+    pub noise: u8,
+    pub dmc: u8,
+}
 
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 enum Mode {
@@ -43,8 +63,6 @@ enum Mode {
 }
 
 pub struct Apu {
-    p_cpu: Option<Rc<RefCell<Cpu>>>,
-
     pulse1: Pulse,
     pulse2: Pulse,
     triangle: Triangle,
@@ -53,8 +71,15 @@ pub struct Apu {
 
     interrupt_inhibit: bool,
     frame_interrupt: bool,
-
-    sample_rate: u64,
+    // Set when the last clock requested an IRQ interrupt to the CPU
+    irq_requested: bool,
+
+    // CPU cycles between two samples, the real time period is scaled by the speed multiplier
+    // so that fast forwarding does not produce more samples than can be played
+    real_time_sample_period: f32,
+    speed_multiplier: f32,
+    sample_period: u64,
+    steps: [u64; 5],
     frame_clock: u64,
     cycles_before_frame_clock_reset: Option<u64>,
     mode: Mode,
@@ -62,30 +87,24 @@ pub struct Apu {
 
     last_4017_value: u8,
 
-    pulse_table: [f32; 31],
-    tnd_table: [f32; 203],
-
     filters: [Box<dyn Filter>; 3],
-}
 
-impl Apu {
-    pub fn new(ppu_clock_frequency: u64) -> Self {
-        let clock_frequency = ppu_clock_frequency / 3;
-        let sample_rate = clock_frequency as f32 / 44_100.0;
+    // Channels muted at the output, indexed by ApuChannel
+    enabled_channels: [bool; 5],
 
-        let mut pulse_table = [0.0; 31];
-        for (i, elt) in pulse_table.iter_mut().enumerate() {
-            *elt = 95.52 / (8128.0 / i as f32 + 100.0);
-        }
+    // Stereo output, with the pan of each channel from -1.0 (left) to 1.0 (right)
+    stereo_output: bool,
+    channel_pans: [f32; 5],
+    left_filters: [Box<dyn Filter>; 3],
+    right_filters: [Box<dyn Filter>; 3],
+    stereo_sample: (f32, f32),
+}
 
-        let mut tnd_table = [0.0; 203];
-        for (i, elt) in tnd_table.iter_mut().enumerate() {
-            *elt = 163.67 / (24329.0 / i as f32 + 100.0);
-        }
+impl Apu {
+    pub fn new(cpu_clock_frequency: u64, sample_rate: u32, region: Region) -> Self {
+        let sample_period = cpu_clock_frequency as f32 / sample_rate as f32;
 
         Apu {
-            p_cpu: None,
-
             pulse1: Pulse::new(false),
             pulse2: Pulse::new(true),
             triangle: Triangle::new(),
@@ -94,8 +113,15 @@ impl Apu {
 
             interrupt_inhibit: false,
             frame_interrupt: false,
-
-            sample_rate: sample_rate as u64,
+            irq_requested: false,
+
+            real_time_sample_period: sample_period,
+            speed_multiplier: 1.0,
+            sample_period: sample_period as u64,
+            steps: match region {
+                Region::Ntsc => NTSC_STEPS,
+                Region::Pal => PAL_STEPS,
+            },
             frame_clock: 0,
             cycles_before_frame_clock_reset: None,
             mode: Mode::Step4,
@@ -103,39 +129,97 @@ impl Apu {
 
             last_4017_value: 0,
 
-            pulse_table,
-            tnd_table,
+            filters: new_filters(sample_rate),
+
+            enabled_channels: [true; 5],
 
-            filters: [
-                Box::new(HighPassFilter::new(90, sample_rate)),
-                Box::new(HighPassFilter::new(440, sample_rate)),
-                Box::new(LowPassFilter::new(14000, sample_rate)),
-            ],
+            stereo_output: false,
+            channel_pans: [0.0; 5],
+            left_filters: new_filters(sample_rate),
+            right_filters: new_filters(sample_rate),
+            stereo_sample: (0.0, 0.0),
         }
     }
 
-    pub fn from_state(state: &ApuState, ppu_clock_frequency: u64) -> Self {
-        let mut apu = Apu::new(ppu_clock_frequency);
+    pub fn from_state(
+        state: &ApuState,
+        cpu_clock_frequency: u64,
+        sample_rate: u32,
+        region: Region,
+    ) -> Self {
+        let mut apu = Apu::new(cpu_clock_frequency, sample_rate, region);
         apu.set_state(state);
         apu
     }
 
-    pub fn attach_bus_and_cpu(&mut self, p_bus: Rc<RefCell<Bus>>, p_cpu: Rc<RefCell<Cpu>>) {
-        self.p_cpu = Some(p_cpu.clone());
-        self.dmc.attach_bus_and_cpu(p_bus, p_cpu);
+    pub fn set_speed_multiplier(&mut self, multiplier: f32) {
+        self.speed_multiplier = multiplier.max(1.0);
+        self.sample_period = (self.real_time_sample_period * self.speed_multiplier) as u64;
+    }
+
+    pub fn get_speed_multiplier(&self) -> f32 {
+        self.speed_multiplier
+    }
+
+    pub fn is_irq_requested(&self) -> bool {
+        self.irq_requested
+    }
+
+    pub fn get_dmc_sample_address(&self) -> Option<u16> {
+        self.dmc.get_sample_address()
+    }
+
+    pub fn load_dmc_sample(&mut self, sample: u8) {
+        self.dmc.load_sample(sample);
+    }
+
+    pub fn set_dmc_sample_callback(&mut self, o_callback: Option<DmcSampleCallback>) {
+        self.dmc.set_sample_callback(o_callback);
+    }
+
+    pub fn take_dmc_sample_callback(&mut self) -> Option<DmcSampleCallback> {
+        self.dmc.take_sample_callback()
+    }
+
+    pub fn set_channel_enabled(&mut self, channel: ApuChannel, enabled: bool) {
+        self.enabled_channels[channel as usize] = enabled;
+    }
+
+    pub fn get_enabled_channels(&self) -> [bool; 5] {
+        self.enabled_channels
+    }
+
+    pub fn set_enabled_channels(&mut self, enabled_channels: [bool; 5]) {
+        self.enabled_channels = enabled_channels;
+    }
+
+    pub fn set_stereo_output(&mut self, stereo_output: bool) {
+        self.stereo_output = stereo_output;
+    }
+
+    pub fn is_stereo_output(&self) -> bool {
+        self.stereo_output
+    }
+
+    pub fn set_channel_pans(&mut self, channel_pans: [f32; 5]) {
+        self.channel_pans = channel_pans;
+    }
+
+    pub fn get_channel_pans(&self) -> [f32; 5] {
+        self.channel_pans
+    }
+
+    // Only updated when the stereo output is enabled
+    pub fn get_stereo_sample(&self) -> (f32, f32) {
+        self.stereo_sample
     }
 
     pub fn read_register(&mut self, address: u16) -> Result<u8, Box<dyn Error>> {
         match address {
             0x4015 => {
-                let mut status: u8 = 0;
-                status |= !self.pulse1.length_counter.is_channel_silenced() as u8;
-                status |= (!self.pulse2.length_counter.is_channel_silenced() as u8) << 1;
-                status |= (!self.triangle.length_counter.is_channel_silenced() as u8) << 2;
-                status |= (!self.noise.length_counter.is_channel_silenced() as u8) << 3;
-                status |= (self.dmc.is_active() as u8) << 4;
-                status |= (self.frame_interrupt as u8) << 6;
-                status |= (self.dmc.interrupt_flag as u8) << 7;
+                let status = self.get_status();
+                // Only the frame interrupt is acknowledged, the DMC interrupt is acknowledged
+                // by writing to 0x4010 or 0x4015
                 self.frame_interrupt = false;
                 Ok(status)
             }
@@ -145,21 +229,24 @@ impl Apu {
 
     pub fn read_only_register(&self, address: u16) -> Result<u8, Box<dyn Error>> {
         match address {
-            0x4015 => {
-                let mut status: u8 = 0;
-                status |= !self.pulse1.length_counter.is_channel_silenced() as u8;
-                status |= (!self.pulse2.length_counter.is_channel_silenced() as u8) << 1;
-                status |= (!self.triangle.length_counter.is_channel_silenced() as u8) << 2;
-                status |= (!self.noise.length_counter.is_channel_silenced() as u8) << 3;
-                status |= (self.dmc.is_active() as u8) << 4;
-                status |= (self.frame_interrupt as u8) << 6;
-                status |= (self.dmc.interrupt_flag as u8) << 7;
-                Ok(status)
-            }
+            0x4015 => Ok(self.get_status()),
             _ => Err(Box::new(InvalidAPURegisterReadError(address))),
         }
     }
 
+    // Value read at 0x4015, without acknowledging the frame interrupt
+    pub fn get_status(&self) -> u8 {
+        let mut status: u8 = 0;
+        status |= !self.pulse1.length_counter.is_channel_silenced() as u8;
+        status |= (!self.pulse2.length_counter.is_channel_silenced() as u8) << 1;
+        status |= (!self.triangle.length_counter.is_channel_silenced() as u8) << 2;
+        status |= (!self.noise.length_counter.is_channel_silenced() as u8) << 3;
+        status |= (self.dmc.is_active() as u8) << 4;
+        status |= (self.frame_interrupt as u8) << 6;
+        status |= (self.dmc.interrupt_flag as u8) << 7;
+        status
+    }
+
     pub fn write_register(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
         match address {
             0x4000 => self.pulse1.set_control(value),
@@ -216,6 +303,19 @@ impl Apu {
         self.frame_interrupt = false;
         self.triangle.reset();
         self.dmc.reset();
+        self.flush_filters();
+    }
+
+    // Start the filters from silence, so that no click is heard when the audio resumes
+    pub fn flush_filters(&mut self) {
+        for filter in self
+            .filters
+            .iter_mut()
+            .chain(self.left_filters.iter_mut())
+            .chain(self.right_filters.iter_mut())
+        {
+            filter.reset();
+        }
     }
 
     fn clock_quarter_frame(&mut self) {
@@ -226,6 +326,13 @@ impl Apu {
         self.triangle.clock_linear_counter();
     }
 
+    fn apply_length_counter_writes(&mut self) {
+        self.pulse1.length_counter.apply_writes();
+        self.pulse2.length_counter.apply_writes();
+        self.triangle.length_counter.apply_writes();
+        self.noise.length_counter.apply_writes();
+    }
+
     fn clock_half_frame(&mut self) {
         // Clock envelope, triangle linear counter, lenght counter, and sweep units
         self.clock_quarter_frame();
@@ -238,6 +345,8 @@ impl Apu {
     }
 
     pub fn clock(&mut self) -> Option<f32> {
+        self.irq_requested = false;
+
         if let Some(c) = self.cycles_before_frame_clock_reset {
             if c == 0 {
                 self.cycles_before_frame_clock_reset = None;
@@ -250,31 +359,29 @@ impl Apu {
         if self.instant_clock {
             self.instant_clock = false;
             self.clock_half_frame();
+            self.apply_length_counter_writes();
             return None;
         }
 
-        if self.frame_clock == STEP_1 || self.frame_clock == STEP_3 {
+        if self.frame_clock == self.steps[0] || self.frame_clock == self.steps[2] {
             self.clock_quarter_frame();
         }
-        if self.frame_clock == STEP_2 {
+        if self.frame_clock == self.steps[1] {
             self.clock_half_frame();
         }
 
-        if self.frame_clock == STEP_4 && self.mode == Mode::Step4 {
+        if self.frame_clock == self.steps[3] && self.mode == Mode::Step4 {
             self.clock_half_frame();
             if !self.interrupt_inhibit {
                 self.frame_interrupt = true;
-                if let Some(cpu) = &self.p_cpu {
-                    cpu.borrow_mut().interrupt(Interrupt::Irq);
-                } else {
-                    panic!("No CPU attached to the APU");
-                }
+                self.irq_requested = true;
             }
             self.frame_clock = 0;
-        } else if self.frame_clock == STEP_5 && self.mode == Mode::Step5 {
+        } else if self.frame_clock == self.steps[4] && self.mode == Mode::Step5 {
             self.clock_half_frame();
             self.frame_clock = 0;
         }
+        self.apply_length_counter_writes();
 
         if self.frame_clock % 2 == 0 {
             self.pulse1.clock();
@@ -282,31 +389,82 @@ impl Apu {
             self.noise.clock();
         }
         self.triangle.clock();
+        if self.dmc.interrupt_flag {
+            self.irq_requested = true;
+        }
         self.dmc.clock();
 
         self.frame_clock = self.frame_clock.wrapping_add(1);
 
-        // Push the current amplitude to the sample buffer at a rate that is close to the 44100Hz required by sdl2
+        // Push the current amplitude to the sample buffer at a rate that is close to the requested sample rate
         // If we produce less samples, the sound will pop and it is horrible to the ear. Instead, producing
         // a bit to much samples may result in a lower tune, but it is better than poping sounds.
-        if self.frame_clock % self.sample_rate as u64 == 0 {
-            return Some(self.apply_filters(self.get_amplitude()));
+        if self.frame_clock.is_multiple_of(self.sample_period) {
+            if self.stereo_output {
+                // A centered channel is played at full volume on both sides
+                let left_gains = self.channel_pans.map(|pan| (1.0 - pan).min(1.0));
+                let right_gains = self.channel_pans.map(|pan| (1.0 + pan).min(1.0));
+                let left = self.get_amplitude(left_gains);
+                let right = self.get_amplitude(right_gains);
+                self.stereo_sample = (
+                    apply_filters(&mut self.left_filters, left),
+                    apply_filters(&mut self.right_filters, right),
+                );
+            }
+            let amplitude = self.get_amplitude([1.0; 5]);
+            return Some(apply_filters(&mut self.filters, amplitude));
         }
 
         None
     }
 
-    fn get_amplitude(&self) -> f32 {
-        let pulse_out = (self.pulse1.get_output() + self.pulse2.get_output()) as usize;
-        let tnd_out = (3 * self.triangle.get_output()
-            + 2 * self.noise.get_output()
-            + self.dmc.get_output()) as usize;
-        self.pulse_table[pulse_out] + self.tnd_table[tnd_out]
+    // Get the output of a channel, or 0 if it has been muted
+    fn get_channel_output(&self, channel: ApuChannel) -> u8 {
+        if !self.enabled_channels[channel as usize] {
+            return 0;
+        }
+        match channel {
+            ApuChannel::Pulse1 => self.pulse1.get_output(),
+            ApuChannel::Pulse2 => self.pulse2.get_output(),
+            ApuChannel::Triangle => self.triangle.get_output(),
+            ApuChannel::Noise => self.noise.get_output(),
+            ApuChannel::Dmc => self.dmc.get_output(),
+        }
     }
 
-    fn apply_filters(&mut self, amplitude: f32) -> f32 {
-        self.filters
-            .iter_mut()
-            .fold(amplitude, |acc, filter| filter.process(acc))
+    pub fn get_channel_outputs(&self) -> ApuChannelOutputs {
+        ApuChannelOutputs {
+            pulse1: self.get_channel_output(ApuChannel::Pulse1),
+            pulse2: self.get_channel_output(ApuChannel::Pulse2),
+            triangle: self.get_channel_output(ApuChannel::Triangle),
+            noise: self.get_channel_output(ApuChannel::Noise),
+            dmc: self.get_channel_output(ApuChannel::Dmc),
+        }
+    }
+
+    // Mix the outputs of the channels, scaled by the given gains
+    fn get_amplitude(&self, gains: [f32; 5]) -> f32 {
+        let output =
+            |channel: ApuChannel| self.get_channel_output(channel) as f32 * gains[channel as usize];
+        let pulse_out = output(ApuChannel::Pulse1) + output(ApuChannel::Pulse2);
+        let tnd_out = 3.0 * output(ApuChannel::Triangle)
+            + 2.0 * output(ApuChannel::Noise)
+            + output(ApuChannel::Dmc);
+        // The outputs are 0 for silent channels, as the divisions give an infinity
+        95.52 / (8128.0 / pulse_out + 100.0) + 163.67 / (24329.0 / tnd_out + 100.0)
     }
 }
+
+fn new_filters(sample_rate: u32) -> [Box<dyn Filter>; 3] {
+    [
+        Box::new(HighPassFilter::new(90, sample_rate as f32)),
+        Box::new(HighPassFilter::new(440, sample_rate as f32)),
+        Box::new(LowPassFilter::new(14000, sample_rate as f32)),
+    ]
+}
+
+fn apply_filters(filters: &mut [Box<dyn Filter>; 3], amplitude: f32) -> f32 {
+    filters
+        .iter_mut()
+        .fold(amplitude, |acc, filter| filter.process(acc))
+}
@@ -47,10 +47,12 @@ impl Noise {
         self.envelope.start_flag = true;
     }
 
+    // The feedback comes from bit 1 in the long mode (32767 steps), and from bit 6
+    // in the short mode (93 or 31 steps depending on the starting value)
     fn clock_shift(&mut self) {
         let feedback = (self.shift & 0x0001)
             ^ if self.mode {
-                (self.shift & 0x0020) >> 5
+                (self.shift & 0x0040) >> 6
             } else {
                 (self.shift & 0x0002) >> 1
             };
@@ -67,7 +69,8 @@ impl Noise {
     }
 
     pub fn get_output(&self) -> u8 {
-        if self.shift & 0x01 > 0 && !self.length_counter.is_channel_silenced() {
+        // The channel is muted when the bit 0 of the shift register is set
+        if self.shift & 0x01 == 0 && !self.length_counter.is_channel_silenced() {
             self.envelope.get_output()
         } else {
             0
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::state::Stateful;
 
-use super::{dmc::Dmc, noise::Noise, pulse::Pulse, triangle::Triangle, Mode};
+use super::{noise::Noise, pulse::Pulse, triangle::Triangle, Mode};
 
 #[derive(Serialize, Deserialize)]
 pub struct DmcState {
@@ -31,7 +31,6 @@ pub struct ApuState {
     dmc: DmcState,
     interrupt_inhibit: bool,
     frame_interrupt: bool,
-    sample_rate: u64,
     frame_clock: u64,
     mode: Mode,
     instant_clock: bool,
@@ -50,7 +49,6 @@ impl Stateful for super::Apu {
             dmc: self.dmc.get_state(),
             interrupt_inhibit: self.interrupt_inhibit,
             frame_interrupt: self.frame_interrupt,
-            sample_rate: self.sample_rate,
             frame_clock: self.frame_clock,
             mode: self.mode.clone(),
             instant_clock: self.instant_clock,
@@ -63,10 +61,9 @@ impl Stateful for super::Apu {
         self.pulse2 = state.pulse2.clone();
         self.triangle = state.triangle.clone();
         self.noise = state.noise.clone();
-        self.dmc = Dmc::from_state(&state.dmc);
+        self.dmc.set_state(&state.dmc);
         self.interrupt_inhibit = state.interrupt_inhibit;
         self.frame_interrupt = state.frame_interrupt;
-        self.sample_rate = state.sample_rate;
         self.frame_clock = state.frame_clock;
         self.mode = state.mode.clone();
         self.instant_clock = state.instant_clock;
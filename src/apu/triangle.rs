@@ -7,6 +7,9 @@ const TRIANGLE_STEPS: [u8; 32] = [
     13, 14, 15,
 ];
 
+// Periods below this one produce ultrasonic frequencies, the sequencer is not clocked
+const MIN_PERIOD: u16 = 2;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Triangle {
     pub length_counter: LengthCounter,
@@ -73,7 +76,10 @@ impl Triangle {
             self.timer -= 1;
         } else {
             self.timer = self.period + 1;
-            if self.linear_counter != 0 && !self.length_counter.is_channel_silenced() {
+            if self.linear_counter != 0
+                && !self.length_counter.is_channel_silenced()
+                && self.period >= MIN_PERIOD
+            {
                 self.clock_sequencer();
             }
         }
@@ -1,6 +1,6 @@
 use std::process::exit;
 
-use nesmulator_core::{nes::NES, Config};
+use nesmulator_core::{nes::NES, Config, CpuLog};
 
 const NESTEST_ROM_PATH: &str = "../ROM/Tests/nestest.nes";
 
@@ -15,7 +15,7 @@ fn nestest_automation(run_once: bool) {
     // Instantiate a NES and connect the nestest ROM file
     let mut nes = NES::from_config(Config {
         display_cpu_logs: false, // Change to true to follow each CPU instruction
-        palette_path: None,
+        ..Config::default()
     });
     if let Err(e) = nes.insert_cartdrige(rom_path) {
         println!("Error parsing ROM: {e}");
@@ -26,13 +26,23 @@ fn nestest_automation(run_once: bool) {
     nes.set_program_counter_at(0xC000);
 
     let mut cycle_count = 0;
+    let mut last_logged_clock = None;
 
     // Main emulation loop
     // This only clocks the NES as fast as possible.
     // For an example of a full GUI using this crate, check out https://github.com/AntoineRR/nesmulator-gui
     // The loop will stop at the end of the nestest ROM or start it again.
     loop {
-        nes.clock();
+        if let Err(e) = nes.clock() {
+            println!("Emulation error: {e}");
+            exit(1);
+        }
+        if let Some(log) = nes.get_last_cpu_log() {
+            if last_logged_clock != Some(log.total_clock) {
+                println!("{}", format_cpu_log(&log));
+                last_logged_clock = Some(log.total_clock);
+            }
+        }
         cycle_count += 1;
         if cycle_count % (26560 * 3) == 0 {
             if run_once {
@@ -42,7 +52,30 @@ fn nestest_automation(run_once: bool) {
                 nes.insert_cartdrige(NESTEST_ROM_PATH).unwrap();
                 nes.set_program_counter_at(0xC000);
                 cycle_count = 0;
+                last_logged_clock = None;
             }
         }
     }
 }
+
+// Format a CPU log the same way as the nestest logs
+fn format_cpu_log(log: &CpuLog) -> String {
+    let mut instruction_bytes = format!("{:02X} ", log.opcode);
+    for operand in log.operands.iter() {
+        instruction_bytes.push_str(&format!("{:02X} ", operand));
+    }
+    format!(
+        "{:04X}  {:<9} {:<32}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+        log.pc,
+        instruction_bytes,
+        log.disassembly,
+        log.a,
+        log.x,
+        log.y,
+        log.p,
+        log.sp,
+        log.scanline,
+        log.cycle,
+        log.total_clock
+    )
+}
@@ -2,19 +2,20 @@
 
 // ===== IMPORTS =====
 
-use std::cell::RefCell;
-use std::error::Error;
-use std::rc::Rc;
+use alloc::collections::BTreeMap;
+use core::error::Error;
 
 use log::debug;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use crate::apu::Apu;
-use crate::cartridge::mapper::Mapper;
-use crate::controllers::Controller;
+use crate::cheats::{Cheat, CheatHandle};
+use crate::controllers::{Controller, Zapper};
 use crate::ppu::Ppu;
+use crate::prelude::*;
 use crate::state::Stateful;
+use crate::RamPattern;
 
 // ===== CONSTANTS =====
 
@@ -22,123 +23,227 @@ pub const STACK_OFFSET: u16 = 0x100;
 
 // ===== TYPE ALIAS =====
 
-type MapperRc = Rc<RefCell<Box<dyn Mapper>>>;
+pub type WriteWatch = Box<dyn FnMut(u16, u8) + Send + Sync>;
 
 // ===== BUS STRUCT =====
 
 pub struct Bus {
     cpu_ram: [u8; 0x0800],
-    o_p_mapper: Option<MapperRc>,
-    p_ppu: Rc<RefCell<Ppu>>,
-    p_apu: Rc<RefCell<Apu>>,
+    ram_startup_pattern: RamPattern,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    // Last value read or written on the bus, returned by open bus reads
+    last_bus_value: u8,
+
+    // Game Genie cheats patching the data read from the cartridge
+    cheats: BTreeMap<CheatHandle, Cheat>,
 
     controllers: [Controller; 2],
+    // Zapper plugged in the second controller port
+    o_zapper: Option<Zapper>,
+    // Controller port read by the current CPU instruction
+    o_controller_read: Option<usize>,
+
+    // Callbacks called when writing at specific addresses
+    write_watches: BTreeMap<u16, WriteWatch>,
+
+    // Number of reads and writes on each 256 bytes page, counted when profiling is enabled
+    access_profiling: bool,
+    access_histogram: [(u64, u64); 256],
 }
 
 impl Bus {
-    pub fn new(p_ppu: Rc<RefCell<Ppu>>, p_apu: Rc<RefCell<Apu>>) -> Self {
+    pub fn new(ppu: Ppu, apu: Apu, ram_startup_pattern: RamPattern) -> Self {
         Bus {
-            cpu_ram: [0; 0x0800],
-            o_p_mapper: None,
-            p_ppu,
-            p_apu,
+            cpu_ram: get_startup_ram(&ram_startup_pattern),
+            ram_startup_pattern,
+            ppu,
+            apu,
+            last_bus_value: 0,
+
+            cheats: BTreeMap::new(),
 
             controllers: [Controller::new(); 2],
+            o_zapper: None,
+            o_controller_read: None,
+
+            write_watches: BTreeMap::new(),
+
+            access_profiling: false,
+            access_histogram: [(0, 0); 256],
         }
     }
 
-    pub fn from_state(state: &BusState, p_ppu: Rc<RefCell<Ppu>>, p_apu: Rc<RefCell<Apu>>) -> Self {
-        let mut bus = Bus::new(p_ppu, p_apu);
-        bus.set_state(state);
-        bus
+    pub fn add_cheat(&mut self, handle: CheatHandle, cheat: Cheat) {
+        self.cheats.insert(handle, cheat);
     }
 
-    pub fn set_mapper(&mut self, p_mapper: MapperRc) {
-        self.o_p_mapper = Some(p_mapper);
+    pub fn remove_cheat(&mut self, handle: CheatHandle) -> Option<Cheat> {
+        self.cheats.remove(&handle)
+    }
+
+    // Patch the data read from the cartridge with the active cheats
+    fn apply_cheats(&self, address: u16, data: u8) -> u8 {
+        self.cheats
+            .values()
+            .fold(data, |data, cheat| cheat.apply(address, data))
+    }
+
+    pub fn get_last_bus_value(&self) -> u8 {
+        self.last_bus_value
     }
 
     pub fn get_scanline(&self) -> u16 {
-        self.p_ppu.borrow().get_scanline()
+        self.ppu.get_scanline()
     }
 
     pub fn get_cycles(&self) -> u16 {
-        self.p_ppu.borrow().get_cycles()
+        self.ppu.get_cycles()
+    }
+
+    // Fill the CPU RAM with its startup pattern, as after a power cycle
+    pub fn fill_startup_ram(&mut self) {
+        self.cpu_ram = get_startup_ram(&self.ram_startup_pattern);
+    }
+
+    pub fn clear_ram(&mut self) {
+        self.cpu_ram = [0; 0x800];
     }
 
     pub fn set_input(&mut self, id: usize, input: u8) {
         self.controllers[id].buffer = input;
     }
 
+    pub fn get_input(&self, id: usize) -> u8 {
+        self.controllers[id].buffer
+    }
+
+    pub fn set_zapper(&mut self, o_zapper: Option<Zapper>) {
+        self.o_zapper = o_zapper;
+    }
+
+    pub fn set_write_watch(&mut self, address: u16, callback: WriteWatch) {
+        self.write_watches.insert(address, callback);
+    }
+
+    pub fn remove_write_watch(&mut self, address: u16) {
+        self.write_watches.remove(&address);
+    }
+
+    // Enabling the profiling starts counting from zero
+    pub fn enable_access_profiling(&mut self, enabled: bool) {
+        if enabled && !self.access_profiling {
+            self.access_histogram = [(0, 0); 256];
+        }
+        self.access_profiling = enabled;
+    }
+
+    pub fn get_access_histogram(&self) -> [(u64, u64); 256] {
+        self.access_histogram
+    }
+
+    // Reads the second controller port, which may have a zapper plugged in
+    fn read_second_port(&mut self) -> u8 {
+        match self.o_zapper {
+            Some(zapper) => {
+                let light_detected = self.ppu.is_light_detected(zapper.x, zapper.y);
+                zapper.read(light_detected)
+            }
+            None => self.controllers[1].check_shifter(),
+        }
+    }
+
+    pub fn clear_controller_read(&mut self) {
+        self.o_controller_read = None;
+    }
+
+    // Clock the controller port read by the current CPU instruction again, as when the read
+    // is repeated by the CPU being halted
+    pub fn repeat_controller_read(&mut self) {
+        match self.o_controller_read {
+            Some(0) => {
+                self.controllers[0].check_shifter();
+            }
+            Some(_) => {
+                self.read_second_port();
+            }
+            None => (),
+        }
+    }
+
     // Reads data from the bus at the specified address
     pub fn read(&mut self, address: u16) -> Result<u8, Box<dyn Error>> {
-        match address {
+        if self.access_profiling {
+            self.access_histogram[(address >> 8) as usize].0 += 1;
+        }
+        let data = match address {
             // 0x0000 - 0x07FF / 2KB CPU RAM
-            0x0000..=0x7FF => Ok(self.cpu_ram[address as usize]),
+            0x0000..=0x7FF => self.cpu_ram[address as usize],
             // 0x0800 - 0x1FFF / CPU RAM Mirrors
-            0x0800..=0x1FFF => Ok(self.cpu_ram[(address & 0x07FF) as usize]),
+            0x0800..=0x1FFF => self.cpu_ram[(address & 0x07FF) as usize],
             // 0x2000 - 0x2007 / NES PPU Registers
-            0x2000..=0x2007 => match self.p_ppu.borrow_mut().read_register(address) {
-                Ok(data) => Ok(data),
+            0x2000..=0x2007 => match self.ppu.read_register(address) {
+                Ok(data) => data,
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    0
                 }
             },
             // 0x2008 - 0x3FFF / NES PPU Registers Mirrors
-            0x2008..=0x3FFF => match self.p_ppu.borrow_mut().read_register(address & 0x2007) {
-                Ok(data) => Ok(data),
+            0x2008..=0x3FFF => match self.ppu.read_register(address & 0x2007) {
+                Ok(data) => data,
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    0
                 }
             },
             // 0x4000 - 0x4013 / NES APU I/O Registers
-            0x4000..=0x4013 => match self.p_apu.borrow_mut().read_register(address) {
-                Ok(data) => Ok(data),
+            0x4000..=0x4013 => match self.apu.read_register(address) {
+                Ok(data) => data,
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    self.last_bus_value
                 }
             },
             // 0x4014 / NES PPU Register
-            0x4014 => match self.p_ppu.borrow_mut().read_register(address) {
-                Ok(data) => Ok(data),
+            0x4014 => match self.ppu.read_register(address) {
+                Ok(data) => data,
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    self.last_bus_value
                 }
             },
-            // 0x4015 / NES APU Register
-            0x4015 => match self.p_apu.borrow_mut().read_register(address) {
-                Ok(data) => Ok(data),
+            // 0x4015 / NES APU Register, bit 5 is open bus
+            0x4015 => match self.apu.read_register(address) {
+                Ok(data) => data | (self.last_bus_value & 0x20),
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    self.last_bus_value
                 }
             },
-            // 0x4016 / First controller
-            0x4016 => Ok(self.controllers[0].check_shifter()),
-            // 0x4017 / Second controller
-            0x4017 => Ok(self.controllers[1].check_shifter()),
-            // 0x4018 - 0x4020 / I/O Refisters
-            0x4018..=0x4020 => Ok(0),
-            // 0x4021 - 0xFFFF / Handled by the mapper
-            0x4021..=0xFFFF => {
-                match self
-                    .o_p_mapper
-                    .as_ref()
-                    .unwrap()
-                    .borrow()
-                    .prg_rom_read(address)
-                {
-                    Ok(data) => Ok(data),
-                    Err(e) => {
-                        debug!("{}", e);
-                        Ok(0)
-                    }
-                }
+            // 0x4016 / First controller, upper bits are open bus
+            0x4016 => {
+                self.o_controller_read = Some(0);
+                self.controllers[0].check_shifter() | (self.last_bus_value & 0xE0)
             }
-        }
+            // 0x4017 / Second controller, upper bits are open bus
+            0x4017 => {
+                self.o_controller_read = Some(1);
+                self.read_second_port() | (self.last_bus_value & 0xE0)
+            }
+            // 0x4018 - 0x401F / I/O Refisters
+            0x4018..=0x401F => self.last_bus_value,
+            // 0x4020 - 0xFFFF / Handled by the mapper
+            0x4020..=0xFFFF => match self.ppu.get_mapper().unwrap().prg_rom_read(address) {
+                Ok(data) => self.apply_cheats(address, data),
+                Err(e) => {
+                    debug!("{}", e);
+                    self.last_bus_value
+                }
+            },
+        };
+        self.last_bus_value = data;
+        Ok(data)
     }
 
     // Used for debugging
@@ -151,7 +256,7 @@ impl Bus {
             // 0x0800 - 0x1FFF / CPU RAM Mirrors
             0x0800..=0x1FFF => Ok(self.cpu_ram[(address & 0x07FF) as usize]),
             // 0x2000 - 0x2007 / NES PPU Registers
-            0x2000..=0x2007 => match self.p_ppu.borrow().read_only_register(address) {
+            0x2000..=0x2007 => match self.ppu.read_only_register(address) {
                 Ok(data) => Ok(data),
                 Err(e) => {
                     debug!("{}", e);
@@ -159,7 +264,7 @@ impl Bus {
                 }
             },
             // 0x2008 - 0x3FFF / NES PPU Registers Mirrors
-            0x2008..=0x3FFF => match self.p_ppu.borrow().read_only_register(address & 0x2007) {
+            0x2008..=0x3FFF => match self.ppu.read_only_register(address & 0x2007) {
                 Ok(data) => Ok(data),
                 Err(e) => {
                     debug!("{}", e);
@@ -167,56 +272,56 @@ impl Bus {
                 }
             },
             // 0x4000 - 0x4013 / NES APU I/O Registers
-            0x4000..=0x4013 => match self.p_apu.borrow().read_only_register(address) {
+            0x4000..=0x4013 => match self.apu.read_only_register(address) {
                 Ok(data) => Ok(data),
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    Ok(self.last_bus_value)
                 }
             },
             // 0x4014 / NES PPU Register
-            0x4014 => match self.p_ppu.borrow().read_only_register(address) {
+            0x4014 => match self.ppu.read_only_register(address) {
                 Ok(data) => Ok(data),
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    Ok(self.last_bus_value)
                 }
             },
-            // 0x4015 / NES APU Register
-            0x4015 => match self.p_apu.borrow().read_only_register(address) {
-                Ok(data) => Ok(data),
+            // 0x4015 / NES APU Register, bit 5 is open bus
+            0x4015 => match self.apu.read_only_register(address) {
+                Ok(data) => Ok(data | (self.last_bus_value & 0x20)),
                 Err(e) => {
                     debug!("{}", e);
-                    Ok(0)
+                    Ok(self.last_bus_value)
                 }
             },
             // 0x4016 / First controller
-            0x4016 => Ok(0),
+            0x4016 => Ok(self.last_bus_value & 0xE0),
             // 0x4017 / Second controller
-            0x4017 => Ok(0),
-            // 0x4018 - 0x4020 / I/O Refisters
-            0x4018..=0x4020 => Ok(0),
-            // 0x4021 - 0xFFFF / Handled by the mapper
-            0x4021..=0xFFFF => {
-                match self
-                    .o_p_mapper
-                    .as_ref()
-                    .unwrap()
-                    .borrow()
-                    .prg_rom_read(address)
-                {
-                    Ok(data) => Ok(data),
-                    Err(e) => {
-                        debug!("{}", e);
-                        Ok(0)
-                    }
+            0x4017 => Ok(self.last_bus_value & 0xE0),
+            // 0x4018 - 0x401F / I/O Refisters
+            0x4018..=0x401F => Ok(self.last_bus_value),
+            // 0x4020 - 0xFFFF / Handled by the mapper
+            0x4020..=0xFFFF => match self.ppu.get_mapper().unwrap().prg_rom_read(address) {
+                Ok(data) => Ok(self.apply_cheats(address, data)),
+                Err(e) => {
+                    debug!("{}", e);
+                    Ok(self.last_bus_value)
                 }
-            }
+            },
         }
     }
 
     // Writes data to the bus at the specified address
     pub fn write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        if self.access_profiling {
+            self.access_histogram[(address >> 8) as usize].1 += 1;
+        }
+        self.last_bus_value = value;
+        if let Some(callback) = self.write_watches.get_mut(&address) {
+            callback(address, value);
+        }
+
         match address {
             // 0x0000 - 0x07FF / 2KB CPU RAM
             0x0000..=0x7FF => self.cpu_ram[address as usize] = value,
@@ -224,62 +329,54 @@ impl Bus {
             0x0800..=0x1FFF => self.cpu_ram[(address & 0x07FF) as usize] = value,
             // 0x2000 - 0x2007 / NES PPU Registers
             0x2000..=0x2007 => {
-                if let Err(e) = self.p_ppu.borrow_mut().write_register(address, value) {
+                if let Err(e) = self.ppu.write_register(address, value) {
                     debug!("{}", e);
                 }
             }
             // 0x2008 - 0x3FFF / NES PPU Registers Mirrors
             0x2008..=0x3FFF => {
-                if let Err(e) = self
-                    .p_ppu
-                    .borrow_mut()
-                    .write_register(address & 0x2007, value)
-                {
+                if let Err(e) = self.ppu.write_register(address & 0x2007, value) {
                     debug!("{}", e);
                 }
             }
             // 0x4000 - 0x4013 / NES APU I/O Registers
             0x4000..=0x4013 => {
-                if let Err(e) = self.p_apu.borrow_mut().write_register(address, value) {
+                if let Err(e) = self.apu.write_register(address, value) {
                     debug!("{}", e);
                 }
             }
             // 0x4014 / NES PPU Register
             0x4014 => {
-                if let Err(e) = self.p_ppu.borrow_mut().write_register(address, value) {
+                if let Err(e) = self.ppu.write_register(address, value) {
                     debug!("{}", e);
                 }
             }
             // 0x4015 / NES APU Register
             0x4015 => {
-                if let Err(e) = self.p_apu.borrow_mut().write_register(address, value) {
+                if let Err(e) = self.apu.write_register(address, value) {
                     debug!("{}", e);
                 }
             }
-            // 0x4016 / First controller
+            // 0x4016 / Strobe of both controllers
             0x4016 => {
-                if (value & 0x01) > 0 {
-                    self.controllers[0].update_shifter();
+                for controller in self.controllers.iter_mut() {
+                    controller.write_strobe(value);
                 }
             }
-            // 0x4017 / Second controller + NES APU Register
+            // 0x4017 / NES APU Register
             0x4017 => {
-                if let Err(e) = self.p_apu.borrow_mut().write_register(address, value) {
+                if let Err(e) = self.apu.write_register(address, value) {
                     debug!("{}", e);
                 }
-                if (value & 0x01) > 0 {
-                    self.controllers[1].update_shifter();
-                }
             }
-            // 0x4018 - 0x4020 / I/O Refisters
-            0x4018..=0x4020 => (),
-            // 0x4021 - 0xFFFF / Handled by the mapper
-            0x4021..=0xFFFF => {
+            // 0x4018 - 0x401F / I/O Refisters
+            0x4018..=0x401F => (),
+            // 0x4020 - 0xFFFF / Handled by the mapper
+            0x4020..=0xFFFF => {
                 if let Err(e) = self
-                    .o_p_mapper
-                    .as_mut()
+                    .ppu
+                    .get_mapper_mut()
                     .unwrap()
-                    .borrow_mut()
                     .prg_rom_write(address, value)
                 {
                     debug!("{}", e);
@@ -290,12 +387,29 @@ impl Bus {
     }
 }
 
+// Content of the CPU RAM at power on
+fn get_startup_ram(pattern: &RamPattern) -> [u8; 0x0800] {
+    let mut ram = [0; 0x0800];
+    match pattern {
+        RamPattern::Zeroed => (),
+        RamPattern::Filled(value) => ram = [*value; 0x0800],
+        RamPattern::Pattern(bytes) => {
+            for (byte, value) in ram.iter_mut().zip(bytes.iter().cycle()) {
+                *byte = *value;
+            }
+        }
+    }
+    ram
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct BusState {
     #[serde_as(as = "[_; 0x0800]")]
     cpu_ram: [u8; 0x0800],
     controllers: [Controller; 2],
+    #[serde(default)]
+    last_bus_value: u8,
 }
 
 impl Stateful for Bus {
@@ -305,11 +419,13 @@ impl Stateful for Bus {
         BusState {
             cpu_ram: self.cpu_ram,
             controllers: self.controllers,
+            last_bus_value: self.last_bus_value,
         }
     }
 
     fn set_state(&mut self, state: &Self::State) {
         self.cpu_ram = state.cpu_ram;
         self.controllers = state.controllers;
+        self.last_bus_value = state.last_bus_value;
     }
 }
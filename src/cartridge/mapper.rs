@@ -1,7 +1,8 @@
-use std::any::Any;
-use std::error::Error;
-use std::fs::File;
-use std::io::Read;
+use alloc::collections::BTreeMap;
+use core::any::Any;
+use core::error::Error;
+#[cfg(feature = "std")]
+use std::fs;
 
 use log::debug;
 use serde::{Deserialize, Serialize};
@@ -10,8 +11,18 @@ use crate::cartridge::mapper_000::Mapper0;
 use crate::cartridge::mapper_001::Mapper1;
 use crate::cartridge::mapper_002::Mapper2;
 use crate::cartridge::mapper_003::Mapper3;
+use crate::cartridge::mapper_004::Mapper4;
+use crate::cartridge::mapper_005::Mapper5;
+use crate::cartridge::mapper_007::Mapper7;
+use crate::cartridge::mapper_066::Mapper66;
+use crate::errors::NesError;
+use crate::prelude::*;
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+// Size of the optional trainer stored between the header and the PRG ROM
+const TRAINER_SIZE: usize = 512;
+
+/// Mirroring of the name tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Mirroring {
     Horizontal,
     Vertical,
@@ -20,49 +31,108 @@ pub enum Mirroring {
     FourScreens,
 }
 
-#[typetag::serde(tag = "type")]
+/// State of a mapper, saved in the save states.
+/// With the `std` feature, implementations must be annotated with `#[typetag::serde]`.
+// The tag identifies the concrete state when deserializing, this works with
+// both the JSON (serde_json) and binary (bincode) save state formats
+#[cfg_attr(feature = "std", typetag::serde(tag = "type"))]
 pub trait MapperState {
     fn as_any(&self) -> &dyn Any;
 }
 
-pub trait Mapper {
+/// Circuit of a cartridge, mapping its memory on the CPU and PPU buses.
+/// Implement it to support a board with `NES::register_mapper`.
+pub trait Mapper: Send + Sync {
+    /// Read the CPU bus, from 0x4020 to 0xFFFF.
     fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>>;
+    /// Write the CPU bus, from 0x4020 to 0xFFFF.
     fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>>;
+    /// Read the pattern tables, from 0x0000 to 0x1FFF.
     fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>>;
+    /// Write the pattern tables, from 0x0000 to 0x1FFF.
     fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>>;
+    fn get_header(&self) -> &INesHeader;
     fn get_mirroring(&self) -> Mirroring;
-    fn load_persistent_memory(&mut self, _save_path: &str) -> Result<(), Box<dyn Error>> {
-        Err("ROM has no persistent memory".into())
+    /// Called on each PPU bus access to the pattern tables (used by scanline counters).
+    fn notify_ppu_address(&mut self, _address: u16) {}
+    fn is_irq_pending(&self) -> bool {
+        false
+    }
+    /// Copy the 512 bytes trainer of the ROM file to the PRG RAM, from 0x7000 to 0x71FF.
+    /// Mappers without PRG RAM ignore it.
+    fn load_trainer(&mut self, _trainer: &[u8]) {}
+    /// True if the persistent memory can be saved, the default implementation does not save it.
+    fn has_persistent_memory(&self) -> bool {
+        false
+    }
+    #[cfg(feature = "std")]
+    fn load_persistent_memory(&mut self, _save_path: &str) -> Result<(), NesError> {
+        Err(NesError::NoPersistentMemory)
     }
-    fn save_persistent_memory(&self, _save_path: &str) -> Result<(), Box<dyn Error>> {
-        Err("ROM has no persistent memory".into())
+    #[cfg(feature = "std")]
+    fn save_persistent_memory(&self, _save_path: &str) -> Result<(), NesError> {
+        Err(NesError::NoPersistentMemory)
     }
     fn get_mapper_state(&self) -> Box<dyn MapperState>;
     fn set_mapper_state(&mut self, state: &dyn MapperState);
+    /// Banks currently mapped, for debugging. Mappers not reporting them return an empty info.
+    fn debug_bank_state(&self) -> MapperBankInfo {
+        MapperBankInfo::default()
+    }
 }
 
-// Header of the iNES format
+/// Function creating a mapper from the 16KB PRG ROM units, the 8KB CHR ROM units
+/// and the header of a ROM file. Without CHR ROM, a single unit of zeros is given.
+pub type MapperFactory =
+    Box<dyn Fn(Vec<[u8; 0x4000]>, Vec<[u8; 0x2000]>, INesHeader) -> Box<dyn Mapper> + Send + Sync>;
+
+/// Header of the iNES format.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct INesHeader {
     pub path_to_rom: String,
 
     pub n_prg_rom: u8,
     pub n_chr_rom: u8,
-    pub mapper_number: u8,
+    pub mapper_number: u16,
     pub mirroring: Mirroring,
     pub has_persistent_memory: bool,
+    // 512 bytes loaded at 0x7000 are stored before the PRG ROM
+    #[serde(default)]
+    pub has_trainer: bool,
+
+    // iNES 2.0 extensions, deduced from the other fields for iNES 1.0 headers
+    #[serde(default)]
+    pub submapper: u8,
+    // Sizes in bytes of the volatile and battery backed (non volatile) RAM
+    #[serde(default)]
+    pub prg_ram_size: usize,
+    #[serde(default)]
+    pub prg_nvram_size: usize,
+    #[serde(default)]
+    pub chr_ram_size: usize,
+    #[serde(default)]
+    pub chr_nvram_size: usize,
+
+    // Hash of the PRG and CHR ROM data, None when the mapper was not created from a ROM file
+    #[serde(default)]
+    pub o_rom_hash: Option<RomHash>,
 }
 
 impl INesHeader {
-    pub fn new(buffer: [u8; 16], path_to_rom: &str) -> Result<Self, Box<dyn Error>> {
+    pub fn new(buffer: [u8; 16], path_to_rom: &str) -> Result<Self, NesError> {
         if buffer[0..4] != [0x4E, 0x45, 0x53, 0x1A] {
-            return Err("Invalid iNES format".into());
+            return Err(NesError::InvalidRom(String::from("Invalid iNES format")));
         }
 
         let n_prg_rom = buffer[4];
         let n_chr_rom = buffer[5];
 
-        let mapper_number: u8 = (buffer[6] >> 4) + ((buffer[7] >> 4) << 4);
+        let is_ines_2 = buffer[7] & 0x0C == 0x08;
+
+        let mut mapper_number: u16 = ((buffer[6] >> 4) + ((buffer[7] >> 4) << 4)) as u16;
+        if is_ines_2 {
+            mapper_number |= ((buffer[8] & 0x0F) as u16) << 8;
+        }
 
         let mirroring = match (buffer[6] & 0x01 > 0, buffer[6] & 0x08 > 0) {
             (false, false) => Mirroring::Horizontal,
@@ -71,38 +141,205 @@ impl INesHeader {
         };
 
         let has_persistent_memory = buffer[6] & 0x02 > 0;
+        let has_trainer = buffer[6] & 0x04 > 0;
+
+        let header = if is_ines_2 {
+            INesHeader {
+                path_to_rom: String::from(path_to_rom),
+                n_prg_rom,
+                n_chr_rom,
+                mapper_number,
+                mirroring,
+                has_persistent_memory,
+                has_trainer,
+                submapper: buffer[8] >> 4,
+                prg_ram_size: get_ram_size(buffer[10] & 0x0F),
+                prg_nvram_size: get_ram_size(buffer[10] >> 4),
+                chr_ram_size: get_ram_size(buffer[11] & 0x0F),
+                chr_nvram_size: get_ram_size(buffer[11] >> 4),
+                o_rom_hash: None,
+            }
+        } else {
+            // iNES 1.0 does not give the RAM sizes, assume 8KB of PRG RAM and
+            // 8KB of CHR RAM when there is no CHR ROM
+            INesHeader {
+                path_to_rom: String::from(path_to_rom),
+                n_prg_rom,
+                n_chr_rom,
+                mapper_number,
+                mirroring,
+                has_persistent_memory,
+                has_trainer,
+                submapper: 0,
+                prg_ram_size: if has_persistent_memory { 0 } else { 0x2000 },
+                prg_nvram_size: if has_persistent_memory { 0x2000 } else { 0 },
+                chr_ram_size: if n_chr_rom == 0 { 0x2000 } else { 0 },
+                chr_nvram_size: 0,
+                o_rom_hash: None,
+            }
+        };
+        Ok(header)
+    }
+}
+
+// The RAM sizes of iNES 2.0 headers are given as a shift count, 0 meaning no RAM
+fn get_ram_size(shift_count: u8) -> usize {
+    if shift_count == 0 {
+        0
+    } else {
+        64 << shift_count
+    }
+}
+
+/// Information about the cartridge inserted in the NES, read from its iNES header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeInfo {
+    pub mapper_number: u16,
+    /// Number of 16KB PRG ROM units.
+    pub n_prg_rom: u8,
+    /// Number of 8KB CHR ROM units.
+    pub n_chr_rom: u8,
+    /// Mirroring specified by the header, some mappers can change it while running.
+    pub mirroring: Mirroring,
+    pub has_persistent_memory: bool,
+    /// Submapper number, only given by iNES 2.0 headers.
+    pub submapper: u8,
+    /// Sizes in bytes of the volatile and battery backed PRG RAM.
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    /// Sizes in bytes of the volatile and battery backed CHR RAM.
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+}
+
+impl From<&INesHeader> for CartridgeInfo {
+    fn from(header: &INesHeader) -> Self {
+        CartridgeInfo {
+            mapper_number: header.mapper_number,
+            n_prg_rom: header.n_prg_rom,
+            n_chr_rom: header.n_chr_rom,
+            mirroring: header.mirroring,
+            has_persistent_memory: header.has_persistent_memory,
+            submapper: header.submapper,
+            prg_ram_size: header.prg_ram_size,
+            prg_nvram_size: header.prg_nvram_size,
+            chr_ram_size: header.chr_ram_size,
+            chr_nvram_size: header.chr_nvram_size,
+        }
+    }
+}
+
+/// Hash of the PRG and CHR ROM data of a cartridge, without its header.
+/// This is the hash used by the ROM databases to identify the games.
+/// Other hashes may be added, so this can only be built by the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RomHash {
+    pub crc32: u32,
+}
+
+impl RomHash {
+    fn new(data: &[u8]) -> Self {
+        RomHash { crc32: crc32(data) }
+    }
+}
 
-        Ok(INesHeader {
-            path_to_rom: String::from(path_to_rom),
-            n_prg_rom,
-            n_chr_rom,
-            mapper_number,
-            mirroring,
-            has_persistent_memory,
-        })
+// CRC-32 with the polynomial used by zip files and the ROM databases
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 0x01 > 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
     }
+    !crc
 }
 
-pub fn get_mapper(path: &str) -> Result<Box<dyn Mapper>, Box<dyn Error>> {
-    // Opens file in read only mode
-    let mut file = File::open(path)?;
+/// Banks currently mapped by the cartridge, as indices of banks in the ROM.
+/// A field is None when the mapper does not report it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MapperBankInfo {
+    /// 16KB PRG ROM banks mapped from 0x8000 to 0xBFFF and from 0xC000 to 0xFFFF.
+    pub o_lo_prg_bank: Option<usize>,
+    pub o_hi_prg_bank: Option<usize>,
+    /// 4KB CHR banks mapped from 0x0000 to 0x0FFF and from 0x1000 to 0x1FFF.
+    pub o_lo_chr_bank: Option<usize>,
+    pub o_hi_chr_bank: Option<usize>,
+}
+
+#[cfg(feature = "std")]
+pub fn get_mapper(
+    path: &str,
+    factories: &BTreeMap<u16, MapperFactory>,
+) -> Result<Box<dyn Mapper>, NesError> {
+    get_mapper_from_bytes(&fs::read(path)?, path, factories)
+}
 
-    // Reads the first 16 bytes of the file
-    // This is the header of the file
+// Create the mapper from the content of an iNES file
+// The registered factories take precedence over the mappers implemented by the crate
+pub fn get_mapper_from_bytes(
+    rom: &[u8],
+    path_to_rom: &str,
+    factories: &BTreeMap<u16, MapperFactory>,
+) -> Result<Box<dyn Mapper>, NesError> {
+    // Gets the next `length` bytes of the file
+    let mut offset = 0;
+    let mut next_bytes = |length: usize| match rom.get(offset..offset + length) {
+        Some(bytes) => {
+            offset += length;
+            Ok(bytes)
+        }
+        None => Err(NesError::InvalidRom(String::from(
+            "ROM file is too short for its header",
+        ))),
+    };
+
+    // The first 16 bytes of the file are the header of the file
     let mut buffer: [u8; 16] = [0; 16];
-    file.read_exact(&mut buffer)?;
-    let header = INesHeader::new(buffer, path)?;
+    buffer.copy_from_slice(next_bytes(16)?);
+    let mut header = INesHeader::new(buffer, path_to_rom)?;
 
     debug!(
         "{} 16KB PRG ROM units | {} 8KB CHR ROM units",
         header.n_prg_rom, header.n_chr_rom
     );
 
+    // Check the size of the file before allocating the banks, as the header may be corrupted
+    let trainer_size = if header.has_trainer { TRAINER_SIZE } else { 0 };
+    let expected_size = 16
+        + trainer_size
+        + header.n_prg_rom as usize * 16 * 1024
+        + header.n_chr_rom as usize * 8 * 1024;
+    if rom.len() < expected_size {
+        return Err(NesError::InvalidRom(format!(
+            "ROM file is {} bytes long but its header requires {} bytes",
+            rom.len(),
+            expected_size
+        )));
+    }
+    if rom.len() > expected_size {
+        debug!(
+            "Ignoring {} bytes at the end of the ROM file",
+            rom.len() - expected_size
+        );
+    }
+
+    let o_trainer = if header.has_trainer {
+        Some(next_bytes(TRAINER_SIZE)?)
+    } else {
+        None
+    };
+
     // Stores the prg_rom
     let mut prg_rom = vec![];
     let mut buffer = [0; 16 * 1024];
     for _i in 0..header.n_prg_rom {
-        file.read_exact(&mut buffer)?;
+        buffer.copy_from_slice(next_bytes(16 * 1024)?);
         prg_rom.push(buffer);
     }
 
@@ -110,24 +347,39 @@ pub fn get_mapper(path: &str) -> Result<Box<dyn Mapper>, Box<dyn Error>> {
     let mut chr_rom = vec![];
     let mut buffer = [0; 8 * 1024];
     for _i in 0..header.n_chr_rom {
-        file.read_exact(&mut buffer)?;
+        buffer.copy_from_slice(next_bytes(8 * 1024)?);
         chr_rom.push(buffer);
     }
     if chr_rom.is_empty() {
         chr_rom.push(buffer);
     }
 
+    let rom_size = header.n_prg_rom as usize * 16 * 1024 + header.n_chr_rom as usize * 8 * 1024;
+    let rom_start = 16 + trainer_size;
+    header.o_rom_hash = Some(RomHash::new(&rom[rom_start..rom_start + rom_size]));
+
     // Create Mapper
     let mapper_number = header.mapper_number;
-    let mapper: Box<dyn Mapper> = match mapper_number {
-        0 => Box::new(Mapper0::new(prg_rom, chr_rom, header)),
-        1 => Box::new(Mapper1::new(prg_rom, chr_rom, header)),
-        2 => Box::new(Mapper2::new(prg_rom, chr_rom, header)),
-        3 => Box::new(Mapper3::new(prg_rom, chr_rom, header)),
-        x => panic!("Mapper {} is not implemented", x),
+    let mut mapper: Box<dyn Mapper> = match factories.get(&mapper_number) {
+        Some(factory) => factory(prg_rom, chr_rom, header),
+        None => match mapper_number {
+            0 => Box::new(Mapper0::new(prg_rom, chr_rom, header)),
+            1 => Box::new(Mapper1::new(prg_rom, chr_rom, header)),
+            2 => Box::new(Mapper2::new(prg_rom, chr_rom, header)),
+            3 => Box::new(Mapper3::new(prg_rom, chr_rom, header)),
+            4 => Box::new(Mapper4::new(prg_rom, chr_rom, header)),
+            5 => Box::new(Mapper5::new(prg_rom, chr_rom, header)),
+            7 => Box::new(Mapper7::new(prg_rom, chr_rom, header)),
+            66 => Box::new(Mapper66::new(prg_rom, chr_rom, header)),
+            x => return Err(NesError::UnsupportedMapper(x)),
+        },
     };
 
     debug!("Using mapper {}", mapper_number);
 
+    if let Some(trainer) = o_trainer {
+        mapper.load_trainer(trainer);
+    }
+
     Ok(mapper)
 }
@@ -1,11 +1,12 @@
 // Mapper 0 : NROM
 
-use std::{any::Any, error::Error};
+use core::{any::Any, error::Error};
 
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use super::mapper::{INesHeader, Mapper, MapperState, Mirroring};
+use crate::prelude::*;
 use crate::{
     errors::{InvalidMapperReadError, InvalidMapperWriteError},
     state::Stateful,
@@ -15,15 +16,19 @@ pub struct Mapper0 {
     header: INesHeader,
     prg_rom: Vec<[u8; 0x4000]>,
     chr_rom: Vec<[u8; 0x2000]>,
+    // The CHR memory is writable RAM when the ROM has no CHR ROM
+    has_chr_ram: bool,
     ram: [u8; 0x2000], // There can be RAM on Family Basic ROMs
 }
 
 impl Mapper0 {
     pub fn new(prg_rom: Vec<[u8; 0x4000]>, chr_rom: Vec<[u8; 0x2000]>, header: INesHeader) -> Self {
+        let has_chr_ram = header.n_chr_rom == 0;
         Mapper0 {
             header,
             prg_rom,
             chr_rom,
+            has_chr_ram,
             ram: [0; 0x2000],
         }
     }
@@ -60,7 +65,7 @@ impl Mapper for Mapper0 {
 
     fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
         match address {
-            0x0000..=0x1FFF => {
+            0x0000..=0x1FFF if self.has_chr_ram => {
                 self.chr_rom[0][address as usize] = value;
                 Ok(())
             }
@@ -68,6 +73,14 @@ impl Mapper for Mapper0 {
         }
     }
 
+    fn load_trainer(&mut self, trainer: &[u8]) {
+        self.ram[0x1000..0x1000 + trainer.len()].copy_from_slice(trainer);
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
     fn get_mirroring(&self) -> Mirroring {
         self.header.mirroring
     }
@@ -94,7 +107,7 @@ pub struct Mapper0State {
     chr_rom: Vec<[u8; 0x2000]>,
 }
 
-#[typetag::serde]
+#[cfg_attr(feature = "std", typetag::serde)]
 impl MapperState for Mapper0State {
     fn as_any(&self) -> &dyn Any {
         self
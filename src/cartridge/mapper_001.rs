@@ -1,20 +1,24 @@
 // Mapper 1 : MMC1
 
-use std::any::Any;
-use std::convert::TryInto;
-use std::error::Error;
+use core::any::Any;
+use core::convert::TryInto;
+use core::error::Error;
+#[cfg(feature = "std")]
 use std::fs::{self, File};
+#[cfg(feature = "std")]
 use std::io::Write;
-use std::path::Path;
 
 use log::debug;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
+#[cfg(feature = "std")]
+use crate::errors::NesError;
 use crate::errors::{InvalidMapperReadError, InvalidMapperWriteError};
 use crate::state::Stateful;
 
-use super::mapper::{INesHeader, Mapper, MapperState, Mirroring};
+use super::mapper::{INesHeader, Mapper, MapperBankInfo, MapperState, Mirroring};
+use crate::prelude::*;
 
 #[derive(Debug)]
 enum PrgRomBankMode {
@@ -40,7 +44,7 @@ pub struct Mapper1 {
     prg_rom: Vec<[u8; 0x4000]>,
     chr_rom: Vec<[u8; 0x1000]>,
 
-    ram: [u8; 0x2000],
+    ram: Vec<u8>,
     ram_disabled: bool,
 
     shift_register: u8,
@@ -60,6 +64,7 @@ impl Mapper1 {
                     .expect("Failed to convert array"),
             );
         }
+        let ram_size = header.prg_ram_size + header.prg_nvram_size;
         // Add additionnal CHR ROM to get 32 banks (max for MMC1)
         while converted.len() < 0x20 {
             converted.push([0; 0x1000]);
@@ -73,7 +78,7 @@ impl Mapper1 {
             hi_chr_rom: 1,
             prg_rom,
             chr_rom: converted,
-            ram: [0; 0x2000],
+            ram: vec![0; ram_size],
             ram_disabled: false,
             shift_register: 0,
             n_bit_loaded: 0,
@@ -97,6 +102,15 @@ impl Mapper1 {
             _ => unreachable!(),
         }
     }
+
+    // Index of the PRG RAM byte mapped at the given address, if there is PRG RAM
+    fn get_ram_index(&self, address: u16) -> Option<usize> {
+        if self.ram.is_empty() {
+            None
+        } else {
+            Some((address & 0x1FFF) as usize % self.ram.len())
+        }
+    }
 }
 
 impl Mapper for Mapper1 {
@@ -104,7 +118,10 @@ impl Mapper for Mapper1 {
         match address {
             0x0000..=0x401F => Err(Box::new(InvalidMapperReadError(address))),
             0x4020..=0x5FFF => Err(Box::new(InvalidMapperReadError(address))),
-            0x6000..=0x7FFF => Ok(self.ram[(address & 0x1FFF) as usize]),
+            0x6000..=0x7FFF => match self.get_ram_index(address) {
+                Some(index) => Ok(self.ram[index]),
+                None => Err(Box::new(InvalidMapperReadError(address))),
+            },
             0x8000..=0xBFFF => match self.get_prg_rom_bank_mode() {
                 PrgRomBankMode::Switch32 => {
                     Ok(self.prg_rom[self.lo_prg_rom][(address & 0x3FFF) as usize])
@@ -134,10 +151,13 @@ impl Mapper for Mapper1 {
         match address {
             0x0000..=0x401F => Err(Box::new(InvalidMapperWriteError(address))),
             0x4020..=0x5FFF => Err(Box::new(InvalidMapperWriteError(address))),
-            0x6000..=0x7FFF => {
-                self.ram[(address & 0x1FFF) as usize] = value;
-                Ok(())
-            }
+            0x6000..=0x7FFF => match self.get_ram_index(address) {
+                Some(index) => {
+                    self.ram[index] = value;
+                    Ok(())
+                }
+                None => Err(Box::new(InvalidMapperWriteError(address))),
+            },
             0x8000..=0xFFFF => {
                 if value & 0x80 > 0 {
                     self.shift_register = 0;
@@ -237,6 +257,18 @@ impl Mapper for Mapper1 {
         }
     }
 
+    fn load_trainer(&mut self, trainer: &[u8]) {
+        for (address, &value) in (0x7000..).zip(trainer) {
+            if let Some(index) = self.get_ram_index(address) {
+                self.ram[index] = value;
+            }
+        }
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
     fn get_mirroring(&self) -> Mirroring {
         match self.control_register & 0x03 {
             0 => Mirroring::OneScreenLower,
@@ -247,25 +279,51 @@ impl Mapper for Mapper1 {
         }
     }
 
-    fn load_persistent_memory(&mut self, save_path: &str) -> Result<(), Box<dyn Error>> {
+    fn has_persistent_memory(&self) -> bool {
+        self.header.has_persistent_memory
+    }
+
+    #[cfg(feature = "std")]
+    fn load_persistent_memory(&mut self, save_path: &str) -> Result<(), NesError> {
         if self.header.has_persistent_memory {
-            let path_to_save = Path::new(save_path);
-            if path_to_save.exists() {
-                self.ram = fs::read(path_to_save)?[..].try_into()?;
-                return Ok(());
+            let ram = fs::read(save_path)?;
+            if ram.len() != self.ram.len() {
+                return Err(NesError::InvalidSave(String::from(
+                    "Save file size does not match the PRG RAM size",
+                )));
             }
-            return Err(format!("Save file {} not found", path_to_save.to_str().unwrap()).into());
+            self.ram = ram;
+            return Ok(());
         }
-        Err("ROM has no persistent memory".into())
+        Err(NesError::NoPersistentMemory)
     }
 
-    fn save_persistent_memory(&self, save_path: &str) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "std")]
+    fn save_persistent_memory(&self, save_path: &str) -> Result<(), NesError> {
         if self.header.has_persistent_memory {
             let mut save_file = File::create(save_path)?;
             save_file.write_all(&self.ram)?;
             return Ok(());
         }
-        Err("ROM has no persistent memory".into())
+        Err(NesError::NoPersistentMemory)
+    }
+
+    fn debug_bank_state(&self) -> MapperBankInfo {
+        let (lo_prg, hi_prg) = match self.get_prg_rom_bank_mode() {
+            PrgRomBankMode::Switch32 => (self.lo_prg_rom, self.lo_prg_rom + 1),
+            PrgRomBankMode::Switch16FirstFixed => (0, self.hi_prg_rom),
+            PrgRomBankMode::Switch16LastFixed => (self.lo_prg_rom, self.prg_rom.len() - 1),
+        };
+        let (lo_chr, hi_chr) = match self.get_chr_rom_bank_mode() {
+            ChrRomBankMode::Switch8 => (self.lo_chr_rom, self.lo_chr_rom + 1),
+            ChrRomBankMode::Switch4 => (self.lo_chr_rom, self.hi_chr_rom),
+        };
+        MapperBankInfo {
+            o_lo_prg_bank: Some(lo_prg),
+            o_hi_prg_bank: Some(hi_prg),
+            o_lo_chr_bank: Some(lo_chr),
+            o_hi_chr_bank: Some(hi_chr),
+        }
     }
 
     fn get_mapper_state(&self) -> Box<dyn MapperState> {
@@ -288,8 +346,7 @@ pub struct Mapper1State {
     hi_prg_rom: usize,
     lo_chr_rom: usize,
     hi_chr_rom: usize,
-    #[serde_as(as = "[_; 0x2000]")]
-    ram: [u8; 0x2000],
+    ram: Vec<u8>,
     ram_disabled: bool,
     shift_register: u8,
     n_bit_loaded: u8,
@@ -298,7 +355,7 @@ pub struct Mapper1State {
     chr_rom: Vec<[u8; 0x1000]>,
 }
 
-#[typetag::serde]
+#[cfg_attr(feature = "std", typetag::serde)]
 impl MapperState for Mapper1State {
     fn as_any(&self) -> &dyn Any {
         self
@@ -315,7 +372,7 @@ impl Stateful for Mapper1 {
             hi_prg_rom: self.hi_prg_rom,
             lo_chr_rom: self.lo_chr_rom,
             hi_chr_rom: self.hi_chr_rom,
-            ram: self.ram,
+            ram: self.ram.clone(),
             ram_disabled: self.ram_disabled,
             shift_register: self.shift_register,
             n_bit_loaded: self.n_bit_loaded,
@@ -330,7 +387,7 @@ impl Stateful for Mapper1 {
         self.hi_prg_rom = state.hi_prg_rom;
         self.lo_chr_rom = state.lo_chr_rom;
         self.hi_chr_rom = state.hi_chr_rom;
-        self.ram = state.ram;
+        self.ram = state.ram.clone();
         self.ram_disabled = state.ram_disabled;
         self.shift_register = state.shift_register;
         self.n_bit_loaded = state.n_bit_loaded;
@@ -1,11 +1,12 @@
 // Mapper 2 : UNROM
 
-use std::{any::Any, error::Error};
+use core::{any::Any, error::Error};
 
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
-use super::mapper::{INesHeader, Mapper, MapperState, Mirroring};
+use super::mapper::{INesHeader, Mapper, MapperBankInfo, MapperState, Mirroring};
+use crate::prelude::*;
 use crate::{
     errors::{InvalidMapperReadError, InvalidMapperWriteError},
     state::Stateful,
@@ -17,15 +18,19 @@ pub struct Mapper2 {
     lo_prg_rom: usize,
     prg_rom: Vec<[u8; 0x4000]>,
     chr_rom: Vec<[u8; 0x2000]>,
+    // The value written is ANDed with the ROM byte at the same address (NES 2.0 submapper 2)
+    bus_conflicts: bool,
 }
 
 impl Mapper2 {
     pub fn new(prg_rom: Vec<[u8; 0x4000]>, chr_rom: Vec<[u8; 0x2000]>, header: INesHeader) -> Self {
+        let bus_conflicts = header.submapper == 2;
         Mapper2 {
             header,
             lo_prg_rom: 0,
             prg_rom,
             chr_rom,
+            bus_conflicts,
         }
     }
 }
@@ -49,6 +54,11 @@ impl Mapper for Mapper2 {
             0x4020..=0x5FFF => Err(Box::new(InvalidMapperWriteError(address))),
             0x6000..=0x7FFF => Err(Box::new(InvalidMapperWriteError(address))),
             0x8000..=0xFFFF => {
+                let value = if self.bus_conflicts {
+                    value & self.prg_rom_read(address)?
+                } else {
+                    value
+                };
                 self.lo_prg_rom = (value & 0x0F) as usize;
                 Ok(())
             }
@@ -69,10 +79,23 @@ impl Mapper for Mapper2 {
         }
     }
 
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
     fn get_mirroring(&self) -> Mirroring {
         self.header.mirroring
     }
 
+    fn debug_bank_state(&self) -> MapperBankInfo {
+        MapperBankInfo {
+            o_lo_prg_bank: Some(self.lo_prg_rom),
+            o_hi_prg_bank: Some(self.prg_rom.len() - 1),
+            o_lo_chr_bank: Some(0),
+            o_hi_chr_bank: Some(1),
+        }
+    }
+
     fn get_mapper_state(&self) -> Box<dyn MapperState> {
         Box::new(self.get_state())
     }
@@ -94,7 +117,7 @@ pub struct Mapper2State {
     chr_rom: Vec<[u8; 0x2000]>,
 }
 
-#[typetag::serde]
+#[cfg_attr(feature = "std", typetag::serde)]
 impl MapperState for Mapper2State {
     fn as_any(&self) -> &dyn Any {
         self
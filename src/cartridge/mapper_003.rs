@@ -1,11 +1,12 @@
 // Mapper 3 : CNROM
 
-use std::{any::Any, error::Error};
+use core::{any::Any, error::Error};
 
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
-use super::mapper::{INesHeader, Mapper, Mirroring};
+use super::mapper::{INesHeader, Mapper, MapperBankInfo, Mirroring};
+use crate::prelude::*;
 use crate::{
     cartridge::mapper::MapperState,
     errors::{InvalidMapperReadError, InvalidMapperWriteError},
@@ -17,6 +18,8 @@ pub struct Mapper3 {
     selected_chr_rom: usize,
     prg_rom: Vec<[u8; 0x4000]>,
     chr_rom: Vec<[u8; 0x2000]>,
+    // The CHR memory is writable RAM when the ROM has no CHR ROM
+    has_chr_ram: bool,
 }
 
 impl Mapper3 {
@@ -25,11 +28,13 @@ impl Mapper3 {
         chr_rom: Vec<[u8; 8 * 1024]>,
         header: INesHeader,
     ) -> Self {
+        let has_chr_ram = header.n_chr_rom == 0;
         Mapper3 {
             header,
             selected_chr_rom: 0,
             prg_rom,
             chr_rom,
+            has_chr_ram,
         }
     }
 }
@@ -60,12 +65,12 @@ impl Mapper for Mapper3 {
     }
 
     fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
-        Ok(self.chr_rom[self.selected_chr_rom][address as usize])
+        Ok(self.chr_rom[self.selected_chr_rom % self.chr_rom.len()][address as usize])
     }
 
     fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
         match address {
-            0x0000..=0x1FFF => {
+            0x0000..=0x1FFF if self.has_chr_ram => {
                 self.chr_rom[0][address as usize] = value;
                 Ok(())
             }
@@ -73,10 +78,25 @@ impl Mapper for Mapper3 {
         }
     }
 
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
     fn get_mirroring(&self) -> Mirroring {
         self.header.mirroring
     }
 
+    fn debug_bank_state(&self) -> MapperBankInfo {
+        // The 8KB CHR banks are reported as two 4KB banks
+        let chr_bank = self.selected_chr_rom % self.chr_rom.len();
+        MapperBankInfo {
+            o_lo_prg_bank: Some(0),
+            o_hi_prg_bank: Some(self.prg_rom.len() - 1),
+            o_lo_chr_bank: Some(chr_bank * 2),
+            o_hi_chr_bank: Some(chr_bank * 2 + 1),
+        }
+    }
+
     fn get_mapper_state(&self) -> Box<dyn MapperState> {
         Box::new(self.get_state())
     }
@@ -98,7 +118,7 @@ pub struct Mapper3State {
     chr_rom: Vec<[u8; 0x2000]>,
 }
 
-#[typetag::serde]
+#[cfg_attr(feature = "std", typetag::serde)]
 impl MapperState for Mapper3State {
     fn as_any(&self) -> &dyn Any {
         self
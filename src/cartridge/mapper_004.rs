@@ -0,0 +1,355 @@
+// Mapper 4 : MMC3
+
+use core::any::Any;
+use core::convert::TryInto;
+use core::error::Error;
+#[cfg(feature = "std")]
+use std::fs::{self, File};
+#[cfg(feature = "std")]
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+#[cfg(feature = "std")]
+use crate::errors::NesError;
+use crate::errors::{InvalidMapperReadError, InvalidMapperWriteError};
+use crate::state::Stateful;
+
+use super::mapper::{INesHeader, Mapper, MapperState, Mirroring};
+use crate::prelude::*;
+
+// Number of consecutive PPU reads with A12 low required before a rise of A12 clocks the
+// scanline counter. This filters out the rises happening between background tile fetches.
+const A12_LOW_READS_FILTER: u8 = 3;
+
+pub struct Mapper4 {
+    header: INesHeader,
+
+    prg_rom: Vec<[u8; 0x2000]>,
+    chr_rom: Vec<[u8; 0x0400]>,
+    // The CHR memory is writable RAM when the ROM has no CHR ROM
+    has_chr_ram: bool,
+
+    ram: [u8; 0x2000],
+    ram_enabled: bool,
+    ram_write_protected: bool,
+
+    // Bank select and bank registers (R0 to R7)
+    bank_select: u8,
+    bank_registers: [u8; 8],
+    vertical_mirroring: bool,
+
+    // Scanline counter
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    // A12 rise detection
+    a12_low_reads: u8,
+}
+
+impl Mapper4 {
+    pub fn new(prg_rom: Vec<[u8; 0x4000]>, chr_rom: Vec<[u8; 0x2000]>, header: INesHeader) -> Self {
+        let mut converted_prg_rom: Vec<[u8; 0x2000]> = vec![];
+        for elt in prg_rom.iter() {
+            for chunk in elt.chunks(0x2000) {
+                converted_prg_rom.push(chunk.try_into().expect("Failed to convert array"));
+            }
+        }
+        let mut converted_chr_rom: Vec<[u8; 0x0400]> = vec![];
+        for elt in chr_rom.iter() {
+            for chunk in elt.chunks(0x0400) {
+                converted_chr_rom.push(chunk.try_into().expect("Failed to convert array"));
+            }
+        }
+
+        let vertical_mirroring = matches!(header.mirroring, Mirroring::Vertical);
+        let has_chr_ram = header.n_chr_rom == 0;
+
+        Mapper4 {
+            header,
+            prg_rom: converted_prg_rom,
+            chr_rom: converted_chr_rom,
+            has_chr_ram,
+            ram: [0; 0x2000],
+            ram_enabled: true,
+            ram_write_protected: false,
+            bank_select: 0,
+            bank_registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            vertical_mirroring,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12_low_reads: 0,
+        }
+    }
+
+    // Get the 8KB PRG ROM bank mapped at the given address
+    fn get_prg_rom_bank(&self, address: u16) -> usize {
+        let second_last = self.prg_rom.len() - 2;
+        let prg_rom_mode = self.bank_select & 0x40 > 0;
+        let bank = match (address, prg_rom_mode) {
+            (0x8000..=0x9FFF, false) => self.bank_registers[6] as usize,
+            (0x8000..=0x9FFF, true) => second_last,
+            (0xA000..=0xBFFF, _) => self.bank_registers[7] as usize,
+            (0xC000..=0xDFFF, false) => second_last,
+            (0xC000..=0xDFFF, true) => self.bank_registers[6] as usize,
+            _ => self.prg_rom.len() - 1,
+        };
+        bank % self.prg_rom.len()
+    }
+
+    // Get the 1KB CHR ROM bank mapped at the given address
+    fn get_chr_rom_bank(&self, address: u16) -> usize {
+        // The two 2KB and the four 1KB banks are swapped if the CHR A12 inversion bit is set
+        let address = if self.bank_select & 0x80 > 0 {
+            address ^ 0x1000
+        } else {
+            address
+        };
+        let bank = match address {
+            0x0000..=0x07FF => (self.bank_registers[0] & 0xFE) as usize + (address >> 10) as usize,
+            0x0800..=0x0FFF => {
+                (self.bank_registers[1] & 0xFE) as usize + ((address & 0x07FF) >> 10) as usize
+            }
+            0x1000..=0x13FF => self.bank_registers[2] as usize,
+            0x1400..=0x17FF => self.bank_registers[3] as usize,
+            0x1800..=0x1BFF => self.bank_registers[4] as usize,
+            _ => self.bank_registers[5] as usize,
+        };
+        bank % self.chr_rom.len()
+    }
+
+    fn clock_scanline_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Mapper4 {
+    fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            0x0000..=0x401F => Err(Box::new(InvalidMapperReadError(address))),
+            0x4020..=0x5FFF => Err(Box::new(InvalidMapperReadError(address))),
+            0x6000..=0x7FFF => {
+                if self.ram_enabled {
+                    Ok(self.ram[(address & 0x1FFF) as usize])
+                } else {
+                    Err(Box::new(InvalidMapperReadError(address)))
+                }
+            }
+            0x8000..=0xFFFF => {
+                Ok(self.prg_rom[self.get_prg_rom_bank(address)][(address & 0x1FFF) as usize])
+            }
+        }
+    }
+
+    fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x401F => Err(Box::new(InvalidMapperWriteError(address))),
+            0x4020..=0x5FFF => Err(Box::new(InvalidMapperWriteError(address))),
+            0x6000..=0x7FFF => {
+                if self.ram_enabled && !self.ram_write_protected {
+                    self.ram[(address & 0x1FFF) as usize] = value;
+                    Ok(())
+                } else {
+                    Err(Box::new(InvalidMapperWriteError(address)))
+                }
+            }
+            0x8000..=0xFFFF => {
+                let even = address & 0x0001 == 0;
+                match (address, even) {
+                    (0x8000..=0x9FFF, true) => self.bank_select = value,
+                    (0x8000..=0x9FFF, false) => {
+                        self.bank_registers[(self.bank_select & 0x07) as usize] = value
+                    }
+                    (0xA000..=0xBFFF, true) => self.vertical_mirroring = value & 0x01 == 0,
+                    (0xA000..=0xBFFF, false) => {
+                        self.ram_enabled = value & 0x80 > 0;
+                        self.ram_write_protected = value & 0x40 > 0;
+                    }
+                    (0xC000..=0xDFFF, true) => self.irq_latch = value,
+                    (0xC000..=0xDFFF, false) => {
+                        self.irq_counter = 0;
+                        self.irq_reload = true;
+                    }
+                    (_, true) => {
+                        self.irq_enabled = false;
+                        self.irq_pending = false;
+                    }
+                    (_, false) => self.irq_enabled = true,
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            0x0000..=0x1FFF => {
+                Ok(self.chr_rom[self.get_chr_rom_bank(address)][(address & 0x03FF) as usize])
+            }
+            _ => Err(Box::new(InvalidMapperReadError(address))),
+        }
+    }
+
+    fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x1FFF if self.has_chr_ram => {
+                let bank = self.get_chr_rom_bank(address);
+                self.chr_rom[bank][(address & 0x03FF) as usize] = value;
+                Ok(())
+            }
+            _ => Err(Box::new(InvalidMapperWriteError(address))),
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) {
+        self.ram[0x1000..0x1000 + trainer.len()].copy_from_slice(trainer);
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        match self.header.mirroring {
+            Mirroring::FourScreens => Mirroring::FourScreens,
+            _ if self.vertical_mirroring => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn notify_ppu_address(&mut self, address: u16) {
+        if address & 0x1000 == 0 {
+            self.a12_low_reads = self.a12_low_reads.saturating_add(1);
+        } else {
+            if self.a12_low_reads >= A12_LOW_READS_FILTER {
+                self.clock_scanline_counter();
+            }
+            self.a12_low_reads = 0;
+        }
+    }
+
+    fn is_irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn has_persistent_memory(&self) -> bool {
+        self.header.has_persistent_memory
+    }
+
+    #[cfg(feature = "std")]
+    fn load_persistent_memory(&mut self, save_path: &str) -> Result<(), NesError> {
+        if self.header.has_persistent_memory {
+            self.ram = fs::read(save_path)?[..].try_into().map_err(|_| {
+                NesError::InvalidSave(String::from(
+                    "Save file size does not match the PRG RAM size",
+                ))
+            })?;
+            return Ok(());
+        }
+        Err(NesError::NoPersistentMemory)
+    }
+
+    #[cfg(feature = "std")]
+    fn save_persistent_memory(&self, save_path: &str) -> Result<(), NesError> {
+        if self.header.has_persistent_memory {
+            let mut save_file = File::create(save_path)?;
+            save_file.write_all(&self.ram)?;
+            return Ok(());
+        }
+        Err(NesError::NoPersistentMemory)
+    }
+
+    fn get_mapper_state(&self) -> Box<dyn MapperState> {
+        Box::new(self.get_state())
+    }
+
+    fn set_mapper_state(&mut self, state: &dyn MapperState) {
+        match state.as_any().downcast_ref::<Mapper4State>() {
+            Some(s) => self.set_state(s),
+            None => panic!("State is not a Mapper4State"),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct Mapper4State {
+    header: INesHeader,
+    #[serde_as(as = "[_; 0x2000]")]
+    ram: [u8; 0x2000],
+    ram_enabled: bool,
+    ram_write_protected: bool,
+    bank_select: u8,
+    bank_registers: [u8; 8],
+    vertical_mirroring: bool,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    a12_low_reads: u8,
+    #[serde_as(as = "Vec<[_; 0x0400]>")]
+    chr_rom: Vec<[u8; 0x0400]>,
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl MapperState for Mapper4State {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Stateful for Mapper4 {
+    type State = Mapper4State;
+
+    fn get_state(&self) -> Self::State {
+        Mapper4State {
+            header: self.header.clone(),
+            ram: self.ram,
+            ram_enabled: self.ram_enabled,
+            ram_write_protected: self.ram_write_protected,
+            bank_select: self.bank_select,
+            bank_registers: self.bank_registers,
+            vertical_mirroring: self.vertical_mirroring,
+            irq_latch: self.irq_latch,
+            irq_counter: self.irq_counter,
+            irq_reload: self.irq_reload,
+            irq_enabled: self.irq_enabled,
+            irq_pending: self.irq_pending,
+            a12_low_reads: self.a12_low_reads,
+            chr_rom: self.chr_rom.clone(),
+        }
+    }
+
+    fn set_state(&mut self, state: &Self::State) {
+        self.header = state.header.clone();
+        self.ram = state.ram;
+        self.ram_enabled = state.ram_enabled;
+        self.ram_write_protected = state.ram_write_protected;
+        self.bank_select = state.bank_select;
+        self.bank_registers = state.bank_registers;
+        self.vertical_mirroring = state.vertical_mirroring;
+        self.irq_latch = state.irq_latch;
+        self.irq_counter = state.irq_counter;
+        self.irq_reload = state.irq_reload;
+        self.irq_enabled = state.irq_enabled;
+        self.irq_pending = state.irq_pending;
+        self.a12_low_reads = state.a12_low_reads;
+        self.chr_rom = state.chr_rom.clone();
+    }
+}
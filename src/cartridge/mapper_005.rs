@@ -0,0 +1,359 @@
+// Mapper 5 : MMC5
+// Only the PRG and CHR banking, the PRG RAM, the ExRAM as CPU memory and the multiplier are supported.
+// Known limitations:
+// - The scanline IRQ is never triggered, and the in-frame flag is never set
+// - The ExRAM cannot be used as a nametable or for extended attributes, and the fill mode is not supported
+// - The background CHR banks (0x5128 to 0x512B) are ignored, the sprite banks are used for all
+//   fetches, which is only correct with 8x8 sprites
+// - The vertical split mode and the expansion audio are not emulated
+
+use core::any::Any;
+use core::convert::TryInto;
+use core::error::Error;
+#[cfg(feature = "std")]
+use std::fs::{self, File};
+#[cfg(feature = "std")]
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+#[cfg(feature = "std")]
+use crate::errors::NesError;
+use crate::errors::{InvalidMapperReadError, InvalidMapperWriteError};
+use crate::state::Stateful;
+
+use super::mapper::{INesHeader, Mapper, MapperState, Mirroring};
+use crate::prelude::*;
+
+pub struct Mapper5 {
+    header: INesHeader,
+
+    prg_rom: Vec<[u8; 0x2000]>,
+    chr_rom: Vec<[u8; 0x0400]>,
+    // The CHR memory is writable RAM when the ROM has no CHR ROM
+    has_chr_ram: bool,
+
+    // PRG RAM, made of 8KB banks
+    ram: Vec<u8>,
+    ram_protect: [u8; 2],
+    ex_ram: Vec<u8>,
+
+    prg_mode: u8,
+    chr_mode: u8,
+    nametable_mapping: u8,
+
+    // PRG bank registers from 0x5113 to 0x5117
+    prg_ram_bank: u8,
+    prg_banks: [u8; 4],
+    // Sprite CHR bank registers from 0x5120 to 0x5127, with the upper bits set by 0x5130
+    chr_banks: [u16; 8],
+    chr_upper_bits: u8,
+
+    multiplicand: u8,
+    multiplier: u8,
+}
+
+impl Mapper5 {
+    pub fn new(prg_rom: Vec<[u8; 0x4000]>, chr_rom: Vec<[u8; 0x2000]>, header: INesHeader) -> Self {
+        let mut converted_prg_rom: Vec<[u8; 0x2000]> = vec![];
+        for elt in prg_rom.iter() {
+            for chunk in elt.chunks(0x2000) {
+                converted_prg_rom.push(chunk.try_into().expect("Failed to convert array"));
+            }
+        }
+        let mut converted_chr_rom: Vec<[u8; 0x0400]> = vec![];
+        for elt in chr_rom.iter() {
+            for chunk in elt.chunks(0x0400) {
+                converted_chr_rom.push(chunk.try_into().expect("Failed to convert array"));
+            }
+        }
+        let ram_size = header.prg_ram_size + header.prg_nvram_size;
+        let has_chr_ram = header.n_chr_rom == 0;
+
+        Mapper5 {
+            header,
+            prg_rom: converted_prg_rom,
+            chr_rom: converted_chr_rom,
+            has_chr_ram,
+            ram: vec![0; ram_size],
+            ram_protect: [0; 2],
+            ex_ram: vec![0; 0x0400],
+            prg_mode: 3,
+            chr_mode: 3,
+            nametable_mapping: 0,
+            prg_ram_bank: 0,
+            // The last bank is mapped at 0xE000 on power up
+            prg_banks: [0xFF; 4],
+            chr_banks: [0, 1, 2, 3, 4, 5, 6, 7],
+            chr_upper_bits: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+        }
+    }
+
+    // Get the bank register and the size in 8KB units of the PRG bank mapped at the given address
+    fn get_prg_bank_register(&self, address: u16) -> (u8, u8) {
+        match (self.prg_mode, address) {
+            (0, _) => (self.prg_banks[3], 4),
+            (1, 0x8000..=0xBFFF) => (self.prg_banks[1], 2),
+            (1, _) => (self.prg_banks[3], 2),
+            (2, 0x8000..=0xBFFF) => (self.prg_banks[1], 2),
+            (2, 0xC000..=0xDFFF) => (self.prg_banks[2], 1),
+            (2, _) => (self.prg_banks[3], 1),
+            (_, _) => (self.prg_banks[((address - 0x8000) >> 13) as usize], 1),
+        }
+    }
+
+    // Get the index in the PRG RAM of the byte mapped at the given address,
+    // or None if the address maps the PRG ROM
+    fn get_prg_ram_index(&self, address: u16) -> Option<usize> {
+        let bank = match address {
+            0x6000..=0x7FFF => self.prg_ram_bank,
+            _ => {
+                let (register, _) = self.get_prg_bank_register(address);
+                // The bit 7 selects the ROM, the bank at 0xE000 is always in ROM
+                if register & 0x80 > 0 || address >= 0xE000 {
+                    return None;
+                }
+                register
+            }
+        };
+        if self.ram.is_empty() {
+            return None;
+        }
+        let index = ((bank & 0x07) as usize) * 0x2000 + (address & 0x1FFF) as usize;
+        Some(index % self.ram.len())
+    }
+
+    // Get the 8KB PRG ROM bank mapped at the given address
+    fn get_prg_rom_bank(&self, address: u16) -> usize {
+        let (register, size) = self.get_prg_bank_register(address);
+        // Larger banks ignore the low bits of the register
+        let offset = ((address - 0x8000) >> 13) as u8 & (size - 1);
+        let bank = ((register & 0x7F) & !(size - 1)) | offset;
+        bank as usize % self.prg_rom.len()
+    }
+
+    // Get the 1KB CHR bank mapped at the given address
+    fn get_chr_bank(&self, address: u16) -> usize {
+        let slot = (address >> 10) as usize;
+        let bank = match self.chr_mode {
+            0 => self.chr_banks[7] as usize * 8 + slot,
+            1 => self.chr_banks[slot | 3] as usize * 4 + (slot & 3),
+            2 => self.chr_banks[slot | 1] as usize * 2 + (slot & 1),
+            _ => self.chr_banks[slot] as usize,
+        };
+        bank % self.chr_rom.len()
+    }
+
+    // The PRG RAM is only writable after writing 2 to 0x5102 and 1 to 0x5103
+    fn is_ram_writable(&self) -> bool {
+        self.ram_protect == [0x02, 0x01]
+    }
+}
+
+impl Mapper for Mapper5 {
+    fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            // Scanline IRQ status, the IRQ is not emulated
+            0x5204 => Ok(0),
+            0x5205 => Ok((self.multiplicand as u16 * self.multiplier as u16) as u8),
+            0x5206 => Ok(((self.multiplicand as u16 * self.multiplier as u16) >> 8) as u8),
+            0x5C00..=0x5FFF => Ok(self.ex_ram[(address & 0x03FF) as usize]),
+            0x6000..=0xFFFF => match self.get_prg_ram_index(address) {
+                Some(index) => Ok(self.ram[index]),
+                None if address >= 0x8000 => {
+                    Ok(self.prg_rom[self.get_prg_rom_bank(address)][(address & 0x1FFF) as usize])
+                }
+                None => Err(Box::new(InvalidMapperReadError(address))),
+            },
+            _ => Err(Box::new(InvalidMapperReadError(address))),
+        }
+    }
+
+    fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x5100 => self.prg_mode = value & 0x03,
+            0x5101 => self.chr_mode = value & 0x03,
+            0x5102 => self.ram_protect[0] = value & 0x03,
+            0x5103 => self.ram_protect[1] = value & 0x03,
+            0x5105 => self.nametable_mapping = value,
+            0x5113 => self.prg_ram_bank = value,
+            // The bank at 0xE000 is always in ROM
+            0x5114..=0x5116 => self.prg_banks[(address - 0x5114) as usize] = value,
+            0x5117 => self.prg_banks[3] = value | 0x80,
+            0x5120..=0x5127 => {
+                self.chr_banks[(address - 0x5120) as usize] =
+                    value as u16 | ((self.chr_upper_bits as u16) << 8)
+            }
+            0x5130 => self.chr_upper_bits = value & 0x03,
+            0x5205 => self.multiplicand = value,
+            0x5206 => self.multiplier = value,
+            0x5C00..=0x5FFF => self.ex_ram[(address & 0x03FF) as usize] = value,
+            // Registers of the unsupported features
+            0x5000..=0x5015 | 0x5104 | 0x5106 | 0x5107 | 0x5128..=0x512B | 0x5200..=0x5204 => (),
+            0x6000..=0xFFFF => match self.get_prg_ram_index(address) {
+                Some(index) => {
+                    if self.is_ram_writable() {
+                        self.ram[index] = value;
+                    }
+                }
+                None if address >= 0x8000 => (),
+                None => return Err(Box::new(InvalidMapperWriteError(address))),
+            },
+            _ => return Err(Box::new(InvalidMapperWriteError(address))),
+        }
+        Ok(())
+    }
+
+    fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            0x0000..=0x1FFF => {
+                Ok(self.chr_rom[self.get_chr_bank(address)][(address & 0x03FF) as usize])
+            }
+            _ => Err(Box::new(InvalidMapperReadError(address))),
+        }
+    }
+
+    fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x1FFF if self.has_chr_ram => {
+                let bank = self.get_chr_bank(address);
+                self.chr_rom[bank][(address & 0x03FF) as usize] = value;
+                Ok(())
+            }
+            _ => Err(Box::new(InvalidMapperWriteError(address))),
+        }
+    }
+
+    fn load_trainer(&mut self, trainer: &[u8]) {
+        for (address, &value) in (0x7000..).zip(trainer) {
+            if let Some(index) = self.get_prg_ram_index(address) {
+                self.ram[index] = value;
+            }
+        }
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
+    // Each nametable is selected by 2 bits, only the mappings of the internal nametables
+    // matching a mirroring are supported, the others are approximated by horizontal mirroring
+    fn get_mirroring(&self) -> Mirroring {
+        match self.nametable_mapping {
+            0x44 => Mirroring::Vertical,
+            0x00 => Mirroring::OneScreenLower,
+            0x55 => Mirroring::OneScreenUpper,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn has_persistent_memory(&self) -> bool {
+        self.header.has_persistent_memory
+    }
+
+    #[cfg(feature = "std")]
+    fn load_persistent_memory(&mut self, save_path: &str) -> Result<(), NesError> {
+        if self.header.has_persistent_memory {
+            let ram = fs::read(save_path)?;
+            if ram.len() != self.ram.len() {
+                return Err(NesError::InvalidSave(String::from(
+                    "Save file size does not match the PRG RAM size",
+                )));
+            }
+            self.ram = ram;
+            return Ok(());
+        }
+        Err(NesError::NoPersistentMemory)
+    }
+
+    #[cfg(feature = "std")]
+    fn save_persistent_memory(&self, save_path: &str) -> Result<(), NesError> {
+        if self.header.has_persistent_memory {
+            let mut save_file = File::create(save_path)?;
+            save_file.write_all(&self.ram)?;
+            return Ok(());
+        }
+        Err(NesError::NoPersistentMemory)
+    }
+
+    fn get_mapper_state(&self) -> Box<dyn MapperState> {
+        Box::new(self.get_state())
+    }
+
+    fn set_mapper_state(&mut self, state: &dyn MapperState) {
+        match state.as_any().downcast_ref::<Mapper5State>() {
+            Some(s) => self.set_state(s),
+            None => panic!("State is not a Mapper5State"),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct Mapper5State {
+    header: INesHeader,
+    ram: Vec<u8>,
+    ram_protect: [u8; 2],
+    ex_ram: Vec<u8>,
+    prg_mode: u8,
+    chr_mode: u8,
+    nametable_mapping: u8,
+    prg_ram_bank: u8,
+    prg_banks: [u8; 4],
+    chr_banks: [u16; 8],
+    chr_upper_bits: u8,
+    multiplicand: u8,
+    multiplier: u8,
+    #[serde_as(as = "Vec<[_; 0x0400]>")]
+    chr_rom: Vec<[u8; 0x0400]>,
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl MapperState for Mapper5State {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Stateful for Mapper5 {
+    type State = Mapper5State;
+
+    fn get_state(&self) -> Self::State {
+        Mapper5State {
+            header: self.header.clone(),
+            ram: self.ram.clone(),
+            ram_protect: self.ram_protect,
+            ex_ram: self.ex_ram.clone(),
+            prg_mode: self.prg_mode,
+            chr_mode: self.chr_mode,
+            nametable_mapping: self.nametable_mapping,
+            prg_ram_bank: self.prg_ram_bank,
+            prg_banks: self.prg_banks,
+            chr_banks: self.chr_banks,
+            chr_upper_bits: self.chr_upper_bits,
+            multiplicand: self.multiplicand,
+            multiplier: self.multiplier,
+            chr_rom: self.chr_rom.clone(),
+        }
+    }
+
+    fn set_state(&mut self, state: &Self::State) {
+        self.header = state.header.clone();
+        self.ram = state.ram.clone();
+        self.ram_protect = state.ram_protect;
+        self.ex_ram = state.ex_ram.clone();
+        self.prg_mode = state.prg_mode;
+        self.chr_mode = state.chr_mode;
+        self.nametable_mapping = state.nametable_mapping;
+        self.prg_ram_bank = state.prg_ram_bank;
+        self.prg_banks = state.prg_banks;
+        self.chr_banks = state.chr_banks;
+        self.chr_upper_bits = state.chr_upper_bits;
+        self.multiplicand = state.multiplicand;
+        self.multiplier = state.multiplier;
+        self.chr_rom = state.chr_rom.clone();
+    }
+}
@@ -0,0 +1,141 @@
+// Mapper 7 : AxROM
+
+use core::{any::Any, error::Error};
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::mapper::{INesHeader, Mapper, Mirroring};
+use crate::prelude::*;
+use crate::{
+    cartridge::mapper::MapperState,
+    errors::{InvalidMapperReadError, InvalidMapperWriteError},
+    state::Stateful,
+};
+
+pub struct Mapper7 {
+    header: INesHeader,
+    selected_prg_rom: usize,
+    one_screen_upper: bool,
+    prg_rom: Vec<[u8; 0x4000]>,
+    chr_rom: Vec<[u8; 0x2000]>,
+}
+
+impl Mapper7 {
+    pub fn new(
+        prg_rom: Vec<[u8; 16 * 1024]>,
+        chr_rom: Vec<[u8; 8 * 1024]>,
+        header: INesHeader,
+    ) -> Self {
+        Mapper7 {
+            header,
+            selected_prg_rom: 0,
+            one_screen_upper: false,
+            prg_rom,
+            chr_rom,
+        }
+    }
+}
+
+impl Mapper for Mapper7 {
+    fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            0x0000..=0x401F => Err(Box::new(InvalidMapperReadError(address))),
+            0x4020..=0x5FFF => Err(Box::new(InvalidMapperReadError(address))),
+            0x6000..=0x7FFF => Err(Box::new(InvalidMapperReadError(address))),
+            0x8000..=0xFFFF => {
+                // The selected bank is 32KB, made of two 16KB units of the PRG ROM
+                let unit = (self.selected_prg_rom * 2 + ((address as usize & 0x4000) >> 14))
+                    % self.prg_rom.len();
+                Ok(self.prg_rom[unit][(address & 0x3FFF) as usize])
+            }
+        }
+    }
+
+    fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x401F => Err(Box::new(InvalidMapperWriteError(address))),
+            0x4020..=0x5FFF => Err(Box::new(InvalidMapperWriteError(address))),
+            0x6000..=0x7FFF => Err(Box::new(InvalidMapperWriteError(address))),
+            0x8000..=0xFFFF => {
+                self.selected_prg_rom = (value & 0x07) as usize;
+                self.one_screen_upper = value & 0x10 > 0;
+                Ok(())
+            }
+        }
+    }
+
+    fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        Ok(self.chr_rom[0][address as usize])
+    }
+
+    fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x1FFF => {
+                self.chr_rom[0][address as usize] = value;
+                Ok(())
+            }
+            _ => Err(Box::new(InvalidMapperWriteError(address))),
+        }
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        if self.one_screen_upper {
+            Mirroring::OneScreenUpper
+        } else {
+            Mirroring::OneScreenLower
+        }
+    }
+
+    fn get_mapper_state(&self) -> Box<dyn MapperState> {
+        Box::new(self.get_state())
+    }
+
+    fn set_mapper_state(&mut self, state: &dyn MapperState) {
+        match state.as_any().downcast_ref::<Mapper7State>() {
+            Some(s) => self.set_state(s),
+            None => panic!("State is not a Mapper7State"),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct Mapper7State {
+    header: INesHeader,
+    selected_prg_rom: usize,
+    one_screen_upper: bool,
+    #[serde_as(as = "Vec<[_; 0x2000]>")]
+    chr_rom: Vec<[u8; 0x2000]>,
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl MapperState for Mapper7State {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Stateful for Mapper7 {
+    type State = Mapper7State;
+
+    fn get_state(&self) -> Self::State {
+        Mapper7State {
+            header: self.header.clone(),
+            selected_prg_rom: self.selected_prg_rom,
+            one_screen_upper: self.one_screen_upper,
+            chr_rom: self.chr_rom.clone(),
+        }
+    }
+
+    fn set_state(&mut self, state: &Self::State) {
+        self.header = state.header.clone();
+        self.selected_prg_rom = state.selected_prg_rom;
+        self.one_screen_upper = state.one_screen_upper;
+        self.chr_rom = state.chr_rom.clone();
+    }
+}
@@ -0,0 +1,142 @@
+// Mapper 66 : GxROM
+
+use core::{any::Any, error::Error};
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::mapper::{INesHeader, Mapper, Mirroring};
+use crate::prelude::*;
+use crate::{
+    cartridge::mapper::MapperState,
+    errors::{InvalidMapperReadError, InvalidMapperWriteError},
+    state::Stateful,
+};
+
+pub struct Mapper66 {
+    header: INesHeader,
+    selected_prg: usize,
+    selected_chr: usize,
+    prg_rom: Vec<[u8; 0x4000]>,
+    chr_rom: Vec<[u8; 0x2000]>,
+    // The CHR memory is writable RAM when the ROM has no CHR ROM
+    has_chr_ram: bool,
+}
+
+impl Mapper66 {
+    pub fn new(
+        prg_rom: Vec<[u8; 16 * 1024]>,
+        chr_rom: Vec<[u8; 8 * 1024]>,
+        header: INesHeader,
+    ) -> Self {
+        let has_chr_ram = header.n_chr_rom == 0;
+        Mapper66 {
+            header,
+            selected_prg: 0,
+            selected_chr: 0,
+            prg_rom,
+            chr_rom,
+            has_chr_ram,
+        }
+    }
+}
+
+impl Mapper for Mapper66 {
+    fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            0x0000..=0x401F => Err(Box::new(InvalidMapperReadError(address))),
+            0x4020..=0x5FFF => Err(Box::new(InvalidMapperReadError(address))),
+            0x6000..=0x7FFF => Err(Box::new(InvalidMapperReadError(address))),
+            0x8000..=0xFFFF => {
+                // The selected bank is 32KB, made of two 16KB units of the PRG ROM
+                let unit = (self.selected_prg * 2 + ((address as usize & 0x4000) >> 14))
+                    % self.prg_rom.len();
+                Ok(self.prg_rom[unit][(address & 0x3FFF) as usize])
+            }
+        }
+    }
+
+    fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x401F => Err(Box::new(InvalidMapperWriteError(address))),
+            0x4020..=0x5FFF => Err(Box::new(InvalidMapperWriteError(address))),
+            0x6000..=0x7FFF => Err(Box::new(InvalidMapperWriteError(address))),
+            0x8000..=0xFFFF => {
+                self.selected_prg = ((value & 0x30) >> 4) as usize;
+                self.selected_chr = (value & 0x03) as usize;
+                Ok(())
+            }
+        }
+    }
+
+    fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        Ok(self.chr_rom[self.selected_chr % self.chr_rom.len()][address as usize])
+    }
+
+    fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x0000..=0x1FFF if self.has_chr_ram => {
+                let bank = self.selected_chr % self.chr_rom.len();
+                self.chr_rom[bank][address as usize] = value;
+                Ok(())
+            }
+            _ => Err(Box::new(InvalidMapperWriteError(address))),
+        }
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        self.header.mirroring
+    }
+
+    fn get_mapper_state(&self) -> Box<dyn MapperState> {
+        Box::new(self.get_state())
+    }
+
+    fn set_mapper_state(&mut self, state: &dyn MapperState) {
+        match state.as_any().downcast_ref::<Mapper66State>() {
+            Some(s) => self.set_state(s),
+            None => panic!("State is not a Mapper66State"),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct Mapper66State {
+    header: INesHeader,
+    selected_prg: usize,
+    selected_chr: usize,
+    #[serde_as(as = "Vec<[_; 0x2000]>")]
+    chr_rom: Vec<[u8; 0x2000]>,
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl MapperState for Mapper66State {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Stateful for Mapper66 {
+    type State = Mapper66State;
+
+    fn get_state(&self) -> Self::State {
+        Mapper66State {
+            header: self.header.clone(),
+            selected_prg: self.selected_prg,
+            selected_chr: self.selected_chr,
+            chr_rom: self.chr_rom.clone(),
+        }
+    }
+
+    fn set_state(&mut self, state: &Self::State) {
+        self.header = state.header.clone();
+        self.selected_prg = state.selected_prg;
+        self.selected_chr = state.selected_chr;
+        self.chr_rom = state.chr_rom.clone();
+    }
+}
@@ -3,3 +3,8 @@ mod mapper_000;
 mod mapper_001;
 mod mapper_002;
 mod mapper_003;
+mod mapper_004;
+mod mapper_005;
+mod mapper_007;
+mod mapper_066;
+pub mod nsf;
@@ -0,0 +1,300 @@
+// NSF (NES Sound Format) player
+// The NSF data is mapped as a cartridge, along with a small driver program
+// calling the init routine of the track, then the play routine at each NMI
+
+use core::{any::Any, error::Error};
+
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+
+use super::mapper::{INesHeader, Mapper, MapperState, Mirroring};
+use crate::prelude::*;
+use crate::{
+    errors::{InvalidMapperReadError, InvalidMapperWriteError, NesError},
+    state::Stateful,
+};
+
+const HEADER_SIZE: usize = 0x80;
+const BANK_SIZE: usize = 0x1000;
+
+// The driver is located in the expansion area, which NSF files without expansion chips do not use
+const DRIVER_ADDRESS: u16 = 0x5000;
+
+/// Information read from the header of a NSF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NsfInfo {
+    pub name: String,
+    pub artist: String,
+    pub copyright: String,
+    pub n_songs: u8,
+    /// Index of the first song to play, starting at 0.
+    pub starting_song: u8,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    /// Period of the play routine calls, in microseconds.
+    pub ntsc_speed: u16,
+    pub pal_speed: u16,
+    /// The tune is made for PAL consoles.
+    pub pal: bool,
+    /// The tune works on both NTSC and PAL consoles.
+    pub dual_region: bool,
+    /// Banks initially mapped in the 4KB slots from 0x8000 to 0xFFFF, if the tune uses bank switching.
+    pub o_initial_banks: Option<[u8; 8]>,
+    /// Expansion sound chips used by the tune, they are not emulated.
+    pub extra_sound_chips: u8,
+}
+
+#[derive(Clone)]
+pub struct Nsf {
+    pub info: NsfInfo,
+    data: Vec<u8>,
+}
+
+impl Nsf {
+    pub fn new(nsf: &[u8]) -> Result<Self, NesError> {
+        if nsf.len() <= HEADER_SIZE || nsf[0..5] != [0x4E, 0x45, 0x53, 0x4D, 0x1A] {
+            return Err(NesError::InvalidRom(String::from("Invalid NSF format")));
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([nsf[offset], nsf[offset + 1]]);
+        let text = |offset: usize| {
+            let bytes = &nsf[offset..offset + 32];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+
+        let mut initial_banks = [0; 8];
+        initial_banks.copy_from_slice(&nsf[0x70..0x78]);
+        let o_initial_banks = if initial_banks.iter().any(|&b| b != 0) {
+            Some(initial_banks)
+        } else {
+            None
+        };
+
+        let info = NsfInfo {
+            name: text(0x0E),
+            artist: text(0x2E),
+            copyright: text(0x4E),
+            n_songs: nsf[0x06],
+            starting_song: nsf[0x07].saturating_sub(1),
+            load_address: word(0x08),
+            init_address: word(0x0A),
+            play_address: word(0x0C),
+            ntsc_speed: word(0x6E),
+            pal_speed: word(0x78),
+            pal: nsf[0x7A] & 0x01 > 0,
+            dual_region: nsf[0x7A] & 0x02 > 0,
+            o_initial_banks,
+            extra_sound_chips: nsf[0x7B],
+        };
+
+        if info.o_initial_banks.is_none() && info.load_address < 0x8000 {
+            return Err(NesError::InvalidRom(String::from(
+                "NSF load address must be at least 0x8000",
+            )));
+        }
+
+        Ok(Nsf {
+            info,
+            data: nsf[HEADER_SIZE..].to_vec(),
+        })
+    }
+}
+
+pub struct NsfMapper {
+    header: INesHeader,
+    prg_rom: Vec<[u8; BANK_SIZE]>,
+    banks: [u8; 8],
+    ram: [u8; 0x2000],
+    chr_ram: [u8; 0x2000],
+    driver: Vec<u8>,
+    nmi_address: u16,
+}
+
+impl NsfMapper {
+    pub fn new(nsf: &Nsf, track: u8, pal: bool) -> Self {
+        // Without bank switching, the data is loaded at the load address
+        let (padding, banks) = match nsf.info.o_initial_banks {
+            Some(banks) => ((nsf.info.load_address & 0x0FFF) as usize, banks),
+            None => (
+                (nsf.info.load_address - 0x8000) as usize,
+                [0, 1, 2, 3, 4, 5, 6, 7],
+            ),
+        };
+        let mut data = vec![0; padding];
+        data.extend_from_slice(&nsf.data);
+        let n_banks = data.len().div_ceil(BANK_SIZE).max(8);
+        data.resize(n_banks * BANK_SIZE, 0);
+        let prg_rom = data
+            .chunks(BANK_SIZE)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+
+        let (driver, nmi_address) = build_driver(&nsf.info, track, pal);
+
+        // NSF files have no iNES header, this one describes the memory of the player
+        let header = INesHeader {
+            path_to_rom: String::new(),
+            n_prg_rom: (n_banks / 4) as u8,
+            n_chr_rom: 0,
+            mapper_number: 0,
+            mirroring: Mirroring::Vertical,
+            has_persistent_memory: false,
+            has_trainer: false,
+            submapper: 0,
+            prg_ram_size: 0x2000,
+            prg_nvram_size: 0,
+            chr_ram_size: 0x2000,
+            chr_nvram_size: 0,
+            o_rom_hash: None,
+        };
+
+        NsfMapper {
+            header,
+            prg_rom,
+            banks,
+            ram: [0; 0x2000],
+            chr_ram: [0; 0x2000],
+            driver,
+            nmi_address,
+        }
+    }
+}
+
+// Initialize the APU, call the init routine with the track in A and the region in X,
+// then enable the NMI and wait, the NMI handler calls the play routine
+fn build_driver(info: &NsfInfo, track: u8, pal: bool) -> (Vec<u8>, u16) {
+    let [init_low, init_high] = info.init_address.to_le_bytes();
+    let [play_low, play_high] = info.play_address.to_le_bytes();
+    let mut driver = vec![
+        0x78, // SEI
+        0xD8, // CLD
+        0xA2, 0xFF, // LDX #$FF
+        0x9A, // TXS
+        0xA9, 0x00, // LDA #$00
+        0x8D, 0x00, 0x20, // STA $2000
+        0x8D, 0x15, 0x40, // STA $4015
+        0xA2, 0x13, // LDX #$13
+        0x9D, 0x00, 0x40, // STA $4000,X
+        0xCA, // DEX
+        0x10, 0xFA, // BPL -6
+        0xA9, 0x0F, // LDA #$0F
+        0x8D, 0x15, 0x40, // STA $4015
+        0xA9, 0x40, // LDA #$40
+        0x8D, 0x17, 0x40, // STA $4017
+        0xA9, track, // LDA #track
+        0xA2, pal as u8, // LDX #region
+        0x20, init_low, init_high, // JSR init
+        0xA9, 0x80, // LDA #$80
+        0x8D, 0x00, 0x20, // STA $2000
+    ];
+    let [idle_low, idle_high] = (DRIVER_ADDRESS + driver.len() as u16).to_le_bytes();
+    driver.extend([0x4C, idle_low, idle_high]); // JMP idle
+    let nmi_address = DRIVER_ADDRESS + driver.len() as u16;
+    driver.extend([0x20, play_low, play_high]); // JSR play
+    driver.push(0x40); // RTI
+    (driver, nmi_address)
+}
+
+impl Mapper for NsfMapper {
+    fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        match address {
+            // The vectors point to the driver instead of the NSF data
+            0xFFFA => Ok(self.nmi_address.to_le_bytes()[0]),
+            0xFFFB => Ok(self.nmi_address.to_le_bytes()[1]),
+            0xFFFC => Ok(DRIVER_ADDRESS.to_le_bytes()[0]),
+            0xFFFD => Ok(DRIVER_ADDRESS.to_le_bytes()[1]),
+            0x6000..=0x7FFF => Ok(self.ram[(address & 0x1FFF) as usize]),
+            0x8000..=0xFFFF => {
+                let slot = ((address - 0x8000) as usize) / BANK_SIZE;
+                let bank = self.banks[slot] as usize % self.prg_rom.len();
+                Ok(self.prg_rom[bank][address as usize % BANK_SIZE])
+            }
+            _ => match self
+                .driver
+                .get(address.wrapping_sub(DRIVER_ADDRESS) as usize)
+            {
+                Some(&data) => Ok(data),
+                None => Err(Box::new(InvalidMapperReadError(address))),
+            },
+        }
+    }
+
+    fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        match address {
+            0x5FF8..=0x5FFF => {
+                self.banks[(address - 0x5FF8) as usize] = value;
+                Ok(())
+            }
+            0x6000..=0x7FFF => {
+                self.ram[(address & 0x1FFF) as usize] = value;
+                Ok(())
+            }
+            _ => Err(Box::new(InvalidMapperWriteError(address))),
+        }
+    }
+
+    fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        Ok(self.chr_ram[address as usize])
+    }
+
+    fn chr_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+        self.chr_ram[address as usize] = value;
+        Ok(())
+    }
+
+    fn get_header(&self) -> &INesHeader {
+        &self.header
+    }
+
+    fn get_mirroring(&self) -> Mirroring {
+        self.header.mirroring
+    }
+
+    fn get_mapper_state(&self) -> Box<dyn MapperState> {
+        Box::new(self.get_state())
+    }
+
+    fn set_mapper_state(&mut self, state: &dyn MapperState) {
+        match state.as_any().downcast_ref::<NsfMapperState>() {
+            Some(s) => self.set_state(s),
+            None => panic!("State is not a NsfMapperState"),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+pub struct NsfMapperState {
+    banks: [u8; 8],
+    #[serde_as(as = "[_; 0x2000]")]
+    ram: [u8; 0x2000],
+    #[serde_as(as = "[_; 0x2000]")]
+    chr_ram: [u8; 0x2000],
+}
+
+#[cfg_attr(feature = "std", typetag::serde)]
+impl MapperState for NsfMapperState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Stateful for NsfMapper {
+    type State = NsfMapperState;
+
+    fn get_state(&self) -> Self::State {
+        NsfMapperState {
+            banks: self.banks,
+            ram: self.ram,
+            chr_ram: self.chr_ram,
+        }
+    }
+
+    fn set_state(&mut self, state: &Self::State) {
+        self.banks = state.banks;
+        self.ram = state.ram;
+        self.chr_ram = state.chr_ram;
+    }
+}
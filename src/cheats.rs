@@ -0,0 +1,80 @@
+// Game Genie cheats, patching the data read from the cartridge
+
+// ===== IMPORTS =====
+
+use crate::prelude::*;
+use core::error::Error;
+
+// ===== CONSTANTS =====
+
+// Each letter of a Game Genie code encodes 4 bits, its value is its index in this string
+const GAME_GENIE_LETTERS: &str = "APZLGITYEOXUKSVN";
+
+// ===== STRUCTS =====
+
+/// Handle to a cheat added to the NES, used to remove it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CheatHandle(pub(crate) u32);
+
+#[derive(Debug, Clone, Copy)]
+pub struct Cheat {
+    address: u16,
+    value: u8,
+    compare: Option<u8>,
+}
+
+impl Cheat {
+    // Decode a 6 or 8 characters Game Genie code
+    pub fn from_game_genie_code(code: &str) -> Result<Self, Box<dyn Error>> {
+        let n = code
+            .chars()
+            .map(|c| {
+                GAME_GENIE_LETTERS
+                    .find(c.to_ascii_uppercase())
+                    .map(|i| i as u16)
+                    .ok_or_else(|| format!("Invalid Game Genie letter {}", c))
+            })
+            .collect::<Result<Vec<u16>, String>>()?;
+        if n.len() != 6 && n.len() != 8 {
+            return Err("Game Genie codes must have 6 or 8 characters".into());
+        }
+
+        let address = 0x8000
+            + (((n[3] & 7) << 12)
+                | ((n[5] & 7) << 8)
+                | ((n[4] & 8) << 8)
+                | ((n[2] & 7) << 4)
+                | ((n[1] & 8) << 4)
+                | (n[4] & 7)
+                | (n[3] & 8));
+        let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7);
+
+        // 8 characters codes only patch the data if it is equal to the compare value
+        if n.len() == 6 {
+            Ok(Cheat {
+                address,
+                value: (value | (n[5] & 8)) as u8,
+                compare: None,
+            })
+        } else {
+            Ok(Cheat {
+                address,
+                value: (value | (n[7] & 8)) as u8,
+                compare: Some(
+                    (((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8)) as u8,
+                ),
+            })
+        }
+    }
+
+    // Get the data read at the address once patched by the cheat
+    pub fn apply(&self, address: u16, data: u8) -> u8 {
+        if address != self.address {
+            return data;
+        }
+        match self.compare {
+            Some(compare) if compare != data => data,
+            _ => self.value,
+        }
+    }
+}
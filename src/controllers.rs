@@ -4,6 +4,9 @@ use serde::{Deserialize, Serialize};
 pub struct Controller {
     pub buffer: u8,
     shifter: u8,
+    // While the strobe is high, the shifter is continuously reloaded from the buffer
+    #[serde(default)]
+    strobe: bool,
 }
 
 impl Controller {
@@ -11,16 +14,53 @@ impl Controller {
         Controller {
             buffer: 0,
             shifter: 0,
+            strobe: false,
         }
     }
 
+    // Reads always return the A button state while the strobe is high
     pub fn check_shifter(&mut self) -> u8 {
+        if self.strobe {
+            self.shifter = self.buffer;
+        }
         let value: u8 = (self.shifter & 0x80 > 0) as u8;
-        self.shifter <<= 1;
+        if !self.strobe {
+            self.shifter <<= 1;
+        }
         value
     }
 
-    pub fn update_shifter(&mut self) {
-        self.shifter = self.buffer;
+    // The buffer is latched into the shifter when the strobe goes from high to low
+    pub fn write_strobe(&mut self, value: u8) {
+        let strobe = value & 0x01 > 0;
+        if strobe || self.strobe {
+            self.shifter = self.buffer;
+        }
+        self.strobe = strobe;
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Zapper {
+    pub x: u8,
+    pub y: u8,
+    pub trigger: bool,
+}
+
+impl Zapper {
+    pub fn new(x: u8, y: u8, trigger: bool) -> Self {
+        Zapper { x, y, trigger }
+    }
+
+    // Bit 3 is cleared when light is detected, bit 4 is set when the trigger is pulled
+    pub fn read(&self, light_detected: bool) -> u8 {
+        let mut value: u8 = 0;
+        if !light_detected {
+            value |= 0x08;
+        }
+        if self.trigger {
+            value |= 0x10;
+        }
+        value
     }
 }
@@ -2,8 +2,8 @@
 
 // ===== ENUMS =====
 
-// All the possible adressing modes of the CPU
-#[derive(Clone, Copy, PartialEq)]
+/// All the possible adressing modes of the CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AdressingMode {
     Implicit,
     Accumulator,
@@ -18,6 +18,7 @@ pub enum AdressingMode {
     Indirect,
     IndirectX,
     IndirectY,
+    /// Used by the invalid opcodes.
     NoMode,
 }
 
@@ -3,20 +3,87 @@
 // ===== IMPORTS =====
 
 use super::enums::AdressingMode as am;
+use super::state::InstructionInfo;
 use super::Cpu;
+use crate::bus::Bus;
 
 // ===== CPU INSTRUCTION STRUCT =====
 
 pub struct CpuInstruction {
     pub name: &'static str,
     pub opcode: u8,
-    pub execute: fn(&mut Cpu, am),
+    pub execute: fn(&mut Cpu, &mut Bus, am),
     pub adressing_mode: am,
     pub cycles: u8,
     pub add_cycle: bool,
+    // Bit n is set if the cycle done when n cycles of the instruction are left writes to the bus
+    pub write_cycles: u8,
     pub bytes: u8,
 }
 
+// ===== INSTRUCTION SET DESCRIPTION =====
+
+static INSTRUCTION_INFOS: [InstructionInfo; 256] = get_instruction_infos();
+
+/// Get the description of the instruction with the given opcode.
+pub fn instruction_info(opcode: u8) -> InstructionInfo {
+    INSTRUCTION_INFOS[opcode as usize]
+}
+
+/// Get the description of the whole instruction set, indexed by opcode.
+pub fn all_instructions() -> &'static [InstructionInfo; 256] {
+    &INSTRUCTION_INFOS
+}
+
+const fn get_instruction_infos() -> [InstructionInfo; 256] {
+    let mut infos = [InstructionInfo {
+        opcode: 0,
+        name: "",
+        addressing_mode: am::NoMode,
+        bytes: 0,
+        cycles: 0,
+        official: false,
+    }; 256];
+    let mut opcode = 0;
+    while opcode < 256 {
+        let instruction = &INSTRUCTIONS[opcode];
+        infos[opcode] = InstructionInfo {
+            opcode: instruction.opcode,
+            name: instruction.name,
+            addressing_mode: instruction.adressing_mode,
+            bytes: instruction.bytes,
+            cycles: instruction.cycles,
+            official: is_official(instruction.opcode),
+        };
+        opcode += 1;
+    }
+    infos
+}
+
+// The 151 opcodes documented in the 6502 datasheet
+#[rustfmt::skip]
+const fn is_official(opcode: u8) -> bool {
+    matches!(
+        opcode,
+        0x00 | 0x01 | 0x05 | 0x06 | 0x08 | 0x09 | 0x0A | 0x0D | 0x0E |
+        0x10 | 0x11 | 0x15 | 0x16 | 0x18 | 0x19 | 0x1D | 0x1E |
+        0x20 | 0x21 | 0x24 | 0x25 | 0x26 | 0x28 | 0x29 | 0x2A | 0x2C | 0x2D | 0x2E |
+        0x30 | 0x31 | 0x35 | 0x36 | 0x38 | 0x39 | 0x3D | 0x3E |
+        0x40 | 0x41 | 0x45 | 0x46 | 0x48 | 0x49 | 0x4A | 0x4C | 0x4D | 0x4E |
+        0x50 | 0x51 | 0x55 | 0x56 | 0x58 | 0x59 | 0x5D | 0x5E |
+        0x60 | 0x61 | 0x65 | 0x66 | 0x68 | 0x69 | 0x6A | 0x6C | 0x6D | 0x6E |
+        0x70 | 0x71 | 0x75 | 0x76 | 0x78 | 0x79 | 0x7D | 0x7E |
+        0x81 | 0x84 | 0x85 | 0x86 | 0x88 | 0x8A | 0x8C | 0x8D | 0x8E |
+        0x90 | 0x91 | 0x94 | 0x95 | 0x96 | 0x98 | 0x99 | 0x9A | 0x9D |
+        0xA0 | 0xA1 | 0xA2 | 0xA4 | 0xA5 | 0xA6 | 0xA8 | 0xA9 | 0xAA | 0xAC | 0xAD | 0xAE |
+        0xB0 | 0xB1 | 0xB4 | 0xB5 | 0xB6 | 0xB8 | 0xB9 | 0xBA | 0xBC | 0xBD | 0xBE |
+        0xC0 | 0xC1 | 0xC4 | 0xC5 | 0xC6 | 0xC8 | 0xC9 | 0xCA | 0xCC | 0xCD | 0xCE |
+        0xD0 | 0xD1 | 0xD5 | 0xD6 | 0xD8 | 0xD9 | 0xDD | 0xDE |
+        0xE0 | 0xE1 | 0xE4 | 0xE5 | 0xE6 | 0xE8 | 0xE9 | 0xEA | 0xEC | 0xED | 0xEE |
+        0xF0 | 0xF1 | 0xF5 | 0xF6 | 0xF8 | 0xF9 | 0xFD | 0xFE
+    )
+}
+
 // ===== GLOBAL CONSTANT =====
 
 // The whole set of instructions of the CPU.
@@ -30,6 +97,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0011_1000,
         bytes: 1,
     },
     CpuInstruction {
@@ -39,6 +107,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -48,6 +117,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -57,6 +127,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -66,6 +137,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -75,6 +147,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -84,6 +157,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -93,6 +167,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -102,6 +177,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 1,
     },
     CpuInstruction {
@@ -111,6 +187,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -120,6 +197,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Accumulator,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -129,6 +207,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -138,6 +217,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -147,6 +227,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -156,6 +237,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -165,6 +247,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -174,6 +257,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -183,6 +267,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -192,6 +277,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -201,6 +287,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -210,6 +297,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -219,6 +307,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -228,6 +317,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -237,6 +327,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -246,6 +337,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -255,6 +347,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -264,6 +357,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -273,6 +367,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -282,6 +377,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -291,6 +387,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -300,6 +397,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -309,6 +407,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -318,6 +417,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_1100,
         bytes: 3,
     },
     CpuInstruction {
@@ -327,6 +427,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -336,6 +437,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -345,6 +447,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -354,6 +457,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -363,6 +467,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -372,6 +477,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -381,6 +487,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -390,6 +497,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -399,6 +507,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -408,6 +517,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Accumulator,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -417,6 +527,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -426,6 +537,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -435,6 +547,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -444,6 +557,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -453,6 +567,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -462,6 +577,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -471,6 +587,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -480,6 +597,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -489,6 +607,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -498,6 +617,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -507,6 +627,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -516,6 +637,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -525,6 +647,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -534,6 +657,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -543,6 +667,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -552,6 +677,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -561,6 +687,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -570,6 +697,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -579,6 +707,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -588,6 +717,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -597,6 +727,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -606,6 +737,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -615,6 +747,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -624,6 +757,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -633,6 +767,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -642,6 +777,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -651,6 +787,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -660,6 +797,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -669,6 +807,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -678,6 +817,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 1,
     },
     CpuInstruction {
@@ -687,6 +827,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -696,6 +837,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Accumulator,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -705,6 +847,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -714,6 +857,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -723,6 +867,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -732,6 +877,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -741,6 +887,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -750,6 +897,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -759,6 +907,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -768,6 +917,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -777,6 +927,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -786,6 +937,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -795,6 +947,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -804,6 +957,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -813,6 +967,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -822,6 +977,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -831,6 +987,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -840,6 +997,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -849,6 +1007,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -858,6 +1017,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -867,6 +1027,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -876,6 +1037,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -885,6 +1047,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -894,6 +1057,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -903,6 +1067,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -912,6 +1077,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -921,6 +1087,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -930,6 +1097,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -939,6 +1107,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -948,6 +1117,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -957,6 +1127,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -966,6 +1137,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -975,6 +1147,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -984,6 +1157,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Accumulator,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -993,6 +1167,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1002,6 +1177,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Indirect,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1011,6 +1187,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1020,6 +1197,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1029,6 +1207,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1038,6 +1217,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1047,6 +1227,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1056,6 +1237,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1065,6 +1247,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1074,6 +1257,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1083,6 +1267,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1092,6 +1277,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1101,6 +1287,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1110,6 +1297,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1119,6 +1307,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1128,6 +1317,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1137,6 +1327,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1146,6 +1337,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1155,6 +1347,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1164,6 +1357,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1173,6 +1367,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1182,6 +1377,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1191,6 +1387,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1200,6 +1397,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1209,6 +1407,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1218,6 +1417,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1227,6 +1427,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1236,6 +1437,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1245,6 +1447,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1254,6 +1457,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1263,6 +1467,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1272,6 +1477,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Accumulator,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1281,6 +1487,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1290,6 +1497,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1299,6 +1507,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1308,6 +1517,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1317,6 +1527,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1326,6 +1537,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1335,6 +1547,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1344,6 +1557,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1353,6 +1567,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1362,6 +1577,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1371,6 +1587,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1380,6 +1597,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageY,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1389,6 +1607,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageY,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 2,
     },
     CpuInstruction {
@@ -1398,6 +1617,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1407,6 +1627,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1416,6 +1637,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1425,6 +1647,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1434,6 +1657,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1443,6 +1667,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1452,6 +1677,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1461,6 +1687,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0010,
         bytes: 3,
     },
     CpuInstruction {
@@ -1470,6 +1697,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1479,6 +1707,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1488,6 +1717,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1497,6 +1727,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1506,6 +1737,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1515,6 +1747,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1524,6 +1757,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1533,6 +1767,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1542,6 +1777,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1551,6 +1787,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1560,6 +1797,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Accumulator,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1569,6 +1807,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1578,6 +1817,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1587,6 +1827,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1596,6 +1837,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1605,6 +1847,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1614,6 +1857,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1623,6 +1867,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1632,6 +1877,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1641,6 +1887,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1650,6 +1897,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1659,6 +1907,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1668,6 +1917,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageY,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1677,6 +1927,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageY,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1686,6 +1937,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1695,6 +1947,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1704,6 +1957,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1713,6 +1967,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1722,6 +1977,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1731,6 +1987,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1740,6 +1997,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1749,6 +2007,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1758,6 +2017,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1767,6 +2027,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1776,6 +2037,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1785,6 +2047,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1794,6 +2057,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1803,6 +2067,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1812,6 +2077,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1821,6 +2087,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1830,6 +2097,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1839,6 +2107,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1848,6 +2117,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1857,6 +2127,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1866,6 +2137,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1875,6 +2147,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1884,6 +2157,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1893,6 +2167,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -1902,6 +2177,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1911,6 +2187,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1920,6 +2197,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1929,6 +2207,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1938,6 +2217,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1947,6 +2227,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -1956,6 +2237,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1965,6 +2247,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -1974,6 +2257,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -1983,6 +2267,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -1992,6 +2277,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -2001,6 +2287,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2010,6 +2297,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2019,6 +2307,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2028,6 +2317,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2037,6 +2327,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2046,6 +2337,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2055,6 +2347,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2064,6 +2357,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2073,6 +2367,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectX,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -2082,6 +2377,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2091,6 +2387,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 3,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2100,6 +2397,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -2109,6 +2407,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPage,
         cycles: 5,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -2118,6 +2417,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -2127,6 +2427,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2136,6 +2437,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -2145,6 +2447,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Immediate,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2154,6 +2457,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2163,6 +2467,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2172,6 +2477,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2181,6 +2487,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Absolute,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2190,6 +2497,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Relative,
         cycles: 2,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2199,6 +2507,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 5,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2208,6 +2517,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::NoMode,
         cycles: 1,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -2217,6 +2527,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::IndirectY,
         cycles: 8,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -2226,6 +2537,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2235,6 +2547,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 4,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 2,
     },
     CpuInstruction {
@@ -2244,6 +2557,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -2253,6 +2567,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::ZeroPageX,
         cycles: 6,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 2,
     },
     CpuInstruction {
@@ -2262,6 +2577,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -2271,6 +2587,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2280,6 +2597,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::Implicit,
         cycles: 2,
         add_cycle: false,
+        write_cycles: 0,
         bytes: 1,
     },
     CpuInstruction {
@@ -2289,6 +2607,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteY,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2298,6 +2617,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2307,6 +2627,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 4,
         add_cycle: true,
+        write_cycles: 0,
         bytes: 3,
     },
     CpuInstruction {
@@ -2316,6 +2637,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
     CpuInstruction {
@@ -2325,6 +2647,7 @@ pub const INSTRUCTIONS: [CpuInstruction; 256] = [
         adressing_mode: am::AbsoluteX,
         cycles: 7,
         add_cycle: false,
+        write_cycles: 0b0000_0110,
         bytes: 3,
     },
 ];
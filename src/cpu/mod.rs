@@ -1,23 +1,24 @@
 pub mod enums;
+pub mod instructions;
 pub mod state;
 
-mod instructions;
-
 // Implements the CPU of the NES, i.e. a component
 // with a similar behavior as the rp2A03 / 6502
 
 // ====== IMPORTS =====
 
-use std::cell::RefCell;
-use std::rc::Rc;
-
 use crate::bus::Bus;
 use crate::bus::STACK_OFFSET;
+use crate::errors::EmulationError;
 use crate::state::Stateful;
 use enums::{AdressingMode as am, Flag, Interrupt};
 use instructions::{CpuInstruction, INSTRUCTIONS};
 
-use self::state::CpuState;
+use self::state::{CpuLog, CpuRegisters, CpuState, DisassembledInstruction};
+use crate::prelude::*;
+
+// The interrupt sequence pushes the program counter and the status register as BRK does
+const INTERRUPT_WRITE_CYCLES: u8 = INSTRUCTIONS[0x00].write_cycles;
 
 // ===== CPU STRUCT =====
 
@@ -34,6 +35,10 @@ pub struct Cpu {
     // Cycles required by the current instruction to complete
     cycles: u8,
 
+    // Cycles of the current instruction writing to the bus, bit n is set if the cycle done
+    // when n cycles are left is a write
+    write_cycles: u8,
+
     // Does the current instruction require an eventual additional cycle ?
     require_add_cycle: bool,
 
@@ -43,15 +48,19 @@ pub struct Cpu {
     // Total clock cycles from the start of the CPU
     total_clock: u64,
 
-    // Display the log of the CPU
+    // Record the log of the CPU
     display_logs: bool,
 
-    // pointer to the data bus where we read from and write to
-    p_bus: Rc<RefCell<Bus>>,
+    // Value ORed with A by the unstable opcodes ANE and LXA, it depends on the console
+    unstable_opcode_magic: u8,
+    last_log: Option<CpuLog>,
+
+    // First error encountered during the current clock
+    error: Option<EmulationError>,
 }
 
 impl Cpu {
-    pub fn new(p_bus: Rc<RefCell<Bus>>, display_logs: bool) -> Self {
+    pub fn new(display_logs: bool, unstable_opcode_magic: u8) -> Self {
         Cpu {
             a: 0,
             x: 0,
@@ -61,6 +70,7 @@ impl Cpu {
             p: 0x34,
 
             cycles: 0,
+            write_cycles: 0,
 
             require_add_cycle: false,
             page_crossed: false,
@@ -68,13 +78,15 @@ impl Cpu {
             total_clock: 0,
 
             display_logs,
+            unstable_opcode_magic,
+            last_log: None,
 
-            p_bus,
+            error: None,
         }
     }
 
-    pub fn from_state(state: &CpuState, p_bus: Rc<RefCell<Bus>>, display_logs: bool) -> Self {
-        let mut cpu = Cpu::new(p_bus, display_logs);
+    pub fn from_state(state: &CpuState, display_logs: bool, unstable_opcode_magic: u8) -> Self {
+        let mut cpu = Cpu::new(display_logs, unstable_opcode_magic);
         cpu.set_state(state);
         cpu
     }
@@ -82,31 +94,42 @@ impl Cpu {
     // ===== BUS ACCESS =====
 
     // Reads data from the bus at the given address
-    fn read_bus(&self, address: u16) -> u8 {
-        match self.p_bus.borrow_mut().read(address) {
+    // An invalid read is reported at the end of the clock, 0 is used as the data meanwhile
+    fn read_bus(&mut self, bus: &mut Bus, address: u16) -> u8 {
+        match bus.read(address) {
             Ok(data) => data,
-            Err(e) => panic!("{}", e),
+            Err(_) => {
+                self.report_error(EmulationError::InvalidRead(address));
+                0
+            }
         }
     }
 
     // Writes data to the bus at the given address
-    fn write_bus(&mut self, address: u16, data: u8) {
-        match self.p_bus.borrow_mut().write(address, data) {
-            Ok(data) => data,
-            Err(e) => panic!("{}", e),
+    // An invalid write is reported at the end of the clock
+    fn write_bus(&mut self, bus: &mut Bus, address: u16, data: u8) {
+        if bus.write(address, data).is_err() {
+            self.report_error(EmulationError::InvalidWrite(address));
+        }
+    }
+
+    // Keep the first error encountered during the current clock
+    fn report_error(&mut self, error: EmulationError) {
+        if self.error.is_none() {
+            self.error = Some(error);
         }
     }
 
     // Pushes data to stack
-    fn push_to_stack(&mut self, data: u8) {
-        self.write_bus(STACK_OFFSET + self.sp as u16, data);
+    fn push_to_stack(&mut self, bus: &mut Bus, data: u8) {
+        self.write_bus(bus, STACK_OFFSET + self.sp as u16, data);
         self.sp = self.sp.wrapping_sub(1);
     }
 
     // Returns data from the stack
-    fn pop_from_stack(&mut self) -> u8 {
+    fn pop_from_stack(&mut self, bus: &mut Bus) -> u8 {
         self.sp = self.sp.wrapping_add(1);
-        self.read_bus(STACK_OFFSET + self.sp as u16)
+        self.read_bus(bus, STACK_OFFSET + self.sp as u16)
     }
 
     // ===== FLAG SETTER AND GETTER =====
@@ -128,51 +151,53 @@ impl Cpu {
     // ===== INTERRUPTS =====
 
     // Called when an interrupt occurs
-    pub fn interrupt(&mut self, interrupt_type: Interrupt) {
+    pub fn interrupt(&mut self, bus: &mut Bus, interrupt_type: Interrupt) {
         match interrupt_type {
-            Interrupt::Nmi => self.nmi_interrupt(),
-            Interrupt::Irq => self.irq_interrupt(),
-            Interrupt::Reset => self.reset_interrupt(),
+            Interrupt::Nmi => self.nmi_interrupt(bus),
+            Interrupt::Irq => self.irq_interrupt(bus),
+            Interrupt::Reset => self.reset_interrupt(bus),
         }
     }
 
-    fn nmi_interrupt(&mut self) {
+    fn nmi_interrupt(&mut self, bus: &mut Bus) {
         // Push program counter and status register on the stack
-        self.push_to_stack(((self.pc & 0xFF00) >> 8) as u8);
-        self.push_to_stack((self.pc & 0x00FF) as u8);
-        self.push_to_stack(self.p);
+        self.push_to_stack(bus, ((self.pc & 0xFF00) >> 8) as u8);
+        self.push_to_stack(bus, (self.pc & 0x00FF) as u8);
+        self.push_to_stack(bus, self.p);
 
         // Disable interrupts
         self.set_flag(Flag::InterruptDisable, true);
 
         // Load interrupt handler address into the program counter
         let start_address = 0xFFFA;
-        self.pc = self.read_bus(start_address) as u16
-            + ((self.read_bus(start_address + 1) as u16) << 8) as u16;
+        self.pc = self.read_bus(bus, start_address) as u16
+            + ((self.read_bus(bus, start_address + 1) as u16) << 8) as u16;
 
         self.cycles = 7;
+        self.write_cycles = INTERRUPT_WRITE_CYCLES;
     }
 
-    fn irq_interrupt(&mut self) {
+    fn irq_interrupt(&mut self, bus: &mut Bus) {
         if !self.get_flag(Flag::InterruptDisable) {
             // Push program counter and status register on the stack
-            self.push_to_stack(((self.pc & 0xFF00) >> 8) as u8);
-            self.push_to_stack((self.pc & 0x00FF) as u8);
-            self.push_to_stack(self.p);
+            self.push_to_stack(bus, ((self.pc & 0xFF00) >> 8) as u8);
+            self.push_to_stack(bus, (self.pc & 0x00FF) as u8);
+            self.push_to_stack(bus, self.p);
 
             // Disable interrupts
             self.set_flag(Flag::InterruptDisable, true);
 
             // Load interrupt handler address into the program counter
             let start_address = 0xFFFE;
-            self.pc = self.read_bus(start_address) as u16
-                + ((self.read_bus(start_address + 1) as u16) << 8) as u16;
+            self.pc = self.read_bus(bus, start_address) as u16
+                + ((self.read_bus(bus, start_address + 1) as u16) << 8) as u16;
 
             self.cycles = 7;
+            self.write_cycles = INTERRUPT_WRITE_CYCLES;
         }
     }
 
-    fn reset_interrupt(&mut self) {
+    fn reset_interrupt(&mut self, bus: &mut Bus) {
         // Decrease stack pointer by 3 without pushing anything to the stack
         self.sp = self.sp.wrapping_sub(3);
 
@@ -181,37 +206,40 @@ impl Cpu {
 
         // Load interrupt handler address into the program counter
         let start_address = 0xFFFC;
-        self.pc = self.read_bus(start_address) as u16
-            + ((self.read_bus(start_address + 1) as u16) << 8) as u16;
+        self.pc = self.read_bus(bus, start_address) as u16
+            + ((self.read_bus(bus, start_address + 1) as u16) << 8) as u16;
 
+        // The pushes are replaced by reads
         self.cycles = 7;
+        self.write_cycles = 0;
     }
 
     // Called when the reset button is pressed on the NES
-    pub fn reset(&mut self) {
-        self.interrupt(Interrupt::Reset);
+    pub fn reset(&mut self, bus: &mut Bus) {
+        self.interrupt(bus, Interrupt::Reset);
     }
 
     // ===== CALLED BY NES =====
 
     // Executes a clock cycle
-    pub fn clock(&mut self) {
+    pub fn clock(&mut self, bus: &mut Bus) -> Result<(), EmulationError> {
         // cycle 0 does the operation and the others do nothing
         if self.cycles == 0 {
             // Get operation code
-            let opcode: u8 = self.read_bus(self.pc);
+            let opcode: u8 = self.read_bus(bus, self.pc);
 
             // Logs
             if self.display_logs {
-                self.display_cpu_log(opcode);
+                self.last_log = Some(self.get_cpu_log(bus, opcode));
             }
 
             // Get instruction information for the operation code
             let instruction: &CpuInstruction = &INSTRUCTIONS[opcode as usize];
             self.require_add_cycle = instruction.add_cycle;
+            self.write_cycles = instruction.write_cycles;
 
             // Execute the instruction
-            (instruction.execute)(self, instruction.adressing_mode);
+            (instruction.execute)(self, bus, instruction.adressing_mode);
 
             // Increase program counter
             self.pc = self.pc.wrapping_add(1);
@@ -222,6 +250,11 @@ impl Cpu {
             self.cycles -= 1;
         }
         self.total_clock = self.total_clock.wrapping_add(1);
+
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
     // Set the program counter at a specific address
@@ -229,10 +262,37 @@ impl Cpu {
         self.pc = address;
     }
 
+    // True if the next clock fetches a new opcode
+    pub fn is_instruction_done(&self) -> bool {
+        self.cycles == 0
+    }
+
+    // True if the next clock is the last cycle of the current instruction
+    pub fn is_on_last_cycle(&self) -> bool {
+        self.cycles == 1
+    }
+
+    // True if the next clock writes to the bus
+    pub fn is_on_write_cycle(&self) -> bool {
+        self.cycles < 8 && self.write_cycles & (1 << self.cycles) != 0
+    }
+
+    pub fn get_registers(&self) -> CpuRegisters {
+        CpuRegisters {
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            pc: self.pc,
+            sp: self.sp,
+            p: self.p,
+            total_clock: self.total_clock,
+        }
+    }
+
     // ===== ADDRESSING MODES =====
 
     // Returns the parameters for the instruction as an address
-    fn fetch_address(&mut self, mode: am) -> u16 {
+    fn fetch_address(&mut self, bus: &mut Bus, mode: am) -> u16 {
         self.page_crossed = false;
         match mode {
             am::Implicit => 0,
@@ -243,23 +303,19 @@ impl Cpu {
             }
             am::ZeroPage => {
                 self.pc += 1;
-                let address: u8 = self.read_bus(self.pc);
+                let address: u8 = self.read_bus(bus, self.pc);
                 address as u16
             }
             am::ZeroPageX => {
                 self.pc += 1;
-                let address: u8 = self.read_bus(self.pc);
+                let address: u8 = self.read_bus(bus, self.pc);
+                self.read_bus(bus, address as u16); // Dummy read while adding the index
                 (address as u16 + self.x as u16) % 0x100
             }
             am::ZeroPageY => {
                 self.pc += 1;
-                let address: u8 = self.read_bus(self.pc);
-                if (address as u16 + self.y as u16) & 0x0100 > 0 {
-                    self.read_bus(
-                        (address as u16 & 0xFF00)
-                            | (((address as u16).wrapping_add(self.x as u16)) & 0x00FF),
-                    ); // Dummy read
-                }
+                let address: u8 = self.read_bus(bus, self.pc);
+                self.read_bus(bus, address as u16); // Dummy read while adding the index
                 (address as u16 + self.y as u16) % 0x100
             }
             am::Relative => {
@@ -268,75 +324,75 @@ impl Cpu {
             }
             am::Absolute => {
                 self.pc += 1;
-                let lo: u8 = self.read_bus(self.pc);
+                let lo: u8 = self.read_bus(bus, self.pc);
                 self.pc += 1;
-                let hi: u8 = self.read_bus(self.pc);
+                let hi: u8 = self.read_bus(bus, self.pc);
                 lo as u16 + ((hi as u16) << 8)
             }
             am::AbsoluteX => {
                 self.pc += 1;
-                let lo: u8 = self.read_bus(self.pc);
+                let lo: u8 = self.read_bus(bus, self.pc);
                 self.pc += 1;
-                let hi: u8 = self.read_bus(self.pc);
+                let hi: u8 = self.read_bus(bus, self.pc);
                 let address: u16 = lo as u16 + ((hi as u16) << 8);
                 let result = (address as u32 + self.x as u32) as u16;
                 if (result & 0xFF00) != (address & 0xFF00) {
                     if self.require_add_cycle {
                         self.cycles += 1;
                     }
-                    self.read_bus((address & 0xFF00) | (result & 0x00FF)); // Dummy read
+                    self.read_bus(bus, (address & 0xFF00) | (result & 0x00FF)); // Dummy read
                     self.page_crossed = true;
                 }
                 result
             }
             am::AbsoluteY => {
                 self.pc += 1;
-                let lo: u8 = self.read_bus(self.pc);
+                let lo: u8 = self.read_bus(bus, self.pc);
                 self.pc += 1;
-                let hi: u8 = self.read_bus(self.pc);
+                let hi: u8 = self.read_bus(bus, self.pc);
                 let address: u16 = lo as u16 + ((hi as u16) << 8);
                 let result = (address as u32 + self.y as u32) as u16;
                 if (result & 0xFF00) != (address & 0xFF00) {
                     if self.require_add_cycle {
                         self.cycles += 1;
                     }
-                    self.read_bus((address & 0xFF00) | (result & 0x00FF)); // Dummy read
+                    self.read_bus(bus, (address & 0xFF00) | (result & 0x00FF)); // Dummy read
                     self.page_crossed = true;
                 }
                 result
             }
             am::Indirect => {
                 self.pc += 1;
-                let lo: u8 = self.read_bus(self.pc);
+                let lo: u8 = self.read_bus(bus, self.pc);
                 self.pc += 1;
-                let hi: u8 = self.read_bus(self.pc);
+                let hi: u8 = self.read_bus(bus, self.pc);
                 let ptr: u16 = lo as u16 + ((hi as u16) << 8);
                 let (address_lo, address_hi) = if lo == 0xFF {
                     // Hardware bug
-                    (self.read_bus(ptr), self.read_bus(ptr & 0xFF00))
+                    (self.read_bus(bus, ptr), self.read_bus(bus, ptr & 0xFF00))
                 } else {
-                    (self.read_bus(ptr), self.read_bus(ptr + 1))
+                    (self.read_bus(bus, ptr), self.read_bus(bus, ptr + 1))
                 };
                 address_lo as u16 + ((address_hi as u16) << 8)
             }
             am::IndirectX => {
                 self.pc += 1;
-                let ptr_lo: u16 = (self.read_bus(self.pc) as u16 + self.x as u16) % 0x100; // address in the 0x00 page
+                let ptr_lo: u16 = (self.read_bus(bus, self.pc) as u16 + self.x as u16) % 0x100; // address in the 0x00 page
                 let ptr_hi: u16 = (ptr_lo + 1) % 0x100;
-                self.read_bus(ptr_lo) as u16 + ((self.read_bus(ptr_hi) as u16) << 8)
+                self.read_bus(bus, ptr_lo) as u16 + ((self.read_bus(bus, ptr_hi) as u16) << 8)
             }
             am::IndirectY => {
                 self.pc += 1;
-                let ptr_lo: u16 = (self.read_bus(self.pc) as u16) % 0x100; // address in the 0x00 page
+                let ptr_lo: u16 = (self.read_bus(bus, self.pc) as u16) % 0x100; // address in the 0x00 page
                 let ptr_hi: u16 = (ptr_lo + 1) % 0x100;
                 let address: u16 =
-                    self.read_bus(ptr_lo) as u16 + ((self.read_bus(ptr_hi) as u16) << 8);
+                    self.read_bus(bus, ptr_lo) as u16 + ((self.read_bus(bus, ptr_hi) as u16) << 8);
                 let result: u16 = ((address as u32 + self.y as u32) % 0x1_0000) as u16;
                 if (result & 0xFF00) != (address & 0xFF00) {
                     if self.require_add_cycle {
                         self.cycles += 1;
                     }
-                    self.read_bus((address & 0xFF00) | (result & 0x00FF)); // Dummy read
+                    self.read_bus(bus, (address & 0xFF00) | (result & 0x00FF)); // Dummy read
                     self.page_crossed = true;
                 }
                 result
@@ -351,9 +407,10 @@ impl Cpu {
 
     // Add with carry
     // A,Z,C,N = A+M+C
-    pub fn adc(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    // The decimal mode of the 6502 is not wired on the 2A03, the D flag has no effect here
+    pub fn adc(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result: u16 = self.a as u16 + data as u16 + self.get_flag(Flag::Carry) as u16;
         let previous_a: u8 = self.a;
         self.a = result as u8;
@@ -368,9 +425,9 @@ impl Cpu {
 
     // Logical and
     // A,Z,N = A & M
-    pub fn and(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn and(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a &= data;
         self.set_flag(Flag::Zero, self.a == 0);
         self.set_flag(Flag::Negative, self.a & 0x80 == 0x80);
@@ -378,16 +435,16 @@ impl Cpu {
 
     // Arithmetic shift left
     // A,Z,C,N = M*2 or M,Z,C,N = M*2
-    pub fn asl(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u16 = self.read_bus(address) as u16;
+    pub fn asl(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u16 = self.read_bus(bus, address) as u16;
         let result = if mode == am::Accumulator {
             let shifted = (self.a as u16) << 1;
             self.a = shifted as u8;
             shifted
         } else {
             let shifted = data << 1;
-            self.write_bus(address, shifted as u8);
+            self.write_bus(bus, address, shifted as u8);
             shifted
         };
         self.set_flag(Flag::Carry, (result & 0xFF00) > 0);
@@ -397,10 +454,10 @@ impl Cpu {
 
     // Branch if carry clear
     // (C = 0) => pc += addr
-    pub fn bcc(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bcc(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if !self.get_flag(Flag::Carry) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != ((self.pc + 1) & 0xFF00) {
@@ -412,10 +469,10 @@ impl Cpu {
 
     // Branch if carry set
     // (C = 1) => pc += addr
-    pub fn bcs(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bcs(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if self.get_flag(Flag::Carry) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != ((self.pc + 1) & 0xFF00) {
@@ -427,10 +484,10 @@ impl Cpu {
 
     // Branch if equal
     // (Z = 1) => pc += addr
-    pub fn beq(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn beq(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if self.get_flag(Flag::Zero) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != ((self.pc + 1) & 0xFF00) {
@@ -442,9 +499,9 @@ impl Cpu {
 
     // Bit test
     // A & M, N = M7, V = M6
-    pub fn bit(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn bit(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result: u8 = self.a & data;
         self.set_flag(Flag::Zero, result == 0x00);
         self.set_flag(Flag::Negative, (data & 0x80) > 0);
@@ -453,10 +510,10 @@ impl Cpu {
 
     // Branch if minus
     // (N = 1) => pc += addr
-    pub fn bmi(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bmi(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if self.get_flag(Flag::Negative) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != ((self.pc + 1) & 0xFF00) {
@@ -468,10 +525,10 @@ impl Cpu {
 
     // Branch if not equal
     // (Z = 0) => pc += addr
-    pub fn bne(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bne(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if !self.get_flag(Flag::Zero) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != ((self.pc + 1) & 0xFF00) {
@@ -483,10 +540,10 @@ impl Cpu {
 
     // Branch if positive
     // (N = 0) => pc += addr
-    pub fn bpl(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bpl(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if !self.get_flag(Flag::Negative) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != ((self.pc + 1) & 0xFF00) {
@@ -497,19 +554,19 @@ impl Cpu {
     }
 
     // Force interrupt
-    pub fn brk(&mut self, _: am) {
+    pub fn brk(&mut self, bus: &mut Bus, _: am) {
         self.pc += 1;
         self.set_flag(Flag::Break, true);
         self.set_flag(Flag::Unused, true);
-        self.interrupt(Interrupt::Irq);
+        self.interrupt(bus, Interrupt::Irq);
     }
 
     // Branch if overflow clear
     // (V = 0) => pc += addr
-    pub fn bvc(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bvc(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if !self.get_flag(Flag::Overflow) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != (self.pc & 0xFF00) {
@@ -521,10 +578,10 @@ impl Cpu {
 
     // Branch if overflow set
     // (V = 1) => pc += addr
-    pub fn bvs(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn bvs(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         if self.get_flag(Flag::Overflow) {
-            let data: i8 = self.read_bus(address) as i8;
+            let data: i8 = self.read_bus(bus, address) as i8;
             let result: i16 = self.pc as i16 + data as i16;
             self.cycles += 1;
             if ((result + 1) as u16 & 0xFF00) != (self.pc & 0xFF00) {
@@ -536,33 +593,33 @@ impl Cpu {
 
     // Clear carry flag
     // C = 0
-    pub fn clc(&mut self, _: am) {
+    pub fn clc(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::Carry, false);
     }
 
     // Clear decimal mode
     // D = 0
-    pub fn cld(&mut self, _: am) {
+    pub fn cld(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::Decimal, false);
     }
 
     // Clear interrupt disable
     // I = 0
-    pub fn cli(&mut self, _: am) {
+    pub fn cli(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::InterruptDisable, false);
     }
 
     // Clear overflow flag
     // V = 0
-    pub fn clv(&mut self, _: am) {
+    pub fn clv(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::Overflow, false);
     }
 
     // Compare
     // Z,C,N = A-M
-    pub fn cmp(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn cmp(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result = self.a as i16 - data as i16;
         self.set_flag(Flag::Zero, result as u8 == 0x00);
         self.set_flag(Flag::Carry, self.a >= data);
@@ -571,9 +628,9 @@ impl Cpu {
 
     // Compare x register
     // Z,C,N = X-M
-    pub fn cpx(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn cpx(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result = self.x as i16 - data as i16;
         self.set_flag(Flag::Zero, result as u8 == 0x00);
         self.set_flag(Flag::Carry, self.x >= data);
@@ -582,9 +639,9 @@ impl Cpu {
 
     // Compare y register
     // Z,C,N = Y-M
-    pub fn cpy(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn cpy(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result = self.y as i16 - data as i16;
         self.set_flag(Flag::Zero, result as u8 == 0x00);
         self.set_flag(Flag::Carry, self.y >= data);
@@ -593,17 +650,17 @@ impl Cpu {
 
     // Decrement memory
     // M,Z,N = M-1
-    pub fn dec(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address).wrapping_sub(1);
-        self.write_bus(address, data);
+    pub fn dec(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address).wrapping_sub(1);
+        self.write_bus(bus, address, data);
         self.set_flag(Flag::Zero, data == 0);
         self.set_flag(Flag::Negative, data & 0x80 > 0);
     }
 
     // Decrement x register
     // X,Z,N = X-1
-    pub fn dex(&mut self, _: am) {
+    pub fn dex(&mut self, _: &mut Bus, _: am) {
         self.x = self.x.wrapping_sub(1);
         self.set_flag(Flag::Zero, self.x == 0);
         self.set_flag(Flag::Negative, self.x & 0x80 > 0);
@@ -611,7 +668,7 @@ impl Cpu {
 
     // Decrement y register
     // Y,Z,N = Y-1
-    pub fn dey(&mut self, _: am) {
+    pub fn dey(&mut self, _: &mut Bus, _: am) {
         self.y = self.y.wrapping_sub(1);
         self.set_flag(Flag::Zero, self.y == 0);
         self.set_flag(Flag::Negative, self.y & 0x80 > 0);
@@ -619,9 +676,9 @@ impl Cpu {
 
     // Exclusive or
     // A,Z,N = A^M
-    pub fn eor(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn eor(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a ^= data;
         self.set_flag(Flag::Zero, self.a == 0x00);
         self.set_flag(Flag::Negative, self.a & 0x80 > 0);
@@ -629,18 +686,18 @@ impl Cpu {
 
     // Increment memory
     // M,Z,N = M+1
-    pub fn inc(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn inc(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result = data.wrapping_add(1);
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
         self.set_flag(Flag::Zero, result == 0);
         self.set_flag(Flag::Negative, result & 0x80 > 0);
     }
 
     // Increment x register
     // X,Z,N = X+1
-    pub fn inx(&mut self, _: am) {
+    pub fn inx(&mut self, _: &mut Bus, _: am) {
         self.x = self.x.wrapping_add(1);
         self.set_flag(Flag::Zero, self.x == 0);
         self.set_flag(Flag::Negative, self.x & 0x80 > 0);
@@ -648,7 +705,7 @@ impl Cpu {
 
     // Increment y register
     // Y,Z,N = Y+1
-    pub fn iny(&mut self, _: am) {
+    pub fn iny(&mut self, _: &mut Bus, _: am) {
         self.y = self.y.wrapping_add(1);
         self.set_flag(Flag::Zero, self.y == 0);
         self.set_flag(Flag::Negative, self.y & 0x80 > 0);
@@ -656,24 +713,24 @@ impl Cpu {
 
     // Jump
     // pc = addr
-    pub fn jmp(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn jmp(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         self.pc = address.wrapping_sub(1);
     }
 
     // Jump to subroutine
-    pub fn jsr(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        self.push_to_stack((self.pc >> 8) as u8);
-        self.push_to_stack(self.pc as u8);
+    pub fn jsr(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        self.push_to_stack(bus, (self.pc >> 8) as u8);
+        self.push_to_stack(bus, self.pc as u8);
         self.pc = address.wrapping_sub(1);
     }
 
     // Load accumulator
     // A,Z,N = M
-    pub fn lda(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn lda(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a = data;
         self.set_flag(Flag::Zero, self.a == 0);
         self.set_flag(Flag::Negative, self.a & 0x80 > 0);
@@ -681,9 +738,9 @@ impl Cpu {
 
     // Load x register
     // X,Z,N = M
-    pub fn ldx(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn ldx(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.x = data;
         self.set_flag(Flag::Zero, self.x == 0);
         self.set_flag(Flag::Negative, self.x & 0x80 > 0);
@@ -691,9 +748,9 @@ impl Cpu {
 
     // Load y register
     // Y,Z,N = M
-    pub fn ldy(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn ldy(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.y = data;
         self.set_flag(Flag::Zero, self.y == 0);
         self.set_flag(Flag::Negative, self.y & 0x80 > 0);
@@ -701,9 +758,9 @@ impl Cpu {
 
     // Logical shift right
     // A,C,Z,N = A/2 or M,C,Z,N = M/2
-    pub fn lsr(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u16 = self.read_bus(address) as u16;
+    pub fn lsr(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u16 = self.read_bus(bus, address) as u16;
         let (previous, result) = if mode == am::Accumulator {
             let p = self.a;
             self.a >>= 1;
@@ -711,7 +768,7 @@ impl Cpu {
         } else {
             let p = data as u8;
             let r = ((data >> 1) & 0x00FF) as u8;
-            self.write_bus(address, r);
+            self.write_bus(bus, address, r);
             (p, r)
         };
         self.set_flag(Flag::Carry, (previous & 0x01) > 0);
@@ -720,15 +777,19 @@ impl Cpu {
     }
 
     // No operation
-    pub fn nop(&mut self, mode: am) {
-        self.fetch_address(mode);
+    // The unofficial NOPs with an operand still read it from memory
+    pub fn nop(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        if mode != am::Implicit {
+            self.read_bus(bus, address);
+        }
     }
 
     // Logical inclusive or
     // A,Z,N = A|M
-    pub fn ora(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn ora(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a |= data;
         self.set_flag(Flag::Zero, self.a == 0x00);
         self.set_flag(Flag::Negative, self.a & 0x80 > 0);
@@ -736,28 +797,28 @@ impl Cpu {
 
     // Push accumulator
     // A => stack
-    pub fn pha(&mut self, _: am) {
-        self.push_to_stack(self.a);
+    pub fn pha(&mut self, bus: &mut Bus, _: am) {
+        self.push_to_stack(bus, self.a);
     }
 
     // Push processor status
     // status => stack
-    pub fn php(&mut self, _: am) {
-        self.push_to_stack(self.p | Flag::Break as u8 | Flag::Unused as u8);
+    pub fn php(&mut self, bus: &mut Bus, _: am) {
+        self.push_to_stack(bus, self.p | Flag::Break as u8 | Flag::Unused as u8);
     }
 
     // Pull accumulator
     // A <= stack
-    pub fn pla(&mut self, _: am) {
-        self.a = self.pop_from_stack();
+    pub fn pla(&mut self, bus: &mut Bus, _: am) {
+        self.a = self.pop_from_stack(bus);
         self.set_flag(Flag::Zero, self.a == 0x00);
         self.set_flag(Flag::Negative, self.a & 0x80 > 0);
     }
 
     // Pull processor status
     // status <= stack
-    pub fn plp(&mut self, _: am) {
-        let status: u8 = self.pop_from_stack();
+    pub fn plp(&mut self, bus: &mut Bus, _: am) {
+        let status: u8 = self.pop_from_stack(bus);
         self.set_flag(
             Flag::Carry,
             status & (Flag::Carry as u8) == Flag::Carry as u8,
@@ -783,9 +844,9 @@ impl Cpu {
     }
 
     // Rotate left
-    pub fn rol(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn rol(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let (previous, result) = if mode == am::Accumulator {
             let p = self.a;
             self.a = (self.a << 1) + (self.get_flag(Flag::Carry) as u8);
@@ -793,7 +854,7 @@ impl Cpu {
         } else {
             let p = data;
             let r = (data << 1) + (self.get_flag(Flag::Carry) as u8);
-            self.write_bus(address, r);
+            self.write_bus(bus, address, r);
             (p, r)
         };
         self.set_flag(Flag::Carry, (previous & 0x80) > 0);
@@ -802,9 +863,9 @@ impl Cpu {
     }
 
     // Rotate right
-    pub fn ror(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn ror(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let (previous, result) = if mode == am::Accumulator {
             let p = self.a;
             self.a = (self.a >> 1) + (self.get_flag(Flag::Carry) as u8 * 0x80);
@@ -812,7 +873,7 @@ impl Cpu {
         } else {
             let p = data;
             let r = (data >> 1) + (self.get_flag(Flag::Carry) as u8 * 0x80);
-            self.write_bus(address, r);
+            self.write_bus(bus, address, r);
             (p, r)
         };
         self.set_flag(Flag::Carry, (previous & 0x01) > 0);
@@ -822,8 +883,8 @@ impl Cpu {
 
     // Return from interrupt
     // status <= stack, pc <= stack
-    pub fn rti(&mut self, _: am) {
-        let status: u8 = self.pop_from_stack();
+    pub fn rti(&mut self, bus: &mut Bus, _: am) {
+        let status: u8 = self.pop_from_stack(bus);
         self.set_flag(
             Flag::Carry,
             status & (Flag::Carry as u8) == Flag::Carry as u8,
@@ -846,22 +907,25 @@ impl Cpu {
             Flag::Negative,
             status & (Flag::Negative as u8) == Flag::Negative as u8,
         );
-        let address: u16 = self.pop_from_stack() as u16 + ((self.pop_from_stack() as u16) << 8);
+        let address: u16 =
+            self.pop_from_stack(bus) as u16 + ((self.pop_from_stack(bus) as u16) << 8);
         self.pc = address.wrapping_sub(1);
     }
 
     // Return from subroutine
     // pc - 1 <= stack
-    pub fn rts(&mut self, _: am) {
-        let address: u16 = self.pop_from_stack() as u16 + ((self.pop_from_stack() as u16) << 8);
+    pub fn rts(&mut self, bus: &mut Bus, _: am) {
+        let address: u16 =
+            self.pop_from_stack(bus) as u16 + ((self.pop_from_stack(bus) as u16) << 8);
         self.pc = address;
     }
 
     // Substract with carry
     // A,Z,C,N = A-M-(1-C)
-    pub fn sbc(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let original_data: u8 = self.read_bus(address);
+    // As for ADC, the subtraction is binary even if the D flag is set
+    pub fn sbc(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let original_data: u8 = self.read_bus(bus, address);
         let data: u8 = original_data ^ 0xFF; // Converts data into a negative value + 1
         let result: u16 = self.a as u16 + data as u16 + self.get_flag(Flag::Carry) as u16;
         let previous_a: u8 = self.a;
@@ -878,46 +942,46 @@ impl Cpu {
 
     // Set carry flag
     // C = 1
-    pub fn sec(&mut self, _: am) {
+    pub fn sec(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::Carry, true);
     }
 
     // Set decimal flag
     // D = 1
-    pub fn sed(&mut self, _: am) {
+    pub fn sed(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::Decimal, true);
     }
 
     // Set interrupt disable
     // I = 1
-    pub fn sei(&mut self, _: am) {
+    pub fn sei(&mut self, _: &mut Bus, _: am) {
         self.set_flag(Flag::InterruptDisable, true);
     }
 
     // Store accumulator
     // M = A
-    pub fn sta(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        self.write_bus(address, self.a);
+    pub fn sta(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        self.write_bus(bus, address, self.a);
     }
 
     // Store x register
     // M = X
-    pub fn stx(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        self.write_bus(address, self.x);
+    pub fn stx(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        self.write_bus(bus, address, self.x);
     }
 
     // Store y register
     // M = Y
-    pub fn sty(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        self.write_bus(address, self.y);
+    pub fn sty(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        self.write_bus(bus, address, self.y);
     }
 
     // Transfer accumulator to x
     // X = A
-    pub fn tax(&mut self, _: am) {
+    pub fn tax(&mut self, _: &mut Bus, _: am) {
         self.x = self.a;
         self.set_flag(Flag::Zero, self.x == 0x00);
         self.set_flag(Flag::Negative, (self.x & 0x80) > 0);
@@ -925,7 +989,7 @@ impl Cpu {
 
     // Transfer accumulator to y
     // Y = A
-    pub fn tay(&mut self, _: am) {
+    pub fn tay(&mut self, _: &mut Bus, _: am) {
         self.y = self.a;
         self.set_flag(Flag::Zero, self.y == 0x00);
         self.set_flag(Flag::Negative, (self.y & 0x80) > 0);
@@ -933,7 +997,7 @@ impl Cpu {
 
     // Transfer stack pointer to x
     // X = S
-    pub fn tsx(&mut self, _: am) {
+    pub fn tsx(&mut self, _: &mut Bus, _: am) {
         self.x = self.sp;
         self.set_flag(Flag::Zero, self.x == 0x00);
         self.set_flag(Flag::Negative, (self.x & 0x80) > 0);
@@ -941,7 +1005,7 @@ impl Cpu {
 
     // Transfer x to accumulator
     // A = X
-    pub fn txa(&mut self, _: am) {
+    pub fn txa(&mut self, _: &mut Bus, _: am) {
         self.a = self.x;
         self.set_flag(Flag::Zero, self.a == 0x00);
         self.set_flag(Flag::Negative, (self.a & 0x80) > 0);
@@ -949,13 +1013,13 @@ impl Cpu {
 
     // Transfer x to stack pointer
     // S = X
-    pub fn txs(&mut self, _: am) {
+    pub fn txs(&mut self, _: &mut Bus, _: am) {
         self.sp = self.x;
     }
 
     // Transfer y to accumulator
     // A = Y
-    pub fn tya(&mut self, _: am) {
+    pub fn tya(&mut self, _: &mut Bus, _: am) {
         self.a = self.y;
         self.set_flag(Flag::Zero, self.a == 0x00);
         self.set_flag(Flag::Negative, (self.a & 0x80) > 0);
@@ -965,23 +1029,21 @@ impl Cpu {
 
     // Same as AND, with C flag
     // A,C,Z,N = A & M
-    pub fn anc(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn anc(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a &= data;
         self.set_flag(Flag::Zero, self.a == 0);
         self.set_flag(Flag::Negative, self.a & 0x80 > 0);
         self.set_flag(Flag::Carry, self.a & 0x80 > 0);
     }
 
-    // Same as AND, with x transfered to a
-    // A = X
-    // A,Z,N = A & M
-    pub fn ane(&mut self, mode: am) {
-        self.a = self.x;
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
-        self.a &= data;
+    // Same as ORA #magic + AND X + AND
+    // A,Z,N = (A | magic) & X & M
+    pub fn ane(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
+        self.a = (self.a | self.unstable_opcode_magic) & self.x & data;
         self.set_flag(Flag::Zero, self.a == 0);
         self.set_flag(Flag::Negative, self.a & 0x80 > 0);
     }
@@ -989,9 +1051,9 @@ impl Cpu {
     // Same as AND + ROR
     // C = bit 6
     // V = bit 5 != bit 6
-    pub fn arr(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn arr(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a &= data;
         self.a = (self.a >> 1) + ((self.get_flag(Flag::Carry) as u8) << 7);
         self.set_flag(Flag::Zero, self.a == 0x00);
@@ -1004,9 +1066,9 @@ impl Cpu {
     }
 
     // Same as AND + shift right
-    pub fn asr(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn asr(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let tmp: u8 = self.a & data;
         self.a = (tmp >> 1) as u8;
         self.set_flag(Flag::Carry, (tmp & 0x01) > 0);
@@ -1016,10 +1078,10 @@ impl Cpu {
 
     // Same as DEC + CMP
     // M,C,Z,N = M-1
-    pub fn dcp(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address).wrapping_sub(1);
-        self.write_bus(address, data);
+    pub fn dcp(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address).wrapping_sub(1);
+        self.write_bus(bus, address, data);
         let result = self.a as i16 - data as i16;
         self.set_flag(Flag::Zero, result as u8 == 0x00);
         self.set_flag(Flag::Carry, self.a >= data);
@@ -1029,10 +1091,10 @@ impl Cpu {
     // Same as INC + SBC
     // M = M+1
     // A,Z,C,N,V = A-M-(1-C)
-    pub fn isb(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let inc_data: u8 = self.read_bus(address).wrapping_add(1);
-        self.write_bus(address, inc_data);
+    pub fn isb(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let inc_data: u8 = self.read_bus(bus, address).wrapping_add(1);
+        self.write_bus(bus, address, inc_data);
         let data: u8 = inc_data ^ 0xFF; // Converts data into a negative value + 1
         let result: u16 = self.a as u16 + data as u16 + self.get_flag(Flag::Carry) as u16;
         let previous_a: u8 = self.a;
@@ -1048,9 +1110,9 @@ impl Cpu {
 
     // Same as AND between M and SP
     // SP,A,X,N,Z = SP & M
-    pub fn las(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn las(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let tmp: u8 = self.sp & data;
         self.sp = tmp;
         self.a = tmp;
@@ -1061,31 +1123,32 @@ impl Cpu {
 
     // Same as LDA + LDX
     // A,X,N,Z = M
-    pub fn lax(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn lax(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         self.a = data;
         self.x = data;
         self.set_flag(Flag::Zero, data == 0x00);
         self.set_flag(Flag::Negative, (data & 0x80) > 0);
     }
 
-    // Same as ORA #$EE + AND + TXA
-    pub fn lxa(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
-        self.a = data;
+    // Same as ORA #magic + AND + TAX
+    // A,X,Z,N = (A | magic) & M
+    pub fn lxa(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
+        self.a = (self.a | self.unstable_opcode_magic) & data;
         self.x = self.a;
         self.set_flag(Flag::Zero, self.a == 0x00);
         self.set_flag(Flag::Negative, (self.a & 0x80) > 0);
     }
 
     // Same as ROL + AND
-    pub fn rla(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn rla(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let result: u8 = (data << 1) + (self.get_flag(Flag::Carry) as u8);
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
         self.a &= result;
         self.set_flag(Flag::Carry, (data & 0x80) > 0);
         self.set_flag(Flag::Zero, self.a == 0x00);
@@ -1093,11 +1156,11 @@ impl Cpu {
     }
 
     // Same as ROR + ADC
-    pub fn rra(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn rra(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let rored = (data >> 1) + ((self.get_flag(Flag::Carry) as u8) << 7);
-        self.write_bus(address, rored);
+        self.write_bus(bus, address, rored);
         self.set_flag(Flag::Carry, (data & 0x01) > 0);
 
         let result: u16 = self.a as u16 + rored as u16 + self.get_flag(Flag::Carry) as u16;
@@ -1113,16 +1176,16 @@ impl Cpu {
     }
 
     // M = A & X
-    pub fn sax(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn sax(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         let result: u8 = self.a & self.x;
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
     }
 
     // X = (A&X)-M
-    pub fn sbx(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u8 = self.read_bus(address);
+    pub fn sbx(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u8 = self.read_bus(bus, address);
         let anded = self.x & self.a;
         let result = anded as i16 - data as i16;
         self.x = result as u8;
@@ -1132,51 +1195,51 @@ impl Cpu {
     }
 
     // M = A&X&(h[M]+1)
-    pub fn sha(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn sha(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         let result: u8 = self.a & self.x & (((address & 0xFF00) >> 8) + 1) as u8;
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
     }
 
     // SP = A&X
     // M = A&X&(h[M]+1)
-    pub fn shs(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn shs(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         self.sp = self.a & self.x;
         let result: u8 = self.a & self.x & (((address & 0xFF00) >> 8) + 1) as u8;
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
     }
 
     // M = X&(h[M]+1)
-    pub fn shx(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn shx(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         let result: u8 = self.x & (((address & 0xFF00) >> 8) + 1) as u8;
         let address = if self.page_crossed {
             (address & 0x00FF) + ((result as u16) << 8)
         } else {
             address
         };
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
     }
 
     // M = Y&(h[M]+1)
-    pub fn shy(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
+    pub fn shy(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
         let result: u8 = self.y & (((address & 0xFF00) >> 8) + 1) as u8;
         let address = if self.page_crossed {
             (address & 0x00FF) + ((result as u16) << 8)
         } else {
             address
         };
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
     }
 
     // Same as ASL + ORA
-    pub fn slo(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u16 = self.read_bus(address) as u16;
+    pub fn slo(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u16 = self.read_bus(bus, address) as u16;
         let result = (data as u16) << 1;
-        self.write_bus(address, result as u8);
+        self.write_bus(bus, address, result as u8);
         self.a |= result as u8;
         self.set_flag(Flag::Carry, (result & 0xFF00) > 0);
         self.set_flag(Flag::Zero, self.a == 0x00);
@@ -1184,11 +1247,11 @@ impl Cpu {
     }
 
     // Same as LSR + EOR
-    pub fn sre(&mut self, mode: am) {
-        let address: u16 = self.fetch_address(mode);
-        let data: u16 = self.read_bus(address) as u16;
+    pub fn sre(&mut self, bus: &mut Bus, mode: am) {
+        let address: u16 = self.fetch_address(bus, mode);
+        let data: u16 = self.read_bus(bus, address) as u16;
         let result: u8 = ((data >> 1) & 0x00FF) as u8;
-        self.write_bus(address, result);
+        self.write_bus(bus, address, result);
         self.a ^= result;
         self.set_flag(Flag::Carry, (data & 0x01) > 0);
         self.set_flag(Flag::Zero, self.a == 0x00);
@@ -1196,168 +1259,188 @@ impl Cpu {
     }
 
     // Used for unvalid operation codes
-    pub fn err(&mut self, _: am) {
-        panic!("Encountered an unvalid opcode at {:#X}", self.pc);
+    pub fn err(&mut self, bus: &mut Bus, _: am) {
+        let opcode = self.read_only_bus(bus, self.pc);
+        self.report_error(EmulationError::InvalidOpcode {
+            opcode,
+            address: self.pc,
+        });
     }
 
     // ===== DEBUGGING =====
 
-    fn display_cpu_log(&self, opcode: u8) {
-        let mut instruction_and_parameters_str = format!("{:02X} ", opcode);
-        let mut instruction_parameters: Vec<u8> = vec![];
+    fn get_cpu_log(&self, bus: &Bus, opcode: u8) -> CpuLog {
+        let mut operands: Vec<u8> = vec![];
         for i in 0..INSTRUCTIONS[opcode as usize].bytes - 1 {
-            instruction_parameters.push(self.read_only_bus(self.pc + i as u16 + 1));
-            instruction_and_parameters_str
-                .push_str(&format!("{:02X} ", instruction_parameters[i as usize]));
+            operands.push(self.read_only_bus(bus, self.pc + i as u16 + 1));
         }
-        while instruction_and_parameters_str.len() < 9 {
-            instruction_and_parameters_str.push(' ');
+        CpuLog {
+            pc: self.pc,
+            opcode,
+            disassembly: self.dissassemble(bus, opcode, &operands),
+            operands,
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            p: self.p,
+            sp: self.sp,
+            scanline: bus.get_scanline(),
+            cycle: bus.get_cycles(),
+            total_clock: self.total_clock,
         }
-        let cpu_log: String = format!(
-            "{:04X}  {} {}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
-            self.pc,
-            instruction_and_parameters_str,
-            self.dissassemble(opcode, instruction_parameters),
-            self.a,
-            self.x,
-            self.y,
-            self.p,
-            self.sp
-        );
+    }
 
-        let mut scanline_str = self.p_bus.borrow().get_scanline().to_string();
-        while scanline_str.len() < 3 {
-            scanline_str = format!(" {}", scanline_str);
-        }
-        let mut cycle_str = self.p_bus.borrow().get_cycles().to_string();
-        while cycle_str.len() < 3 {
-            cycle_str = format!(" {}", cycle_str);
+    // Get the log of the last instruction, if logs are enabled
+    pub fn get_last_log(&self) -> Option<CpuLog> {
+        self.last_log.clone()
+    }
+
+    // Decode count instructions starting at the start address, without modifying any state
+    pub fn disassemble_range(
+        &self,
+        bus: &Bus,
+        start: u16,
+        count: usize,
+    ) -> Vec<DisassembledInstruction> {
+        let mut instructions = Vec::with_capacity(count);
+        let mut address = start;
+        for _ in 0..count {
+            let opcode = self.read_only_bus(bus, address);
+            let instruction = &INSTRUCTIONS[opcode as usize];
+            let mut bytes = vec![opcode];
+            for i in 1..instruction.bytes {
+                bytes.push(self.read_only_bus(bus, address.wrapping_add(i as u16)));
+            }
+            instructions.push(DisassembledInstruction {
+                address,
+                mnemonic: String::from(instruction.name),
+                operand: format_operand(address, opcode, &bytes[1..]),
+                bytes,
+            });
+            address = address.wrapping_add(instruction.bytes as u16);
         }
-        let ppu_log = format!("PPU:{},{}", scanline_str, cycle_str);
-
-        println!("{} {} CYC:{}", cpu_log, ppu_log, self.total_clock);
+        instructions
     }
 
-    fn read_only_bus(&self, address: u16) -> u8 {
-        match self.p_bus.borrow().read_only(address) {
-            Ok(data) => data,
-            Err(e) => panic!("{}", e),
-        }
+    // An invalid read returns the open bus value, the logs must not stop the emulation
+    fn read_only_bus(&self, bus: &Bus, address: u16) -> u8 {
+        bus.read_only(address)
+            .unwrap_or_else(|_| bus.get_last_bus_value())
     }
 
-    fn dissassemble(&self, opcode: u8, parameters: Vec<u8>) -> String {
+    fn dissassemble(&self, bus: &Bus, opcode: u8, parameters: &[u8]) -> String {
         let mut dissassembly = String::from(INSTRUCTIONS[opcode as usize].name);
         dissassembly.push(' ');
+        dissassembly.push_str(&format_operand(self.pc, opcode, parameters));
+        // Add the memory used by the instruction to its operand
         match INSTRUCTIONS[opcode as usize].adressing_mode {
-            am::Accumulator => {
-                if (opcode != 0xAA) && (opcode != 0x8A) {
-                    dissassembly.push('A');
-                }
-            }
-            am::Implicit => (),
-            am::Immediate => dissassembly.push_str(&format!("#${:02X}", parameters[0])),
+            am::Accumulator | am::Implicit | am::Immediate | am::Relative | am::NoMode => (),
             am::ZeroPage => {
-                let value: u8 = self.read_only_bus(parameters[0] as u16);
-                dissassembly.push_str(&format!("${:02X} = {:02X}", parameters[0], value));
+                let value: u8 = self.read_only_bus(bus, parameters[0] as u16);
+                dissassembly.push_str(&format!(" = {:02X}", value));
             }
             am::ZeroPageX => {
                 let address: u16 = (parameters[0] as u16 + self.x as u16) % 0x100;
-                let value: u8 = self.read_only_bus(address);
-                dissassembly.push_str(&format!(
-                    "${:02X},X @ {:02X} = {:02X}",
-                    parameters[0], address, value
-                ));
+                let value: u8 = self.read_only_bus(bus, address);
+                dissassembly.push_str(&format!(" @ {:02X} = {:02X}", address, value));
             }
             am::ZeroPageY => {
                 let address: u16 = (parameters[0] as u16 + self.y as u16) % 0x100;
-                let value: u8 = self.read_only_bus(address);
-                dissassembly.push_str(&format!(
-                    "${:02X},Y @ {:02X} = {:02X}",
-                    parameters[0], address, value
-                ));
-            }
-            am::Relative => {
-                dissassembly.push_str(&format!(
-                    "${:04X}",
-                    (self.pc as i16) + 2 + ((parameters[0] as i8) as i16)
-                ));
+                let value: u8 = self.read_only_bus(bus, address);
+                dissassembly.push_str(&format!(" @ {:02X} = {:02X}", address, value));
             }
             am::Absolute => {
                 let address: u16 = parameters[0] as u16 + ((parameters[1] as u16) << 8) as u16;
                 // Don't print value if it's a JMP / JSR
-                if (opcode == 0x4C) || (opcode == 0x20) {
-                    dissassembly.push_str(&format!("${:02X}{:02X}", parameters[1], parameters[0]));
-                } else {
-                    let value: u8 = self.read_only_bus(address);
-                    dissassembly.push_str(&format!(
-                        "${:02X}{:02X} = {:02X}",
-                        parameters[1], parameters[0], value
-                    ));
+                if (opcode != 0x4C) && (opcode != 0x20) {
+                    let value: u8 = self.read_only_bus(bus, address);
+                    dissassembly.push_str(&format!(" = {:02X}", value));
                 }
             }
             am::AbsoluteX => {
                 let address: u16 =
                     (parameters[0] as u32 + ((parameters[1] as u32) << 8) + self.x as u32) as u16;
-                let value: u8 = self.read_only_bus(address);
-                dissassembly.push_str(&format!(
-                    "${:02X}{:02X},X @ {:04X} = {:02X}",
-                    parameters[1], parameters[0], address, value
-                ));
+                let value: u8 = self.read_only_bus(bus, address);
+                dissassembly.push_str(&format!(" @ {:04X} = {:02X}", address, value));
             }
             am::AbsoluteY => {
                 let address: u16 =
                     ((parameters[0] as u32 + ((parameters[1] as u32) << 8) + self.y as u32)
                         % 0x10000) as u16;
-                let value: u8 = self.read_only_bus(address);
-                dissassembly.push_str(&format!(
-                    "${:02X}{:02X},Y @ {:04X} = {:02X}",
-                    parameters[1], parameters[0], address, value
-                ));
+                let value: u8 = self.read_only_bus(bus, address);
+                dissassembly.push_str(&format!(" @ {:04X} = {:02X}", address, value));
             }
             am::Indirect => {
                 let ptr: u16 = parameters[0] as u16 + ((parameters[1] as u16) << 8);
                 let (address_lo, address_hi) = if (ptr & 0x00FF) == 0x00FF {
                     // Hardware bug
-                    (self.read_only_bus(ptr), self.read_only_bus(ptr & 0xFF00))
+                    (
+                        self.read_only_bus(bus, ptr),
+                        self.read_only_bus(bus, ptr & 0xFF00),
+                    )
                 } else {
-                    (self.read_only_bus(ptr), self.read_only_bus(ptr + 1))
+                    (
+                        self.read_only_bus(bus, ptr),
+                        self.read_only_bus(bus, ptr + 1),
+                    )
                 };
                 let address: u16 = address_lo as u16 + ((address_hi as u16) << 8);
-                dissassembly.push_str(&format!(
-                    "(${:02X}{:02X}) = {:04X}",
-                    parameters[1], parameters[0], address
-                ));
+                dissassembly.push_str(&format!(" = {:04X}", address));
             }
             am::IndirectX => {
                 let ptr_lo: u16 = (parameters[0] as u16 + self.x as u16) % 0x100;
                 let ptr_hi: u16 = (ptr_lo + 1) % 0x100;
-                let address: u16 =
-                    self.read_only_bus(ptr_lo) as u16 + ((self.read_only_bus(ptr_hi) as u16) << 8);
-                let value: u8 = self.read_only_bus(address);
+                let address: u16 = self.read_only_bus(bus, ptr_lo) as u16
+                    + ((self.read_only_bus(bus, ptr_hi) as u16) << 8);
+                let value: u8 = self.read_only_bus(bus, address);
                 dissassembly.push_str(&format!(
-                    "(${:02X},X) @ {:02X} = {:04X} = {:02X}",
-                    parameters[0], ptr_lo, address, value
+                    " @ {:02X} = {:04X} = {:02X}",
+                    ptr_lo, address, value
                 ));
             }
             am::IndirectY => {
                 let ptr_lo: u16 = (parameters[0] as u16) % 0x100;
                 let ptr_hi: u16 = (ptr_lo + 1) % 0x100;
-                let address_ptr: u16 =
-                    self.read_only_bus(ptr_lo) as u16 + ((self.read_only_bus(ptr_hi) as u16) << 8);
+                let address_ptr: u16 = self.read_only_bus(bus, ptr_lo) as u16
+                    + ((self.read_only_bus(bus, ptr_hi) as u16) << 8);
                 let address: u16 = ((address_ptr as u32 + self.y as u32) % 0x10000) as u16;
-                let value: u8 = self.read_only_bus(address);
+                let value: u8 = self.read_only_bus(bus, address);
                 dissassembly.push_str(&format!(
-                    "(${:02X}),Y = {:04X} @ {:04X} = {:02X}",
-                    parameters[0], address_ptr, address, value
+                    " = {:04X} @ {:04X} = {:02X}",
+                    address_ptr, address, value
                 ));
             }
-            am::NoMode => {
-                panic!("No mode specified when trying to fetch data !");
-            }
         };
-        while dissassembly.len() < 32 {
-            dissassembly.push(' ');
-        }
         dissassembly
     }
 }
+
+// Format the operand of an instruction located at address, without resolving the memory it uses
+fn format_operand(address: u16, opcode: u8, parameters: &[u8]) -> String {
+    match INSTRUCTIONS[opcode as usize].adressing_mode {
+        am::Accumulator => {
+            if (opcode != 0xAA) && (opcode != 0x8A) {
+                String::from("A")
+            } else {
+                String::new()
+            }
+        }
+        am::Implicit | am::NoMode => String::new(),
+        am::Immediate => format!("#${:02X}", parameters[0]),
+        am::ZeroPage => format!("${:02X}", parameters[0]),
+        am::ZeroPageX => format!("${:02X},X", parameters[0]),
+        am::ZeroPageY => format!("${:02X},Y", parameters[0]),
+        am::Relative => format!(
+            "${:04X}",
+            address
+                .wrapping_add(2)
+                .wrapping_add(parameters[0] as i8 as u16)
+        ),
+        am::Absolute => format!("${:02X}{:02X}", parameters[1], parameters[0]),
+        am::AbsoluteX => format!("${:02X}{:02X},X", parameters[1], parameters[0]),
+        am::AbsoluteY => format!("${:02X}{:02X},Y", parameters[1], parameters[0]),
+        am::Indirect => format!("(${:02X}{:02X})", parameters[1], parameters[0]),
+        am::IndirectX => format!("(${:02X},X)", parameters[0]),
+        am::IndirectY => format!("(${:02X}),Y", parameters[0]),
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::enums::{AdressingMode, Flag};
+use crate::prelude::*;
 use crate::state::Stateful;
 
 #[derive(Serialize, Deserialize)]
@@ -11,12 +13,119 @@ pub struct CpuState {
     sp: u8,
     p: u8,
     cycles: u8,
+    #[serde(default)]
+    write_cycles: u8,
     require_add_cycle: bool,
     page_crossed: bool,
     total_clock: u64,
     display_logs: bool,
 }
 
+/// Data about an instruction executed by the CPU, captured before its execution.
+#[derive(Debug, Clone)]
+pub struct CpuLog {
+    pub pc: u16,
+    pub opcode: u8,
+    pub operands: Vec<u8>,
+    pub disassembly: String,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub p: u8,
+    pub sp: u8,
+    pub scanline: u16,
+    pub cycle: u16,
+    pub total_clock: u64,
+}
+
+/// Registers of the CPU at a given time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuRegisters {
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub pc: u16,
+    pub sp: u8,
+    pub p: u8,
+    pub total_clock: u64,
+}
+
+impl CpuRegisters {
+    /// Decode the status register.
+    pub fn status_flags(&self) -> StatusFlags {
+        StatusFlags::from_u8(self.p)
+    }
+}
+
+/// Flags of the CPU status register.
+/// The break flag only exists in the copies of the register pushed on the stack.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatusFlags {
+    pub carry: bool,
+    pub zero: bool,
+    pub interrupt_disable: bool,
+    pub decimal: bool,
+    pub break_flag: bool,
+    pub overflow: bool,
+    pub negative: bool,
+}
+
+impl StatusFlags {
+    /// Decode the value of the status register.
+    pub fn from_u8(value: u8) -> Self {
+        StatusFlags {
+            carry: value & Flag::Carry as u8 > 0,
+            zero: value & Flag::Zero as u8 > 0,
+            interrupt_disable: value & Flag::InterruptDisable as u8 > 0,
+            decimal: value & Flag::Decimal as u8 > 0,
+            break_flag: value & Flag::Break as u8 > 0,
+            overflow: value & Flag::Overflow as u8 > 0,
+            negative: value & Flag::Negative as u8 > 0,
+        }
+    }
+
+    /// Encode the flags as a value of the status register, the unused bit 5 is always set.
+    pub fn to_u8(&self) -> u8 {
+        [
+            (self.carry, Flag::Carry),
+            (self.zero, Flag::Zero),
+            (self.interrupt_disable, Flag::InterruptDisable),
+            (self.decimal, Flag::Decimal),
+            (self.break_flag, Flag::Break),
+            (self.overflow, Flag::Overflow),
+            (self.negative, Flag::Negative),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(Flag::Unused as u8, |value, (_, flag)| value | flag as u8)
+    }
+}
+
+/// An instruction decoded from the memory, without executing it.
+#[derive(Debug, Clone)]
+pub struct DisassembledInstruction {
+    pub address: u16,
+    /// Opcode followed by the operands of the instruction.
+    pub bytes: Vec<u8>,
+    pub mnemonic: String,
+    /// Operand formatted with its addressing mode. Branch targets are resolved.
+    pub operand: String,
+}
+
+/// Description of an instruction of the CPU instruction set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstructionInfo {
+    pub opcode: u8,
+    pub name: &'static str,
+    pub addressing_mode: AdressingMode,
+    /// Size of the instruction, opcode included.
+    pub bytes: u8,
+    /// Cycles taken, without the extra cycle of a page crossing or a taken branch.
+    pub cycles: u8,
+    /// False for the undocumented and invalid opcodes.
+    pub official: bool,
+}
+
 impl Stateful for super::Cpu {
     type State = CpuState;
 
@@ -29,6 +138,7 @@ impl Stateful for super::Cpu {
             sp: self.sp,
             p: self.p,
             cycles: self.cycles,
+            write_cycles: self.write_cycles,
             require_add_cycle: self.require_add_cycle,
             page_crossed: self.page_crossed,
             total_clock: self.total_clock,
@@ -44,6 +154,7 @@ impl Stateful for super::Cpu {
         self.sp = state.sp;
         self.p = state.p;
         self.cycles = state.cycles;
+        self.write_cycles = state.write_cycles;
         self.require_add_cycle = state.require_add_cycle;
         self.page_crossed = state.page_crossed;
         self.total_clock = state.total_clock;
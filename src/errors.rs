@@ -1,9 +1,12 @@
-use std::{
+use core::{
     error::Error,
     fmt::{Debug, Display},
 };
+#[cfg(feature = "std")]
+use std::io;
 
 use crate::display_and_error_impl;
+use crate::prelude::*;
 
 pub trait InvalidMemoryAccess: Debug {
     fn get_address(&self) -> u16;
@@ -130,11 +133,99 @@ impl InvalidMemoryAccess for InvalidAPURegisterWriteError {
 
 display_and_error_impl!(InvalidAPURegisterWriteError);
 
+/// Error encountered while running the emulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulationError {
+    /// The CPU fetched an opcode it does not implement at the given address.
+    InvalidOpcode { opcode: u8, address: u16 },
+    /// The CPU bus could not be read at the given address.
+    InvalidRead(u16),
+    /// The CPU bus could not be written at the given address.
+    InvalidWrite(u16),
+    /// The expected state was not reached within the given number of PPU cycles.
+    Timeout(u64),
+}
+
+impl Display for EmulationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EmulationError::InvalidOpcode { opcode, address } => {
+                write!(f, "Invalid opcode {:#04X} at {:#X}", opcode, address)
+            }
+            EmulationError::InvalidRead(address) => write!(f, "Invalid read at {:#X}", address),
+            EmulationError::InvalidWrite(address) => write!(f, "Invalid write at {:#X}", address),
+            EmulationError::Timeout(cycles) => write!(f, "Timed out after {} cycles", cycles),
+        }
+    }
+}
+
+impl Error for EmulationError {}
+
+/// Error returned by the NES API.
+#[derive(Debug)]
+pub enum NesError {
+    /// The CPU bus could not be accessed at the given address.
+    InvalidAddress(u16),
+    /// The ROM uses a mapper that is not implemented.
+    UnsupportedMapper(u16),
+    /// The ROM is not a valid iNES, iNES 2.0 or NSF file.
+    InvalidRom(String),
+    /// The operation requires a cartridge to be inserted.
+    NoCartridge,
+    /// The cartridge has no battery backed memory to save.
+    NoPersistentMemory,
+    /// The save file does not match the persistent memory of the cartridge.
+    InvalidSave(String),
+    /// The save state could not be serialized or deserialized.
+    InvalidState(String),
+    /// An argument is out of its valid range or has an incorrect format.
+    InvalidArgument(String),
+    /// Reading or writing a file failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+}
+
+impl Display for NesError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NesError::InvalidAddress(address) => write!(f, "Invalid access at {:#X}", address),
+            NesError::UnsupportedMapper(mapper) => {
+                write!(f, "Mapper {} is not implemented", mapper)
+            }
+            NesError::InvalidRom(reason) => write!(f, "Invalid ROM: {}", reason),
+            NesError::NoCartridge => write!(f, "No cartridge inserted"),
+            NesError::NoPersistentMemory => write!(f, "ROM has no persistent memory"),
+            NesError::InvalidSave(reason) => write!(f, "Invalid save: {}", reason),
+            NesError::InvalidState(reason) => write!(f, "Invalid state: {}", reason),
+            NesError::InvalidArgument(reason) => write!(f, "{}", reason),
+            #[cfg(feature = "std")]
+            NesError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for NesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "std")]
+            NesError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for NesError {
+    fn from(e: io::Error) -> Self {
+        NesError::Io(e)
+    }
+}
+
 #[macro_export]
 macro_rules! display_and_error_impl {
     ($t: ty) => {
         impl Display for $t {
-            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
                 write!(
                     f,
                     "Invalid {} at {:#X}",
@@ -1,5 +1,14 @@
 //! This crate provides an API to run a NES emulator.
 //! See more about the project on [Github](https://github.com/AntoineRR/nesmulator).
+//!
+//! The `std` feature is enabled by default. Without it, the crate is `no_std` and only needs
+//! `alloc`: the files and the serialized save states are not available.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use crate::prelude::*;
 
 /// Contain the NES struct, core of the emulator.
 pub mod nes;
@@ -9,17 +18,99 @@ pub mod utils;
 mod apu;
 mod bus;
 mod cartridge;
+mod cheats;
 mod controllers;
 mod cpu;
 mod errors;
 mod ppu;
 mod state;
 
+// Items of the std prelude coming from alloc, needed by the no_std build
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+    pub use alloc::{format, vec};
+}
+
+pub use apu::{ApuChannel, ApuChannelOutputs};
+pub use cartridge::mapper::{
+    CartridgeInfo, INesHeader, Mapper, MapperBankInfo, MapperFactory, MapperState, Mirroring,
+    RomHash,
+};
+pub use cartridge::nsf::NsfInfo;
+pub use cheats::CheatHandle;
+pub use cpu::enums::AdressingMode as AddressingMode;
+pub use cpu::instructions::{all_instructions, instruction_info};
+pub use cpu::state::{CpuLog, CpuRegisters, DisassembledInstruction, InstructionInfo, StatusFlags};
+pub use errors::{EmulationError, NesError};
+pub use ppu::state::{PpuScrollState, SpriteEntry};
+pub use state::StateDiff;
+
+/// Default rate (Hz) at which the audio samples are produced.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44_100;
+/// Default value used by the unstable opcodes ANE and LXA.
+pub const DEFAULT_UNSTABLE_OPCODE_MAGIC: u8 = 0xFF;
+
+/// Television standard emulated by the NES.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// 60 Hz, 262 scanlines per frame.
+    Ntsc,
+    /// 50 Hz, 312 scanlines per frame.
+    Pal,
+}
+
+/// Content of the CPU RAM when the NES is powered on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RamPattern {
+    /// Every byte is 0.
+    Zeroed,
+    /// Every byte has the given value.
+    Filled(u8),
+    /// The given bytes are repeated over the whole RAM.
+    Pattern(Vec<u8>),
+}
+
+/// Number of pixels hidden on each side of the frame, as on many real displays.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Overscan {
+    pub top: u8,
+    pub bottom: u8,
+    pub left: u8,
+    pub right: u8,
+}
+
+impl Overscan {
+    /// Width of the frame once cropped.
+    pub fn width(&self) -> usize {
+        nes::NES_WIDTH.saturating_sub(self.left as usize + self.right as usize)
+    }
+
+    /// Height of the frame once cropped.
+    pub fn height(&self) -> usize {
+        nes::NES_HEIGHT.saturating_sub(self.top as usize + self.bottom as usize)
+    }
+}
+
 /// Configuration to pass to the emulator.
 #[derive(Clone)]
 pub struct Config {
     pub palette_path: Option<String>,
+    /// Content of a .pal file, used instead of `palette_path` when set.
+    pub palette_data: Option<Vec<u8>>,
     pub display_cpu_logs: bool,
+    /// Rate (Hz) at which the audio samples are produced.
+    /// It must be between 1 Hz and the CPU clock frequency, other rates are clamped.
+    pub sample_rate: u32,
+    pub region: Region,
+    /// Content of the CPU RAM at power on.
+    pub ram_startup_pattern: RamPattern,
+    /// Pixels cropped by `get_cropped_frame_buffer`.
+    pub overscan: Overscan,
+    /// Value ORed with the A register by the unstable opcodes ANE and LXA.
+    /// It depends on the console, 0xFF matches most NES consoles and test ROMs.
+    pub unstable_opcode_magic: u8,
 }
 
 impl Config {
@@ -28,7 +119,13 @@ impl Config {
     pub fn new(palette_path: Option<&str>, display_cpu_logs: bool) -> Self {
         Config {
             palette_path: palette_path.map(str::to_string),
+            palette_data: None,
             display_cpu_logs,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            region: Region::Ntsc,
+            ram_startup_pattern: RamPattern::Zeroed,
+            overscan: Overscan::default(),
+            unstable_opcode_magic: DEFAULT_UNSTABLE_OPCODE_MAGIC,
         }
     }
 
@@ -36,7 +133,13 @@ impl Config {
     pub fn default() -> Self {
         Config {
             palette_path: None,
+            palette_data: None,
             display_cpu_logs: false,
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            region: Region::Ntsc,
+            ram_startup_pattern: RamPattern::Zeroed,
+            overscan: Overscan::default(),
+            unstable_opcode_magic: DEFAULT_UNSTABLE_OPCODE_MAGIC,
         }
     }
 }
@@ -1,44 +1,83 @@
 // ===== IMPORTS =====
 
-use std::cell::RefCell;
-use std::error::Error;
-use std::fs::File;
-use std::rc::Rc;
-use std::time::Duration;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::mem;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::fs::{self, File};
 
-use log::debug;
+#[cfg(feature = "std")]
+use log::{debug, warn};
 
-use crate::apu::Apu;
+use crate::apu::{Apu, ApuChannel, ApuChannelOutputs};
 use crate::bus::Bus;
-use crate::cartridge::mapper::{get_mapper, Mapper};
-use crate::cpu::{enums::Interrupt, Cpu};
-use crate::ppu::Ppu;
+#[cfg(feature = "std")]
+use crate::cartridge::mapper::get_mapper;
+use crate::cartridge::mapper::{
+    get_mapper_from_bytes, CartridgeInfo, MapperBankInfo, MapperFactory, Mirroring, RomHash,
+};
+use crate::cartridge::nsf::{Nsf, NsfInfo, NsfMapper};
+use crate::cheats::{Cheat, CheatHandle};
+use crate::controllers::Zapper;
+use crate::cpu::{
+    enums::Interrupt,
+    state::{CpuLog, CpuRegisters, DisassembledInstruction},
+    Cpu,
+};
+use crate::errors::{EmulationError, NesError};
+use crate::ppu::{
+    state::{PpuScrollState, SpriteEntry},
+    Ppu,
+};
+use crate::prelude::*;
+#[cfg(feature = "std")]
+use crate::state::StateDiff;
 use crate::state::{NesState, Stateful};
-use crate::utils::ARGBColor;
-use crate::Config;
+use crate::utils::{ARGBColor, Button, ControllerPort};
+use crate::{Config, Region};
 
 // ===== CONSTANTS =====
 
 /// Frequency at which the PPU of a NTSC NES is clocked (Hz).
 pub const PPU_CLOCK_FREQUENCY: u64 = 5_369_318;
+/// Frequency at which the PPU of a PAL NES is clocked (Hz).
+pub const PAL_PPU_CLOCK_FREQUENCY: u64 = 5_320_342;
+/// Width of the frames produced by the PPU, in pixels.
+pub const NES_WIDTH: usize = 256;
+/// Height of the frames produced by the PPU, in pixels.
+pub const NES_HEIGHT: usize = 240;
+/// Number of pixels of a frame buffer.
+pub const FRAME_BUFFER_LEN: usize = NES_WIDTH * NES_HEIGHT;
+// CPU cycles between the detection of an NMI edge and the moment the CPU can poll it
+const NMI_POLL_DELAY: u8 = 2;
 
-type MapperRc = Rc<RefCell<Box<dyn Mapper>>>;
+// ===== TYPE ALIAS =====
+
+/// Callback called with the frame buffer each time a frame is completed.
+pub type FrameCallback = Box<dyn FnMut(&[ARGBColor; FRAME_BUFFER_LEN]) + Send + Sync>;
+/// Callback called with the scanline number each time a visible scanline is rendered.
+pub type ScanlineCallback = Box<dyn FnMut(u16) + Send + Sync>;
+/// Callback called with the 7-bit output level of the DMC each time it produces one.
+pub type DmcSampleCallback = Box<dyn FnMut(u8) + Send + Sync>;
+/// Callback called each time the CPU takes an NMI interrupt.
+pub type NmiCallback = Box<dyn FnMut() + Send + Sync>;
 
 // ===== NES STRUCT =====
 
 /// Represent a NES. This will create the NES architecture and provide an API to run the emulation.
 pub struct NES {
     // NES components
-    p_bus: Rc<RefCell<Bus>>,
-    p_cpu: Rc<RefCell<Cpu>>,
-    p_ppu: Rc<RefCell<Ppu>>,
-    p_apu: Rc<RefCell<Apu>>,
+    // The bus owns the PPU and the APU, and the cartridge is plugged on the PPU bus
+    bus: Bus,
+    cpu: Cpu,
 
-    // Mapper
-    o_p_mapper: Option<MapperRc>,
+    // Mappers registered by the user, by mapper number
+    mapper_factories: BTreeMap<u16, MapperFactory>,
 
     // NES clock counter
     total_clock: u64,
+    // Number of frames completed by the PPU
+    frame_count: u64,
 
     // DMA variables
     dma_started: bool,
@@ -46,17 +85,66 @@ pub struct NES {
     dma_base_address: u8,
     dma_address_offset: u8,
     dma_data: u8,
+    // CPU cycles left before the DMC DMA pausing the OAM DMA fetches its sample byte
+    dmc_dma_cycles: u8,
+    // The DMC memory reader needs a sample byte, the DMC DMA halts the CPU on its next read cycle
+    dmc_dma_requested: bool,
+    // CPU cycles the requested DMC DMA has halted the CPU for
+    dmc_dma_halt_cycles: u8,
+
+    // CPU cycles before the detected NMI edge can be polled by the CPU
+    o_nmi_cycles_before_poll: Option<u8>,
+    // The NMI was enabled on the last cycle of the current instruction, it is polled by the next one
+    nmi_delayed: bool,
+    // NMI polled by the CPU, taken before the next instruction
+    nmi_polled: bool,
+    // IRQ triggered with `trigger_irq`, taken before the next instruction
+    irq_triggered: bool,
 
     // Audio
     add_samples: bool,
     samples: Vec<f32>,
+    stereo_samples: Vec<(f32, f32)>,
+    // Samples produced since the last frame was completed, and during the last completed frame
+    current_frame_samples: Vec<f32>,
+    last_frame_samples: Vec<f32>,
+    // Samples produced by the APU and samples that should have been produced at the sample rate,
+    // counted since the last reset or state load
+    produced_samples: u64,
+    expected_samples: f64,
+    samples_per_cpu_cycle: f64,
+
+    // Cheats
+    next_cheat_id: u32,
+
+    // Called with the frame buffer each time a frame is completed
+    o_frame_callback: Option<FrameCallback>,
+    // Called with the scanline number at the end of each visible scanline
+    o_scanline_callback: Option<ScanlineCallback>,
+    // Called each time the CPU takes an NMI interrupt
+    o_nmi_callback: Option<NmiCallback>,
+
+    // NSF file loaded instead of a cartridge
+    o_nsf: Option<Nsf>,
+
+    // Serialized states captured at the end of the last frames, the most recent at the back
+    #[cfg(feature = "std")]
+    rewind_max_frames: usize,
+    #[cfg(feature = "std")]
+    rewind_states: VecDeque<Vec<u8>>,
+
+    // Inputs recorded and inputs to replay, as (clock, controller port, input)
+    o_input_recording: Option<Vec<(u64, ControllerPort, u8)>>,
+    input_replay: VecDeque<(u64, ControllerPort, u8)>,
+
+    // Path of the save file and number of frames between two automatic saves of the persistent memory
+    #[cfg(feature = "std")]
+    o_autosave: Option<(String, u64)>,
 
     // Configuration
     config: Config,
 }
 
-unsafe impl Send for NES {}
-
 impl Default for NES {
     fn default() -> Self {
         NES::new()
@@ -72,36 +160,70 @@ impl NES {
     }
 
     /// Create a NES using a custom configuration.
-    pub fn from_config(config: Config) -> Self {
-        let p_ppu = Rc::new(RefCell::new(Ppu::new(&config.palette_path)));
-        let p_apu = Rc::new(RefCell::new(Apu::new(PPU_CLOCK_FREQUENCY)));
-        let p_bus = Rc::new(RefCell::new(Bus::new(p_ppu.clone(), p_apu.clone())));
-        let p_cpu = Rc::new(RefCell::new(Cpu::new(
-            p_bus.clone(),
-            config.display_cpu_logs,
-        )));
-        p_apu
-            .borrow_mut()
-            .attach_bus_and_cpu(p_bus.clone(), p_cpu.clone());
+    /// The sample rate is clamped between 1 Hz and the CPU clock frequency.
+    pub fn from_config(mut config: Config) -> Self {
+        // At least one sample every CPU cycle, and no division by 0 in the audio filters
+        config.sample_rate = config
+            .sample_rate
+            .clamp(1, get_cpu_clock_frequency(config.region) as u32);
+        let ppu = Ppu::new(&config.palette_path, &config.palette_data, config.region);
+        let apu = Apu::new(
+            get_cpu_clock_frequency(config.region),
+            config.sample_rate,
+            config.region,
+        );
 
         NES {
-            p_bus,
-            p_cpu,
-            p_ppu,
-            p_apu,
+            bus: Bus::new(ppu, apu, config.ram_startup_pattern.clone()),
+            cpu: Cpu::new(config.display_cpu_logs, config.unstable_opcode_magic),
 
-            o_p_mapper: None,
+            mapper_factories: BTreeMap::new(),
 
             total_clock: 0,
+            frame_count: 0,
 
             dma_started: false,
             dma_hi_address: 0,
             dma_base_address: 0,
             dma_address_offset: 0,
             dma_data: 0,
+            dmc_dma_cycles: 0,
+            dmc_dma_requested: false,
+            dmc_dma_halt_cycles: 0,
+
+            o_nmi_cycles_before_poll: None,
+            nmi_delayed: false,
+            nmi_polled: false,
+            irq_triggered: false,
 
             add_samples: true,
             samples: Vec::with_capacity(1024),
+            stereo_samples: vec![],
+            current_frame_samples: Vec::with_capacity(1024),
+            last_frame_samples: vec![],
+            produced_samples: 0,
+            expected_samples: 0.0,
+            samples_per_cpu_cycle: config.sample_rate as f64
+                / get_cpu_clock_frequency(config.region) as f64,
+
+            next_cheat_id: 0,
+
+            o_frame_callback: None,
+            o_scanline_callback: None,
+            o_nmi_callback: None,
+
+            o_nsf: None,
+
+            #[cfg(feature = "std")]
+            rewind_max_frames: 0,
+            #[cfg(feature = "std")]
+            rewind_states: VecDeque::new(),
+
+            o_input_recording: None,
+            input_replay: VecDeque::new(),
+
+            #[cfg(feature = "std")]
+            o_autosave: None,
 
             config,
         }
@@ -109,55 +231,295 @@ impl NES {
 
     /// Restart the NES. This is different from reseting it.
     pub fn restart(&mut self) {
+        let mapper_factories = mem::take(&mut self.mapper_factories);
         *self = NES::from_config(self.config.clone());
+        self.mapper_factories = mapper_factories;
     }
 
     /// Load the ROM located at `rom_path` into the NES.
     /// The ROM file must be in a correct iNES or iNES v2 format.
-    pub fn insert_cartdrige(&mut self, rom_path: &str) -> Result<(), Box<dyn Error>> {
-        let mapper = get_mapper(rom_path)?;
-        let p_mapper = Rc::new(RefCell::new(mapper));
+    #[cfg(feature = "std")]
+    pub fn insert_cartdrige(&mut self, rom_path: &str) -> Result<(), NesError> {
+        let mapper = get_mapper(rom_path, &self.mapper_factories)?;
+        self.bus.ppu.set_mapper(mapper);
+        self.o_nsf = None;
+        self.rewind_states.clear();
+        self.reset();
 
-        self.p_bus.borrow_mut().set_mapper(p_mapper.clone());
-        self.p_ppu.borrow_mut().set_mapper(p_mapper.clone());
-        self.o_p_mapper = Some(p_mapper.clone());
+        Ok(())
+    }
+
+    /// Load a ROM from the content of a file in the iNES or iNES v2 format.
+    /// This does not touch the filesystem.
+    pub fn insert_cartdrige_from_bytes(&mut self, rom: &[u8]) -> Result<(), NesError> {
+        let mapper = get_mapper_from_bytes(rom, "", &self.mapper_factories)?;
+        self.bus.ppu.set_mapper(mapper);
+        self.o_nsf = None;
+        #[cfg(feature = "std")]
+        self.rewind_states.clear();
         self.reset();
 
         Ok(())
     }
 
+    /// Use `factory` to create the mapper of the cartridges with the mapper `number`,
+    /// instead of the mapper implemented by the crate if there is one.
+    /// This applies to the cartridges inserted afterwards.
+    pub fn register_mapper(&mut self, number: u16, factory: MapperFactory) {
+        self.mapper_factories.insert(number, factory);
+    }
+
+    /// Load the content of a NSF (NES Sound Format) music file, replacing the cartridge,
+    /// and start playing its first song.
+    /// The play routine of the tune is called at each frame, expansion sound chips are not emulated.
+    pub fn load_nsf(&mut self, nsf: &[u8]) -> Result<NsfInfo, NesError> {
+        let nsf = Nsf::new(nsf)?;
+        let info = nsf.info.clone();
+        self.o_nsf = Some(nsf);
+        self.play_nsf_track(info.starting_song)?;
+        Ok(info)
+    }
+
+    /// Start playing the song `track` (starting at 0) of the NSF file loaded with `load_nsf`.
+    /// Will return an error if no NSF file is loaded or if the song does not exist.
+    pub fn play_nsf_track(&mut self, track: u8) -> Result<(), NesError> {
+        let nsf = self.o_nsf.as_ref().ok_or(NesError::NoCartridge)?;
+        if track >= nsf.info.n_songs {
+            return Err(NesError::InvalidArgument(format!(
+                "NSF has {} songs",
+                nsf.info.n_songs
+            )));
+        }
+        let mapper = NsfMapper::new(nsf, track, self.config.region == Region::Pal);
+        self.bus.ppu.set_mapper(Box::new(mapper));
+        #[cfg(feature = "std")]
+        self.rewind_states.clear();
+        // The driver of the NSF player runs from a fresh power on, with a cleared RAM
+        self.power_on();
+        self.bus.clear_ram();
+        Ok(())
+    }
+
+    /// Power the NES on, as opposed to a reset.
+    /// The CPU RAM is filled with the startup pattern of the configuration, and the palette RAM
+    /// and the name tables are cleared to 0, before throwing a reset interrupt at the NES emulated CPU.
+    pub fn power_on(&mut self) {
+        self.bus.fill_startup_ram();
+        self.bus.ppu.clear_memory();
+        self.reset();
+    }
+
+    /// Get information about the inserted cartridge, or None if no cartridge was inserted.
+    pub fn get_cartridge_info(&self) -> Option<CartridgeInfo> {
+        self.bus
+            .ppu
+            .get_mapper()
+            .map(|m| CartridgeInfo::from(m.get_header()))
+    }
+
+    /// Get the hash of the ROM data of the inserted cartridge, to look it up in the ROM databases.
+    /// Return None if no cartridge was inserted or if the cartridge is a NSF file.
+    pub fn rom_hash(&self) -> Option<RomHash> {
+        self.bus.ppu.get_mapper()?.get_header().o_rom_hash
+    }
+
+    /// Return true if the inserted cartridge has a battery backed RAM that can be saved
+    /// with `save` and loaded with `load_save`.
+    pub fn has_battery_backed_ram(&self) -> bool {
+        match self.bus.ppu.get_mapper() {
+            Some(m) => m.has_persistent_memory(),
+            None => false,
+        }
+    }
+
+    /// Get the banks currently mapped by the cartridge, or None if no cartridge was inserted.
+    pub fn get_bank_state(&self) -> Option<MapperBankInfo> {
+        self.bus.ppu.get_mapper().map(|m| m.debug_bank_state())
+    }
+
     /// Reset the NES components.
     /// This will throw a reset interrupt at the NES emulated CPU.
+    /// Unlike `power_on`, the content of the RAM and of the PPU memory is kept.
     pub fn reset(&mut self) {
-        self.p_cpu.borrow_mut().reset();
-        self.p_apu.borrow_mut().reset();
+        self.cpu.reset(&mut self.bus);
+        self.bus.apu.reset();
+        self.bus.ppu.reset();
+        self.produced_samples = 0;
+        self.expected_samples = 0.0;
     }
 
     /// Read the bus memory at the given address
     /// You should know what you are doing when calling this method as it can easily
     /// be an invalid read
-    pub fn read_memory_at(&mut self, address: u16) -> Result<u8, Box<dyn Error>> {
-        self.p_bus.borrow_mut().read(address)
+    pub fn read_memory_at(&mut self, address: u16) -> Result<u8, NesError> {
+        self.check_cartridge_access(address)?;
+        self.bus
+            .read(address)
+            .map_err(|_| NesError::InvalidAddress(address))
+    }
+
+    /// Read the PPU bus memory at the given address, between 0x0000 and 0x3FFF.
+    /// This gives access to the pattern tables, the name tables and the palette RAM,
+    /// without the side effects of a read through the PPU registers.
+    pub fn read_ppu_memory_at(&self, address: u16) -> Result<u8, NesError> {
+        self.check_cartridge()?;
+        self.bus
+            .ppu
+            .read_memory(address)
+            .map_err(|_| NesError::InvalidAddress(address))
+    }
+
+    /// Write the value at the given address of the bus
+    /// This behaves like a store from the CPU: writing to the PPU or APU registers
+    /// or to the mapper registers triggers their side effects
+    pub fn write_memory_at(&mut self, address: u16, value: u8) -> Result<(), NesError> {
+        self.check_cartridge_access(address)?;
+        self.bus
+            .write(address, value)
+            .map_err(|_| NesError::InvalidAddress(address))
+    }
+
+    /// Add a 6 or 8 characters Game Genie code.
+    /// The returned handle can be used to remove the cheat.
+    pub fn add_cheat(&mut self, code: &str) -> Result<CheatHandle, NesError> {
+        let cheat = Cheat::from_game_genie_code(code)
+            .map_err(|e| NesError::InvalidArgument(e.to_string()))?;
+        let handle = CheatHandle(self.next_cheat_id);
+        self.next_cheat_id += 1;
+        self.bus.add_cheat(handle, cheat);
+        Ok(handle)
+    }
+
+    /// Remove a cheat previously added with `add_cheat`.
+    pub fn remove_cheat(&mut self, handle: CheatHandle) -> Result<(), NesError> {
+        match self.bus.remove_cheat(handle) {
+            Some(_) => Ok(()),
+            None => Err(NesError::InvalidArgument(String::from(
+                "No cheat found for this handle",
+            ))),
+        }
     }
 
     /// Set the program counter of the CPU at a specific address
     /// You should know what you are doing when calling this method as it can easily
     /// result in a crash of the emulator
     pub fn set_program_counter_at(&mut self, address: u16) {
-        self.p_cpu.borrow_mut().set_program_counter_at(address);
+        self.cpu.set_program_counter_at(address);
+    }
+
+    /// Trigger a NMI, taken by the CPU before its next instruction.
+    /// This can be used to test the NMI handler of a program without waiting for the VBlank.
+    pub fn trigger_nmi(&mut self) {
+        self.nmi_polled = true;
+    }
+
+    /// Trigger an IRQ, taken by the CPU before its next instruction.
+    /// Like an IRQ sent by the APU or the mapper, it is ignored if the interrupt disable flag is set.
+    pub fn trigger_irq(&mut self) {
+        self.irq_triggered = true;
+    }
+
+    /// Get the current values of the CPU registers.
+    pub fn get_cpu_registers(&self) -> CpuRegisters {
+        self.cpu.get_registers()
+    }
+
+    /// Get the number of PPU cycles elapsed since the NES was created or restarted.
+    /// This is the master clock of the emulation, saved in the save states.
+    pub fn total_clock(&self) -> u64 {
+        self.total_clock
+    }
+
+    /// Get the number of frames completed by the PPU since the NES was created or restarted.
+    /// This is saved in the save states.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Call `callback` with the address and the written value each time the CPU bus
+    /// writes at `address`. This replaces any callback previously set for this address.
+    /// Writes to mirrored addresses only trigger the callback of the address actually written.
+    pub fn set_write_watch(
+        &mut self,
+        address: u16,
+        callback: Box<dyn FnMut(u16, u8) + Send + Sync>,
+    ) {
+        self.bus.set_write_watch(address, callback);
+    }
+
+    /// Remove the callback set for `address` with `set_write_watch`.
+    pub fn remove_write_watch(&mut self, address: u16) {
+        self.bus.remove_write_watch(address);
+    }
+
+    /// Start or stop counting the CPU bus accesses, see `get_access_histogram`.
+    /// Enabling the profiling resets the counts. When disabled, only a flag is checked on each access.
+    pub fn enable_access_profiling(&mut self, enabled: bool) {
+        self.bus.enable_access_profiling(enabled);
+    }
+
+    /// Get the number of (reads, writes) on each 256 bytes page of the CPU bus,
+    /// counted while the profiling is enabled with `enable_access_profiling`.
+    pub fn get_access_histogram(&self) -> [(u64, u64); 256] {
+        self.bus.get_access_histogram()
+    }
+
+    /// Get the log of the last instruction executed by the CPU.
+    /// This will return None if the CPU logs are disabled in the configuration.
+    pub fn get_last_cpu_log(&self) -> Option<CpuLog> {
+        self.cpu.get_last_log()
+    }
+
+    /// Disassemble `count` instructions starting at the `start` address.
+    /// The memory is read without side effects, so this can be called at any time.
+    /// Will return an error if no cartridge was inserted.
+    pub fn disassemble_range(
+        &self,
+        start: u16,
+        count: usize,
+    ) -> Result<Vec<DisassembledInstruction>, NesError> {
+        self.check_cartridge()?;
+        Ok(self.cpu.disassemble_range(&self.bus, start, count))
     }
 
     /// Set the palette to use for displaying the pattern tables
-    pub fn set_debug_palette_id(&mut self, debug_palette_id: u8) -> Result<(), Box<dyn Error>> {
+    pub fn set_debug_palette_id(&mut self, debug_palette_id: u8) -> Result<(), NesError> {
         if debug_palette_id > 7 {
-            return Err("Palette id must be between 0 and 7".into());
+            return Err(NesError::InvalidArgument(String::from(
+                "Palette id must be between 0 and 7",
+            )));
         }
-        self.p_ppu
-            .borrow_mut()
-            .set_debug_palette_id(debug_palette_id);
+        self.bus.ppu.set_debug_palette_id(debug_palette_id);
         Ok(())
     }
 
+    /// Set the maximum number of sprites drawn on a scanline, None removes the limit.
+    /// The NES draws at most 8 sprites per scanline, which makes games flicker when more sprites
+    /// are on the same line. Raising the limit removes the flicker, the sprite overflow flag is
+    /// still set as with 8 sprites so the games are not affected. Limits under 8 are raised to 8.
+    pub fn set_sprite_limit(&mut self, limit: Option<u8>) {
+        self.bus.ppu.set_sprite_limit(limit);
+    }
+
+    /// Make the PPU use `mirroring` for the name tables, whatever the cartridge sets.
+    /// This helps with ROMs having a wrong header, until `clear_mirroring_override` is called.
+    pub fn override_mirroring(&mut self, mirroring: Mirroring) {
+        self.bus.ppu.set_mirroring_override(Some(mirroring));
+    }
+
+    /// Use the mirroring set by the cartridge again, after a call to `override_mirroring`.
+    pub fn clear_mirroring_override(&mut self) {
+        self.bus.ppu.set_mirroring_override(None);
+    }
+
+    /// Enable or disable the rendering of the frames, it is enabled by default.
+    /// When disabled, the PPU still runs and updates its flags, so the games behave the same,
+    /// but the frame buffer is not updated anymore. This speeds up the emulation when the frames
+    /// are not displayed, for example when fast forwarding or running tests.
+    pub fn set_render_enabled(&mut self, render_enabled: bool) {
+        self.bus.ppu.set_render_enabled(render_enabled);
+    }
+
     /// Return if the NES is currently adding samples produced by the APU to the samples buffer.
     pub fn is_producing_samples(&self) -> bool {
         self.add_samples
@@ -169,6 +531,92 @@ impl NES {
         self.add_samples = produce;
     }
 
+    /// Mute or unmute one of the APU sound channels.
+    /// A muted channel keeps running, so unmuting it resumes the sound where it should be.
+    pub fn set_channel_enabled(&mut self, channel: ApuChannel, enabled: bool) {
+        self.bus.apu.set_channel_enabled(channel, enabled);
+    }
+
+    /// Produce stereo samples, returned by `get_stereo_samples` instead of `get_samples`.
+    /// The channels are centered until `set_channel_pan` is called.
+    pub fn set_stereo_output(&mut self, stereo_output: bool) {
+        self.bus.apu.set_stereo_output(stereo_output);
+    }
+
+    /// Set the position of an APU sound channel in the stereo output, from -1.0 (left)
+    /// to 1.0 (right). A centered channel is played at full volume on both sides.
+    pub fn set_channel_pan(&mut self, channel: ApuChannel, pan: f32) {
+        let mut channel_pans = self.bus.apu.get_channel_pans();
+        channel_pans[channel as usize] = pan.clamp(-1.0, 1.0);
+        self.bus.apu.set_channel_pans(channel_pans);
+    }
+
+    /// Get the current raw output of each APU sound channel, before mixing and filtering.
+    /// Muted channels output 0.
+    pub fn get_apu_channel_outputs(&self) -> ApuChannelOutputs {
+        self.bus.apu.get_channel_outputs()
+    }
+
+    /// Get the APU status register, as read at 0x4015, without acknowledging the frame interrupt.
+    /// Bits 0 to 4 tell if the channels are playing, bit 6 is the frame interrupt flag and
+    /// bit 7 the DMC interrupt flag.
+    pub fn get_apu_status(&self) -> u8 {
+        self.bus.apu.get_status()
+    }
+
+    /// Tell the NES at which speed the emulation is played, relative to real time.
+    /// For values greater than 1.0, less samples are produced so that the samples buffer holds
+    /// about the right amount of audio for real time playback while fast forwarding.
+    /// The emulation itself is not affected. Values lower than 1.0 are treated as 1.0.
+    pub fn set_speed_multiplier(&mut self, multiplier: f32) {
+        self.bus.apu.set_speed_multiplier(multiplier);
+    }
+
+    /// Reset the audio filters as if no sound had been played before.
+    /// Call this when the emulation is paused to avoid a click when it resumes.
+    /// This is done automatically when the NES is reset.
+    pub fn flush_audio_filters(&mut self) {
+        self.bus.apu.flush_filters();
+    }
+
+    /// Keep the states of the NES at the end of the last `max_frames` frames,
+    /// so that the emulation can go back in time with `rewind_one_frame`.
+    /// 0 disables the rewind and frees the kept states.
+    #[cfg(feature = "std")]
+    pub fn enable_rewind(&mut self, max_frames: usize) {
+        self.rewind_max_frames = max_frames;
+        while self.rewind_states.len() > max_frames {
+            self.rewind_states.pop_front();
+        }
+    }
+
+    /// Go back to the end of the last frame, or to the end of the frame before
+    /// if no clock happened since then. Each call goes back one more frame.
+    /// Will return an error if there is no frame left to go back to.
+    #[cfg(feature = "std")]
+    pub fn rewind_one_frame(&mut self) -> Result<(), NesError> {
+        let (serialized, state) = loop {
+            let serialized = self
+                .rewind_states
+                .pop_back()
+                .ok_or_else(|| NesError::InvalidState(String::from("No frame to rewind")))?;
+            let state: NesState = bincode::deserialize(&serialized)
+                .map_err(|e| NesError::InvalidState(e.to_string()))?;
+            if state.total_clock != self.total_clock {
+                break (serialized, state);
+            }
+        };
+        let mut mapper = self.bus.ppu.take_mapper().ok_or(NesError::NoCartridge)?;
+        self.set_state(&state);
+        mapper.set_mapper_state(&*state.mapper);
+        self.bus.ppu.set_mapper(mapper);
+        // The frame buffer is not part of the state, the next frame is the first one to display
+        self.bus.ppu.consume_frame_buffer();
+        // Keep the restored state to be able to go back to it again
+        self.rewind_states.push_back(serialized);
+        Ok(())
+    }
+
     /// Gets the samples buffer and cleans it.
     pub fn get_samples(&mut self) -> Vec<f32> {
         let samples = self.samples.clone();
@@ -176,149 +624,732 @@ impl NES {
         samples
     }
 
+    /// Gets the stereo samples buffer, as (left, right) pairs, and cleans it.
+    /// Stereo samples are only produced after enabling them with `set_stereo_output`.
+    pub fn get_stereo_samples(&mut self) -> Vec<(f32, f32)> {
+        mem::take(&mut self.stereo_samples)
+    }
+
+    /// Get the number of samples produced by the APU minus the number of samples that should have
+    /// been produced at the configured sample rate, since the last reset or state load.
+    /// The APU produces a sample every whole number of CPU cycles, so the real sample rate is a bit
+    /// different from the configured one. A positive drift means that more samples than expected
+    /// were produced, the resampling ratio of the audio output can be adjusted accordingly.
+    pub fn audio_clock_drift(&self) -> f64 {
+        self.produced_samples as f64 - self.expected_samples
+    }
+
+    /// Get the samples produced during the last completed frame.
+    /// This is independent from `get_samples`, and an empty buffer is returned
+    /// if no frame has been completed since the last call.
+    pub fn get_samples_for_frame(&mut self) -> Vec<f32> {
+        mem::take(&mut self.last_frame_samples)
+    }
+
     /// Get the Duration of a frame.
     pub fn get_one_frame_duration(&self) -> Duration {
-        Duration::from_micros(1_000_000 / 60)
+        match self.config.region {
+            Region::Ntsc => Duration::from_micros(1_000_000 / 60),
+            Region::Pal => Duration::from_micros(1_000_000 / 50),
+        }
     }
 
     /// Clock the NES for one PPU cycle.
-    /// The CPU and the APU are clocked every 3 PPU cycles.
+    /// The CPU and the APU are clocked every 3 PPU cycles (3.2 PPU cycles for PAL).
     /// This call may have to be delayed to achieve an emulation running at the desired speed.
-    pub fn clock(&mut self) {
-        // CPU and APU are clocked every 3 PPU cycles
-        if self.total_clock % 3 == 0 {
-            // If we initialized a DMA, do not clock CPU for nearly 513 cycles
-            if self.p_ppu.borrow().registers.perform_dma {
-                self.perform_dma();
+    /// An error is returned if the CPU encountered an invalid opcode or memory access,
+    /// the NES is still clocked in this case.
+    pub fn clock(&mut self) -> Result<(), EmulationError> {
+        let mut result = Ok(());
+
+        // Feed the replayed inputs recorded at this clock
+        while let Some(&(clock, port, input)) = self.input_replay.front() {
+            if clock > self.total_clock {
+                break;
+            }
+            self.bus.set_input(port.index(), input);
+            self.input_replay.pop_front();
+        }
+
+        let mut nmi_written = false;
+        if self.is_cpu_cycle() {
+            let nmi_line = self.bus.ppu.registers.emit_nmi;
+
+            if self.is_cpu_at_instruction_boundary() {
+                self.bus.clear_controller_read();
+            }
+
+            // The DMC DMA pauses the OAM DMA for a get and a put cycle, unless it is ending
+            if self.dmc_dma_requested && self.bus.ppu.registers.perform_dma && !self.is_dma_ending()
+            {
+                self.dmc_dma_requested = false;
+                self.dmc_dma_cycles = 2;
+            }
+
+            // The DMC DMA halts the CPU, or pauses the OAM DMA, while fetching a sample byte
+            // If we initialized an OAM DMA, do not clock CPU for nearly 513 cycles
+            // The CPU keeps running its write cycles until the DMC DMA can halt it
+            // A polled NMI is taken instead of fetching the next instruction
+            result = if self.dmc_dma_cycles > 0 {
+                self.perform_dmc_dma()
+            } else if self.bus.ppu.registers.perform_dma {
+                // The CPU is already halted for the DMC DMA waiting for the end of the OAM DMA
+                if self.dmc_dma_requested {
+                    self.dmc_dma_halt_cycles += 1;
+                }
+                self.perform_dma()
+            } else if self.dmc_dma_requested
+                && (self.dmc_dma_halt_cycles > 0 || !self.cpu.is_on_write_cycle())
+            {
+                self.halt_for_dmc_dma()
+            } else if self.nmi_polled && self.cpu.is_instruction_done() {
+                self.nmi_polled = false;
+                self.cpu.interrupt(&mut self.bus, Interrupt::Nmi);
+                if let Some(callback) = &mut self.o_nmi_callback {
+                    callback();
+                }
+                self.cpu.clock(&mut self.bus)
+            } else if self.irq_triggered && self.cpu.is_instruction_done() {
+                self.irq_triggered = false;
+                self.cpu.interrupt(&mut self.bus, Interrupt::Irq);
+                self.cpu.clock(&mut self.bus)
             } else {
-                self.p_cpu.borrow_mut().clock();
+                self.cpu.clock(&mut self.bus)
+            };
+
+            // Writing to 0x2000 during VBlank enables the NMI on the last cycle of the instruction
+            if !nmi_line && self.bus.ppu.registers.emit_nmi {
+                self.nmi_delayed = true;
+                nmi_written = true;
+            }
+            // The NMI is polled before the last cycle of an instruction
+            if self.cpu.is_instruction_done() {
+                self.nmi_delayed = false;
+            } else if self.o_nmi_cycles_before_poll == Some(0) && !self.nmi_delayed {
+                self.o_nmi_cycles_before_poll = None;
+                self.nmi_polled = true;
+            }
+            if let Some(cycles) = self.o_nmi_cycles_before_poll.as_mut() {
+                *cycles = cycles.saturating_sub(1);
             }
 
-            if let Some(s) = self.p_apu.borrow_mut().clock() {
+            // Samples are produced at a lower rate when running faster than real time
+            self.expected_samples +=
+                self.samples_per_cpu_cycle / self.bus.apu.get_speed_multiplier() as f64;
+            if let Some(s) = self.bus.apu.clock() {
+                self.produced_samples += 1;
                 if self.add_samples {
-                    self.samples.push(s);
+                    if self.bus.apu.is_stereo_output() {
+                        self.stereo_samples.push(self.bus.apu.get_stereo_sample());
+                    } else {
+                        self.samples.push(s);
+                    }
+                    self.current_frame_samples.push(s);
                 }
             }
+
+            // The DMC memory reader needs a new sample byte, this stalls the CPU for 1 to 4
+            // cycles or the OAM DMA for 2 cycles
+            if !self.dmc_dma_requested
+                && self.dmc_dma_cycles == 0
+                && self.bus.apu.get_dmc_sample_address().is_some()
+            {
+                self.dmc_dma_requested = true;
+            }
+
+            // Check if the APU or the mapper request an IRQ interrupt
+            let mapper_irq = match self.bus.ppu.get_mapper() {
+                Some(m) => m.is_irq_pending(),
+                None => false,
+            };
+            if self.bus.apu.is_irq_requested() || mapper_irq {
+                self.cpu.interrupt(&mut self.bus, Interrupt::Irq);
+            }
         }
 
         // Check if an NMI interrupt should be thrown
-        if self.p_ppu.borrow().registers.emit_nmi {
-            self.p_ppu.borrow_mut().registers.emit_nmi = false;
-            self.p_cpu.borrow_mut().interrupt(Interrupt::Nmi);
+        // An NMI enabled by the CPU is detected one clock later, if the VBlank flag is still set
+        if self.bus.ppu.registers.emit_nmi && !nmi_written {
+            self.bus.ppu.registers.emit_nmi = false;
+            if self.bus.ppu.is_in_vblank() {
+                self.o_nmi_cycles_before_poll = Some(NMI_POLL_DELAY);
+            }
         }
 
         // Clock PPU
-        self.p_ppu.borrow_mut().clock();
+        let frame_completed = self.bus.ppu.clock();
+        if frame_completed {
+            self.frame_count = self.frame_count.wrapping_add(1);
+            self.last_frame_samples = mem::take(&mut self.current_frame_samples);
+            if let Some(callback) = &mut self.o_frame_callback {
+                callback(self.bus.ppu.peek_frame_buffer());
+            }
+        }
+        if let Some(callback) = &mut self.o_scanline_callback {
+            let scanline = self.bus.ppu.get_scanline();
+            if self.bus.ppu.get_cycles() == 0 && (1..=240).contains(&scanline) {
+                callback(scanline - 1);
+            }
+        }
 
         self.total_clock = self.total_clock.wrapping_add(1);
+
+        #[cfg(feature = "std")]
+        if frame_completed {
+            if self.rewind_max_frames > 0 {
+                self.capture_rewind_state();
+            }
+            self.autosave();
+        }
+
+        result
+    }
+
+    /// Clock the NES for `count` PPU cycles.
+    /// This is equivalent to calling `clock` in a loop.
+    /// Stops at the first error returned by `clock`.
+    pub fn clock_n(&mut self, count: u64) -> Result<(), EmulationError> {
+        for _ in 0..count {
+            self.clock()?;
+        }
+        Ok(())
+    }
+
+    /// Return true if the next CPU cycle fetches a new opcode, false if it continues the current
+    /// instruction or if the CPU is halted by a DMA.
+    /// The CPU is only clocked every 3 PPU cycles, so several calls to `clock` may be needed to reach it.
+    pub fn is_cpu_at_instruction_boundary(&self) -> bool {
+        self.dmc_dma_cycles == 0
+            && !self.dmc_dma_requested
+            && !self.bus.ppu.registers.perform_dma
+            && self.cpu.is_instruction_done()
+    }
+
+    /// Clock the NES until the current CPU instruction is completed and the next one is about
+    /// to be fetched. If no instruction is in progress, the next one is executed.
+    /// A DMA started by the instruction is completed before returning.
+    /// Return the number of PPU cycles consumed, or the first error returned by `clock`.
+    pub fn step_cpu_instruction(&mut self) -> Result<u64, EmulationError> {
+        let start_clock = self.total_clock;
+        loop {
+            let is_fetching = self.is_cpu_fetching_next();
+            self.clock()?;
+            if is_fetching {
+                break;
+            }
+        }
+        while !self.is_cpu_fetching_next() {
+            self.clock()?;
+        }
+        Ok(self.total_clock.wrapping_sub(start_clock))
+    }
+
+    /// Clock the NES until the CPU is about to execute the instruction at the `target` address.
+    /// Returns a `Timeout` error if it is not reached within `max_cycles` PPU cycles.
+    /// Stops at the first error returned by `clock`.
+    pub fn run_until_pc(&mut self, target: u16, max_cycles: u64) -> Result<(), EmulationError> {
+        let mut cycles = 0;
+        while !(self.is_cpu_fetching_next() && self.cpu.get_registers().pc == target) {
+            if cycles == max_cycles {
+                return Err(EmulationError::Timeout(max_cycles));
+            }
+            self.clock()?;
+            cycles += 1;
+        }
+        Ok(())
     }
 
     /// If a frame has been completely calculated, get the frame buffer and cleans it.
     /// Else this will return None.
-    pub fn get_frame_buffer(&mut self) -> Option<[ARGBColor; 61_440]> {
-        if self.p_ppu.borrow().is_frame_ready() {
-            Some(self.p_ppu.borrow_mut().get_frame_buffer())
+    pub fn get_frame_buffer(&mut self) -> Option<[ARGBColor; FRAME_BUFFER_LEN]> {
+        if self.bus.ppu.is_frame_ready() {
+            Some(self.bus.ppu.get_frame_buffer())
         } else {
             None
         }
     }
 
-    /// Handle an input from the controller id.
-    /// Will return an error if the id is not 0 or 1.
-    pub fn input(&mut self, id: usize, input: u8) -> Result<(), Box<dyn Error>> {
-        if id > 1 {
-            return Err("Controller id must be either 0 or 1".into());
+    /// Same as `get_frame_buffer`, but without the pixels hidden by the configured overscan.
+    /// The returned frame is `Overscan::width` pixels wide and `Overscan::height` pixels high.
+    pub fn get_cropped_frame_buffer(&mut self) -> Option<Vec<ARGBColor>> {
+        let frame = self.get_frame_buffer()?;
+        let overscan = self.config.overscan;
+        let (left, width) = (overscan.left as usize, overscan.width());
+        Some(
+            frame
+                .chunks(NES_WIDTH)
+                .skip(overscan.top as usize)
+                .take(overscan.height())
+                .flat_map(|line| line[left..left + width].iter().copied())
+                .collect(),
+        )
+    }
+
+    /// Set a callback called with the frame buffer each time the PPU completes a frame.
+    /// This does not consume the frame, `get_frame_buffer` and `run_one_frame` still return it.
+    pub fn set_frame_callback(&mut self, callback: FrameCallback) {
+        self.o_frame_callback = Some(callback);
+    }
+
+    /// Set a callback called with the output level of the DMC (0 to 127) each time the DMC
+    /// produces a new level, either from its sample bits or from a direct write to 0x4011.
+    /// This allows extracting the sampled audio played by games through the DMC.
+    pub fn set_dmc_sample_callback(&mut self, callback: DmcSampleCallback) {
+        self.bus.apu.set_dmc_sample_callback(Some(callback));
+    }
+
+    /// Set a callback called with the scanline number at the end of each visible scanline (0 to 239).
+    /// This can be used to observe raster effects done by writing to the PPU registers mid-frame.
+    pub fn set_scanline_callback(&mut self, callback: ScanlineCallback) {
+        self.o_scanline_callback = Some(callback);
+    }
+
+    /// Set a callback called each time the CPU takes an NMI interrupt.
+    /// Games usually run their logic once per frame from the NMI, which happens at the start of
+    /// the VBlank instead of the end of the frame. This allows synchronizing with the game loop.
+    pub fn set_nmi_callback(&mut self, callback: NmiCallback) {
+        self.o_nmi_callback = Some(callback);
+    }
+
+    /// If a frame has been completely calculated, borrow the frame buffer without copying it.
+    /// The frame is marked as consumed like with `get_frame_buffer`.
+    /// Else this will return None.
+    pub fn frame_buffer(&mut self) -> Option<&[ARGBColor; FRAME_BUFFER_LEN]> {
+        if self.bus.ppu.is_frame_ready() {
+            Some(self.bus.ppu.consume_frame_buffer())
+        } else {
+            None
         }
-        self.p_bus.borrow_mut().set_input(id, input);
-        Ok(())
+    }
+
+    /// Clock the NES until a frame has been completely calculated and return it.
+    /// The next call to this method will return the following frame.
+    /// Stops at the first error returned by `clock`.
+    pub fn run_one_frame(&mut self) -> Result<[ARGBColor; FRAME_BUFFER_LEN], EmulationError> {
+        while !self.bus.ppu.is_frame_ready() {
+            self.clock()?;
+        }
+        Ok(self.bus.ppu.get_frame_buffer())
+    }
+
+    /// Handle an input from the controller plugged in `port`.
+    /// Use `ControllerPort::try_from` to get the port from an index.
+    pub fn input(&mut self, port: ControllerPort, input: u8) {
+        self.bus.set_input(port.index(), input);
+        if let Some(recording) = &mut self.o_input_recording {
+            recording.push((self.total_clock, port, input));
+        }
+    }
+
+    /// Press or release a single button of the controller plugged in `port`.
+    /// The other buttons of the controller keep their state.
+    pub fn set_button(&mut self, port: ControllerPort, button: Button, pressed: bool) {
+        let input = if pressed {
+            self.bus.get_input(port.index()) | button.mask()
+        } else {
+            self.bus.get_input(port.index()) & !button.mask()
+        };
+        self.input(port, input);
+    }
+
+    /// Get the buttons currently pressed on the controller plugged in `port`,
+    /// in the same format as the input given to `input`.
+    pub fn get_controller_state(&self, port: ControllerPort) -> u8 {
+        self.bus.get_input(port.index())
+    }
+
+    /// Start recording the inputs given with `input`, along with the clock at which they were given.
+    /// This discards any previous recording.
+    pub fn begin_input_recording(&mut self) {
+        self.o_input_recording = Some(vec![]);
+    }
+
+    /// Get the inputs recorded since `begin_input_recording` was called,
+    /// as a list of (clock, controller port, input).
+    pub fn export_input_log(&self) -> Vec<(u64, ControllerPort, u8)> {
+        match &self.o_input_recording {
+            Some(recording) => recording.clone(),
+            None => vec![],
+        }
+    }
+
+    /// Replay inputs exported with `export_input_log`: each input is given to the controller
+    /// when `clock` reaches its recorded clock. This replaces the inputs that were still to replay.
+    /// Starting from the same state as the recording, this reproduces the recorded emulation.
+    pub fn replay_input_log(&mut self, log: Vec<(u64, ControllerPort, u8)>) {
+        let mut log = log;
+        log.sort_by_key(|&(clock, _, _)| clock);
+        self.input_replay = VecDeque::from(log);
+    }
+
+    /// Plug a zapper (light gun) in the second controller port and update its state.
+    /// `x` and `y` are the coordinates of the pixel targeted on the screen.
+    /// The light is sensed at the moment the game reads the zapper: the targeted pixel is only
+    /// detected if it is bright and if the PPU drew it during the last few scanlines.
+    pub fn set_zapper_state(&mut self, x: u8, y: u8, trigger: bool) {
+        self.bus.set_zapper(Some(Zapper::new(x, y, trigger)));
+    }
+
+    /// Unplug the zapper plugged with `set_zapper_state`,
+    /// the second controller port is read as a standard controller again.
+    pub fn unplug_zapper(&mut self) {
+        self.bus.set_zapper(None);
     }
 
     /// Load a save in the ".sav" format.
-    pub fn load_save(&self, save_path: &str) -> Result<(), Box<dyn Error>> {
-        if let Some(m) = &self.o_p_mapper {
-            m.borrow_mut().load_persistent_memory(save_path)
+    #[cfg(feature = "std")]
+    pub fn load_save(&mut self, save_path: &str) -> Result<(), NesError> {
+        if let Some(m) = self.bus.ppu.get_mapper_mut() {
+            m.load_persistent_memory(save_path)
         } else {
-            Err("Insert a cartridge before trying to save".into())
+            Err(NesError::NoCartridge)
         }
     }
 
     /// Save the game in the ".sav" format.
-    pub fn save(&self, save_path: &str) -> Result<(), Box<dyn Error>> {
-        if let Some(m) = &self.o_p_mapper {
-            m.borrow().save_persistent_memory(save_path)
+    #[cfg(feature = "std")]
+    pub fn save(&self, save_path: &str) -> Result<(), NesError> {
+        if let Some(m) = self.bus.ppu.get_mapper() {
+            m.save_persistent_memory(save_path)
         } else {
-            Err("Insert a cartridge before trying to save".into())
+            Err(NesError::NoCartridge)
+        }
+    }
+
+    /// Save the persistent memory of the cartridge to `save_path` every `every_frames` frames,
+    /// in the same format as `save`. Nothing is saved if the cartridge has no battery backed RAM.
+    /// 0 frames disables the automatic save.
+    #[cfg(feature = "std")]
+    pub fn enable_autosave(&mut self, save_path: String, every_frames: u64) {
+        self.o_autosave = if every_frames > 0 {
+            Some((save_path, every_frames))
+        } else {
+            None
+        };
+    }
+
+    #[cfg(feature = "std")]
+    fn autosave(&self) {
+        if let Some((save_path, every_frames)) = &self.o_autosave {
+            if self.frame_count.is_multiple_of(*every_frames) && self.has_battery_backed_ram() {
+                if let Err(e) = self.save(save_path) {
+                    warn!("Automatic save to {} failed: {}", save_path, e);
+                }
+            }
         }
     }
 
     /// Load a NES state from a previously saved state.
-    pub fn load_state(&mut self, state_path: &str, rom_path: &str) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "std")]
+    pub fn load_state(&mut self, state_path: &str, rom_path: &str) -> Result<(), NesError> {
         debug!("Loading NES state from {}...", state_path);
         let state_file = File::open(state_path)?;
-        let state = &serde_json::from_reader(state_file)?;
-        self.set_state(state);
-        let mut mapper = get_mapper(rom_path)?;
-        mapper.set_mapper_state(&*state.mapper);
-        let p_mapper = Rc::new(RefCell::new(mapper));
-
-        self.p_bus.borrow_mut().set_mapper(p_mapper.clone());
-        self.p_ppu.borrow_mut().set_mapper(p_mapper.clone());
-        self.o_p_mapper = Some(p_mapper.clone());
+        let state = serde_json::from_reader(state_file)
+            .map_err(|e| NesError::InvalidState(e.to_string()))?;
+        self.apply_state(&state, rom_path)?;
         debug!("State successfully loaded.");
         Ok(())
     }
 
     /// Save the current state of the NES.
-    pub fn save_state(&self, state_path: &str) -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "std")]
+    pub fn save_state(&self, state_path: &str) -> Result<(), NesError> {
         debug!("Saving NES state...");
         let state = self.get_state();
         let state_file = File::create(state_path)?;
-        serde_json::to_writer(state_file, &state)?;
+        serde_json::to_writer(state_file, &state)
+            .map_err(|e| NesError::InvalidState(e.to_string()))?;
         debug!("Current NES state saved in {}.", state_path);
         Ok(())
     }
 
+    /// Load a NES state from a state previously saved with `save_state_binary`.
+    #[cfg(feature = "std")]
+    pub fn load_state_binary(&mut self, state_path: &str, rom_path: &str) -> Result<(), NesError> {
+        debug!("Loading NES binary state from {}...", state_path);
+        self.deserialize_state(&fs::read(state_path)?, rom_path)?;
+        debug!("State successfully loaded.");
+        Ok(())
+    }
+
+    /// Save the current state of the NES in a compact binary format.
+    /// This is much faster than `save_state` and produces smaller files.
+    #[cfg(feature = "std")]
+    pub fn save_state_binary(&self, state_path: &str) -> Result<(), NesError> {
+        debug!("Saving NES binary state...");
+        fs::write(state_path, self.serialize_state()?)?;
+        debug!("Current NES state saved in {}.", state_path);
+        Ok(())
+    }
+
+    /// Load a NES state from a buffer produced by `serialize_state`.
+    #[cfg(feature = "std")]
+    pub fn deserialize_state(&mut self, state: &[u8], rom_path: &str) -> Result<(), NesError> {
+        let state =
+            bincode::deserialize(state).map_err(|e| NesError::InvalidState(e.to_string()))?;
+        self.apply_state(&state, rom_path)
+    }
+
+    /// Serialize the current state of the NES in a compact binary format.
+    /// This does not touch the filesystem, which is useful to keep states in memory.
+    #[cfg(feature = "std")]
+    pub fn serialize_state(&self) -> Result<Vec<u8>, NesError> {
+        if self.bus.ppu.get_mapper().is_none() {
+            return Err(NesError::NoCartridge);
+        }
+        bincode::serialize(&self.get_state()).map_err(|e| NesError::InvalidState(e.to_string()))
+    }
+
+    /// Compare the current state of the NES with a state produced by `serialize_state`,
+    /// to find where two emulations diverged. An empty list is returned if the states are equal.
+    #[cfg(feature = "std")]
+    pub fn state_diff(&self, other: &[u8]) -> Result<Vec<StateDiff>, NesError> {
+        if self.bus.ppu.get_mapper().is_none() {
+            return Err(NesError::NoCartridge);
+        }
+        let other: NesState =
+            bincode::deserialize(other).map_err(|e| NesError::InvalidState(e.to_string()))?;
+        self.get_state()
+            .diff(&other)
+            .map_err(|e| NesError::InvalidState(e.to_string()))
+    }
+
     /// Get the current pattern table.
     /// The number parameter allows to choose a pattern table.
     /// Will return an error if number is not 0 or 1.
-    pub fn get_pattern_table(&self, number: u16) -> Result<[ARGBColor; 16384], Box<dyn Error>> {
+    pub fn get_pattern_table(&self, number: u16) -> Result<[ARGBColor; 16384], NesError> {
+        if number > 1 {
+            return Err(NesError::InvalidArgument(String::from(
+                "Pattern table number must be either 0 or 1",
+            )));
+        }
+        self.check_cartridge()?;
+        self.bus
+            .ppu
+            .get_pattern_table(number)
+            .map_err(|e| NesError::InvalidArgument(e.to_string()))
+    }
+
+    /// Get the pattern table `number` drawn with the palette `palette_id`, without changing the
+    /// palette set by `set_debug_palette_id`.
+    /// Will return an error if number is not 0 or 1, or if palette_id is not between 0 and 7.
+    pub fn get_pattern_table_with_palette(
+        &self,
+        number: u16,
+        palette_id: u8,
+    ) -> Result<[ARGBColor; 16384], NesError> {
         if number > 1 {
-            return Err("Pattern table number must be either 0 or 1".into());
+            return Err(NesError::InvalidArgument(String::from(
+                "Pattern table number must be either 0 or 1",
+            )));
         }
-        self.p_ppu.borrow().get_pattern_table(number)
+        if palette_id > 7 {
+            return Err(NesError::InvalidArgument(String::from(
+                "Palette id must be between 0 and 7",
+            )));
+        }
+        self.check_cartridge()?;
+        self.bus
+            .ppu
+            .get_pattern_table_with_palette(number, palette_id)
+            .map_err(|e| NesError::InvalidArgument(e.to_string()))
+    }
+
+    /// Get the nametable `index` as a 256x240 image, using the current background pattern table
+    /// and the attribute data of the nametable.
+    /// The index goes through the current mirroring: with horizontal mirroring, 0 and 1 are the same nametable.
+    /// Will return an error if index is not between 0 and 3.
+    pub fn get_nametable(&self, index: u8) -> Result<Vec<ARGBColor>, NesError> {
+        self.check_cartridge()?;
+        self.bus
+            .ppu
+            .get_nametable(index)
+            .map_err(|e| NesError::InvalidArgument(e.to_string()))
+    }
+
+    /// Get the tile indices of the nametable `index`, as 30 rows of 32 tiles.
+    /// This reads the memory without decoding the patterns and the palettes, like `get_nametable` does.
+    /// Will return an error if index is not between 0 and 3.
+    pub fn dump_nametable_tiles(&self, index: u8) -> Result<[[u8; 32]; 30], NesError> {
+        self.check_cartridge()?;
+        self.bus
+            .ppu
+            .get_nametable_tiles(index)
+            .map_err(|e| NesError::InvalidArgument(e.to_string()))
     }
 
     /// Get the colors of the palette that are currently stored in memory.
-    pub fn get_palette(&self) -> Result<[ARGBColor; 32], Box<dyn Error>> {
-        self.p_ppu.borrow().get_palette()
+    pub fn get_palette(&self) -> Result<[ARGBColor; 32], NesError> {
+        self.check_cartridge()?;
+        self.bus
+            .ppu
+            .get_palette()
+            .map_err(|e| NesError::InvalidArgument(e.to_string()))
+    }
+
+    /// Get the 32 entries of the palette RAM, as indices in the system palette (0x00 to 0x3F).
+    /// Unlike `get_palette`, this does not depend on the palette file used to display the colors.
+    /// The entries 0x10, 0x14, 0x18 and 0x1C are mirrors of the entries 0x00, 0x04, 0x08 and 0x0C.
+    pub fn get_palette_indices(&self) -> [u8; 32] {
+        self.bus.ppu.get_palette_indices()
+    }
+
+    /// Get the 64 sprites of the primary OAM (Object Attribute Memory).
+    pub fn get_oam(&self) -> [SpriteEntry; 64] {
+        self.bus.ppu.get_oam()
+    }
+
+    /// Get the 8 sprites of the secondary OAM, i.e. the sprites selected by the last
+    /// sprite evaluation to be drawn on the next scanline.
+    pub fn get_secondary_oam(&self) -> [SpriteEntry; 8] {
+        self.bus.ppu.get_secondary_oam()
+    }
+
+    /// Get the scrolling registers of the PPU, decoded from its VRAM addresses.
+    pub fn get_ppu_scroll_state(&self) -> PpuScrollState {
+        self.bus.ppu.get_scroll_state()
+    }
+
+    /// Predict the scanline on which the sprite 0 hit flag will be set during the next frame.
+    /// The prediction uses the current sprite 0 position, scrolling and background, so it only
+    /// matches the emulation if they are not changed while the frame is rendered.
+    /// Returns None if no hit will happen, if rendering is disabled or if no cartridge was inserted.
+    pub fn sprite_0_hit_scanline(&self) -> Option<u16> {
+        self.bus.ppu.get_mapper()?;
+        self.bus.ppu.predict_sprite_0_hit_scanline()
+    }
+
+    // Set the state of the NES and of a new mapper created from the ROM file
+    #[cfg(feature = "std")]
+    fn apply_state(&mut self, state: &NesState, rom_path: &str) -> Result<(), NesError> {
+        self.set_state(state);
+        let mut mapper = get_mapper(rom_path, &self.mapper_factories)?;
+        mapper.set_mapper_state(&*state.mapper);
+        self.bus.ppu.set_mapper(mapper);
+        Ok(())
+    }
+
+    // Called at the end of each frame when the rewind is enabled
+    #[cfg(feature = "std")]
+    fn capture_rewind_state(&mut self) {
+        if self.bus.ppu.get_mapper().is_none() {
+            return;
+        }
+        match bincode::serialize(&self.get_state()) {
+            Ok(serialized) => {
+                if self.rewind_states.len() == self.rewind_max_frames {
+                    self.rewind_states.pop_front();
+                }
+                self.rewind_states.push_back(serialized);
+            }
+            Err(e) => debug!("Failed to capture the rewind state: {}", e),
+        }
+    }
+
+    // The cartridge is needed to access the memory it maps
+    fn check_cartridge_access(&self, address: u16) -> Result<(), NesError> {
+        if address >= 0x4020 {
+            self.check_cartridge()?;
+        }
+        Ok(())
+    }
+
+    fn check_cartridge(&self) -> Result<(), NesError> {
+        match self.bus.ppu.get_mapper() {
+            Some(_) => Ok(()),
+            None => Err(NesError::NoCartridge),
+        }
+    }
+
+    // CPU and APU are clocked every 3 PPU cycles (5 times every 16 PPU cycles for PAL)
+    fn is_cpu_cycle(&self) -> bool {
+        match self.config.region {
+            Region::Ntsc => self.total_clock % 3 == 0,
+            Region::Pal => (self.total_clock % 16) * 5 % 16 < 5,
+        }
+    }
+
+    // True if the next clock makes the CPU fetch a new opcode
+    fn is_cpu_fetching_next(&self) -> bool {
+        self.is_cpu_cycle() && self.is_cpu_at_instruction_boundary()
+    }
+
+    // True on the even CPU cycles, when the DMAs can read from the bus
+    fn is_get_cycle(&self) -> bool {
+        self.total_clock % 2 == 0
+    }
+
+    // True if the OAM DMA ends in the next 2 CPU cycles
+    fn is_dma_ending(&self) -> bool {
+        self.dma_started && self.dma_address_offset == 255
+    }
+
+    // The sample byte is read on the last cycle of the DMC DMA pausing the OAM DMA
+    fn perform_dmc_dma(&mut self) -> Result<(), EmulationError> {
+        self.dmc_dma_cycles -= 1;
+        if self.dmc_dma_cycles == 0 {
+            self.fetch_dmc_sample()
+        } else {
+            Ok(())
+        }
+    }
+
+    // The CPU is halted on a read cycle, then the DMC DMA takes a dummy cycle, and an alignment
+    // cycle if needed as the sample byte is read on a get cycle. This stalls the CPU for 3 or 4
+    // cycles, after its eventual write cycles. When the OAM DMA already halted the CPU, the
+    // halt and dummy cycles may be done while it ends, down to a single cycle stall.
+    fn halt_for_dmc_dma(&mut self) -> Result<(), EmulationError> {
+        // If the CPU is halted on the controller read done on the last cycle of the instruction,
+        // the read is repeated and the controller is clocked twice. The instruction is already
+        // executed here, so the deleted bit is the one returned by the next read instead of this one.
+        if self.dmc_dma_halt_cycles == 0 && self.cpu.is_on_last_cycle() {
+            self.bus.repeat_controller_read();
+        }
+        if self.dmc_dma_halt_cycles >= 2 && self.is_get_cycle() {
+            self.dmc_dma_requested = false;
+            self.dmc_dma_halt_cycles = 0;
+            self.fetch_dmc_sample()
+        } else {
+            self.dmc_dma_halt_cycles += 1;
+            Ok(())
+        }
+    }
+
+    fn fetch_dmc_sample(&mut self) -> Result<(), EmulationError> {
+        if let Some(address) = self.bus.apu.get_dmc_sample_address() {
+            match self.bus.read(address) {
+                Ok(data) => self.bus.apu.load_dmc_sample(data),
+                Err(_) => return Err(EmulationError::InvalidRead(address)),
+            }
+        }
+        Ok(())
     }
 
     // Performs a DMA (transfer of 256 bytes of sprite data to PPU)
-    fn perform_dma(&mut self) {
+    fn perform_dma(&mut self) -> Result<(), EmulationError> {
         if !self.dma_started {
             // Wait for an even cycle to start
-            if self.total_clock % 2 == 1 {
-                self.dma_hi_address = self.p_ppu.borrow().registers.oam_dma;
-                self.dma_base_address = self.p_ppu.borrow().registers.oam_addr;
+            if !self.is_get_cycle() {
+                self.dma_hi_address = self.bus.ppu.registers.oam_dma;
+                self.dma_base_address = self.bus.ppu.registers.oam_addr;
                 self.dma_address_offset = 0;
                 self.dma_started = true;
             }
         } else {
             // On even cycles, read data from the bus
-            if self.total_clock % 2 == 0 {
+            if self.is_get_cycle() {
                 let address: u16 =
                     self.dma_address_offset as u16 + ((self.dma_hi_address as u16) << 8);
-                match self.p_bus.borrow_mut().read(address) {
+                match self.bus.read(address) {
                     Ok(data) => self.dma_data = data,
-                    Err(e) => panic!("{}", e),
+                    Err(_) => return Err(EmulationError::InvalidRead(address)),
                 }
             }
             // On odd cycles, write data to the PPU OAM
             else {
-                self.p_ppu
-                    .borrow_mut()
-                    .write_register(0x2004, self.dma_data)
-                    .unwrap();
+                if self.bus.ppu.write_register(0x2004, self.dma_data).is_err() {
+                    return Err(EmulationError::InvalidWrite(0x2004));
+                }
 
                 if self.dma_address_offset < 255 {
                     self.dma_address_offset += 1;
@@ -329,10 +1360,19 @@ impl NES {
                 // End DMA
                 if self.dma_address_offset == 0 {
                     self.dma_started = false;
-                    self.p_ppu.borrow_mut().registers.perform_dma = false;
+                    self.bus.ppu.registers.perform_dma = false;
                 }
             }
         }
+        Ok(())
+    }
+}
+
+// Frequency at which the CPU is clocked (Hz)
+fn get_cpu_clock_frequency(region: Region) -> u64 {
+    match region {
+        Region::Ntsc => PPU_CLOCK_FREQUENCY / 3,
+        Region::Pal => PAL_PPU_CLOCK_FREQUENCY * 5 / 16,
     }
 }
 
@@ -341,54 +1381,82 @@ impl Stateful for NES {
 
     fn get_state(&self) -> Self::State {
         NesState {
-            bus: self.p_bus.borrow().get_state(),
-            cpu: self.p_cpu.borrow().get_state(),
-            ppu: self.p_ppu.borrow().get_state(),
-            apu: self.p_apu.borrow().get_state(),
-            mapper: self
-                .o_p_mapper
-                .as_ref()
-                .unwrap()
-                .borrow()
-                .get_mapper_state(),
+            bus: self.bus.get_state(),
+            cpu: self.cpu.get_state(),
+            ppu: self.bus.ppu.get_state(),
+            apu: self.bus.apu.get_state(),
+            mapper: self.bus.ppu.get_mapper().unwrap().get_mapper_state(),
             total_clock: self.total_clock,
+            frame_count: self.frame_count,
             dma_started: self.dma_started,
             dma_hi_address: self.dma_hi_address,
             dma_base_address: self.dma_base_address,
             dma_address_offset: self.dma_address_offset,
             dma_data: self.dma_data,
+            dmc_dma_cycles: self.dmc_dma_cycles,
+            dmc_dma_requested: self.dmc_dma_requested,
+            dmc_dma_halt_cycles: self.dmc_dma_halt_cycles,
+            o_nmi_cycles_before_poll: self.o_nmi_cycles_before_poll,
+            nmi_delayed: self.nmi_delayed,
+            nmi_polled: self.nmi_polled,
+            irq_triggered: self.irq_triggered,
             add_samples: self.add_samples,
         }
     }
 
     fn set_state(&mut self, state: &Self::State) {
-        self.p_ppu = Rc::new(RefCell::new(Ppu::from_state(
+        // Muted channels, stereo, speed, sprite limit, rendering, mirroring override, callbacks
+        // and cheats are not emulation state, keep them across state loads
+        let enabled_channels = self.bus.apu.get_enabled_channels();
+        let stereo_output = self.bus.apu.is_stereo_output();
+        let channel_pans = self.bus.apu.get_channel_pans();
+        let speed_multiplier = self.bus.apu.get_speed_multiplier();
+        let o_dmc_sample_callback = self.bus.apu.take_dmc_sample_callback();
+        let sprite_limit = self.bus.ppu.get_sprite_limit();
+        let render_enabled = self.bus.ppu.is_render_enabled();
+        let o_mirroring_override = self.bus.ppu.get_mirroring_override();
+        self.bus.ppu = Ppu::from_state(
             &state.ppu,
             &self.config.palette_path,
-        )));
-        self.p_apu = Rc::new(RefCell::new(Apu::from_state(
+            &self.config.palette_data,
+            self.config.region,
+        );
+        self.bus.ppu.set_sprite_limit(Some(sprite_limit));
+        self.bus.ppu.set_render_enabled(render_enabled);
+        self.bus.ppu.set_mirroring_override(o_mirroring_override);
+        self.bus.apu = Apu::from_state(
             &state.apu,
-            PPU_CLOCK_FREQUENCY,
-        )));
-        self.p_bus = Rc::new(RefCell::new(Bus::from_state(
-            &state.bus,
-            self.p_ppu.clone(),
-            self.p_apu.clone(),
-        )));
-        self.p_cpu = Rc::new(RefCell::new(Cpu::from_state(
+            get_cpu_clock_frequency(self.config.region),
+            self.config.sample_rate,
+            self.config.region,
+        );
+        self.bus.apu.set_enabled_channels(enabled_channels);
+        self.bus.apu.set_stereo_output(stereo_output);
+        self.bus.apu.set_channel_pans(channel_pans);
+        self.bus.apu.set_speed_multiplier(speed_multiplier);
+        self.bus.apu.set_dmc_sample_callback(o_dmc_sample_callback);
+        self.bus.set_state(&state.bus);
+        self.cpu = Cpu::from_state(
             &state.cpu,
-            self.p_bus.clone(),
             self.config.display_cpu_logs,
-        )));
-        self.p_apu
-            .borrow_mut()
-            .attach_bus_and_cpu(self.p_bus.clone(), self.p_cpu.clone());
+            self.config.unstable_opcode_magic,
+        );
         self.total_clock = state.total_clock;
+        self.frame_count = state.frame_count;
+        self.produced_samples = 0;
+        self.expected_samples = 0.0;
         self.dma_started = state.dma_started;
         self.dma_hi_address = state.dma_hi_address;
         self.dma_base_address = state.dma_base_address;
         self.dma_address_offset = state.dma_address_offset;
         self.dma_data = state.dma_data;
+        self.dmc_dma_cycles = state.dmc_dma_cycles;
+        self.dmc_dma_requested = state.dmc_dma_requested;
+        self.dmc_dma_halt_cycles = state.dmc_dma_halt_cycles;
+        self.o_nmi_cycles_before_poll = state.o_nmi_cycles_before_poll;
+        self.nmi_delayed = state.nmi_delayed;
+        self.nmi_polled = state.nmi_polled;
+        self.irq_triggered = state.irq_triggered;
         self.add_samples = state.add_samples;
     }
 }
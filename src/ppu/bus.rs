@@ -2,7 +2,7 @@
 
 // ===== IMPORTS =====
 
-use std::{cell::RefCell, error::Error, rc::Rc};
+use core::error::Error;
 
 use serde::{Deserialize, Serialize};
 
@@ -13,8 +13,7 @@ use crate::{
 };
 
 use super::{enums::VRAMAddressMask, state::PpuBusState};
-
-type MapperRc = Rc<RefCell<Box<dyn Mapper>>>;
+use crate::prelude::*;
 
 // ===== STRUCT =====
 
@@ -70,7 +69,8 @@ impl VRAMAddress {
 
 pub struct PPUBus {
     // Name tables loaded in VRAM
-    name_tables: [[u8; 0x0400]; 2],
+    // The last two are only used by cartridges providing four screen mirroring
+    name_tables: [[u8; 0x0400]; 4],
 
     // Palette table
     palette_table: [u8; 0x20],
@@ -80,20 +80,23 @@ pub struct PPUBus {
     pub tmp_vram_address: VRAMAddress,
 
     // Mapper
-    pub o_p_mapper: Option<MapperRc>,
+    pub o_mapper: Option<Box<dyn Mapper>>,
+    // Mirroring used instead of the one of the mapper
+    pub o_mirroring_override: Option<Mirroring>,
 }
 
 impl PPUBus {
     pub fn new() -> Self {
         PPUBus {
-            name_tables: [[0; 0x0400]; 2],
+            name_tables: [[0; 0x0400]; 4],
 
             palette_table: [0; 0x20],
 
             vram_address: VRAMAddress::new(),
             tmp_vram_address: VRAMAddress::new(),
 
-            o_p_mapper: None,
+            o_mapper: None,
+            o_mirroring_override: None,
         }
     }
 
@@ -103,18 +106,28 @@ impl PPUBus {
         bus
     }
 
-    pub fn set_mapper(&mut self, p_mapper: MapperRc) {
-        self.o_p_mapper = Some(p_mapper);
+    pub fn set_mapper(&mut self, mapper: Box<dyn Mapper>) {
+        self.o_mapper = Some(mapper);
+    }
+
+    // Clear the name tables and the palette table, as after a power cycle
+    pub fn clear_memory(&mut self) {
+        self.name_tables = [[0; 0x0400]; 4];
+        self.palette_table = [0; 0x20];
+    }
+
+    // Reads done by the PPU itself, the mapper is notified of the address
+    pub fn read(&mut self, address: u16) -> Result<u8, Box<dyn Error>> {
+        if let 0x0000..=0x1FFF = address {
+            self.o_mapper.as_mut().unwrap().notify_ppu_address(address);
+        }
+        self.read_only(address)
     }
 
-    pub fn read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+    // Used for debugging, the mapper does not see these reads
+    pub fn read_only(&self, address: u16) -> Result<u8, Box<dyn Error>> {
         match address {
-            0x0000..=0x1FFF => self
-                .o_p_mapper
-                .as_ref()
-                .unwrap()
-                .borrow()
-                .chr_rom_read(address),
+            0x0000..=0x1FFF => self.o_mapper.as_ref().unwrap().chr_rom_read(address),
             0x2000..=0x2FFF => self.read_name_tables(address),
             0x3000..=0x3EFF => self.read_name_tables(address & 0x2FFF),
             0x3F00..=0x3FFF => self.read_palette_table(address & 0x001F),
@@ -122,8 +135,15 @@ impl PPUBus {
         }
     }
 
+    fn get_mirroring(&self) -> Mirroring {
+        match self.o_mirroring_override {
+            Some(mirroring) => mirroring,
+            None => self.o_mapper.as_ref().unwrap().get_mirroring(),
+        }
+    }
+
     fn read_name_tables(&self, address: u16) -> Result<u8, Box<dyn Error>> {
-        match self.o_p_mapper.as_ref().unwrap().borrow().get_mirroring() {
+        match self.get_mirroring() {
             Mirroring::Horizontal => match address {
                 0x2000..=0x23FF => Ok(self.name_tables[0][(address & 0x03FF) as usize]),
                 0x2400..=0x27FF => Ok(self.name_tables[0][(address & 0x03FF) as usize]),
@@ -140,7 +160,13 @@ impl PPUBus {
             },
             Mirroring::OneScreenLower => Ok(self.name_tables[0][(address & 0x03FF) as usize]),
             Mirroring::OneScreenUpper => Ok(self.name_tables[1][(address & 0x03FF) as usize]),
-            Mirroring::FourScreens => panic!("Four screen mirroring is not handled for now"),
+            Mirroring::FourScreens => match address {
+                0x2000..=0x23FF => Ok(self.name_tables[0][(address & 0x03FF) as usize]),
+                0x2400..=0x27FF => Ok(self.name_tables[1][(address & 0x03FF) as usize]),
+                0x2800..=0x2BFF => Ok(self.name_tables[2][(address & 0x03FF) as usize]),
+                0x2C00..=0x2FFF => Ok(self.name_tables[3][(address & 0x03FF) as usize]),
+                _ => Err(Box::new(InvalidPPUBusReadError(address))),
+            },
         }
     }
 
@@ -159,10 +185,9 @@ impl PPUBus {
     pub fn write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
         match address {
             0x0000..=0x1FFF => self
-                .o_p_mapper
+                .o_mapper
                 .as_mut()
                 .unwrap()
-                .borrow_mut()
                 .chr_rom_write(address, value),
             0x2000..=0x2FFF => self.write_name_tables(address, value),
             0x3000..=0x3EFF => self.write_name_tables(address & 0x2FFF, value),
@@ -172,7 +197,7 @@ impl PPUBus {
     }
 
     fn write_name_tables(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
-        match self.o_p_mapper.as_ref().unwrap().borrow().get_mirroring() {
+        match self.get_mirroring() {
             Mirroring::Horizontal => match address {
                 0x2000..=0x23FF => self.name_tables[0][(address & 0x03FF) as usize] = value,
                 0x2400..=0x27FF => self.name_tables[0][(address & 0x03FF) as usize] = value,
@@ -187,15 +212,15 @@ impl PPUBus {
                 0x2C00..=0x2FFF => self.name_tables[1][(address & 0x03FF) as usize] = value,
                 _ => return Err(Box::new(InvalidPPUBusWriteError(address))),
             },
-            Mirroring::OneScreenLower => {
-                self.name_tables[0][(address & 0x03FF) as usize] = value;
-                self.name_tables[1][(address & 0x03FF) as usize] = value;
-            }
-            Mirroring::OneScreenUpper => {
-                self.name_tables[0][(address & 0x03FF) as usize] = value;
-                self.name_tables[1][(address & 0x03FF) as usize] = value;
-            }
-            Mirroring::FourScreens => panic!("Four screen mirroring is not handled for now"),
+            Mirroring::OneScreenLower => self.name_tables[0][(address & 0x03FF) as usize] = value,
+            Mirroring::OneScreenUpper => self.name_tables[1][(address & 0x03FF) as usize] = value,
+            Mirroring::FourScreens => match address {
+                0x2000..=0x23FF => self.name_tables[0][(address & 0x03FF) as usize] = value,
+                0x2400..=0x27FF => self.name_tables[1][(address & 0x03FF) as usize] = value,
+                0x2800..=0x2BFF => self.name_tables[2][(address & 0x03FF) as usize] = value,
+                0x2C00..=0x2FFF => self.name_tables[3][(address & 0x03FF) as usize] = value,
+                _ => return Err(Box::new(InvalidPPUBusWriteError(address))),
+            },
         }
         Ok(())
     }
@@ -12,7 +12,7 @@ mod sprite;
 
 // ===== IMPORTS =====
 
-use std::{cell::RefCell, error::Error, rc::Rc};
+use core::error::Error;
 
 use log::warn;
 
@@ -22,16 +22,31 @@ use oam::Oam;
 use palette::Palette;
 use registers::Registers;
 
-use crate::{cartridge::mapper::Mapper, state::Stateful, utils::ARGBColor};
+use crate::{
+    cartridge::mapper::{Mapper, Mirroring},
+    nes::FRAME_BUFFER_LEN,
+    state::Stateful,
+    utils::ARGBColor,
+    Region,
+};
 
-use self::state::PpuState;
+use self::state::{PpuScrollState, PpuState, SpriteEntry};
+use crate::prelude::*;
 
 // ===== CONSTANTS =====
 
 const MAX_CYCLES: u16 = 340;
-const MAX_SCANLINES: u16 = 261;
+const NTSC_MAX_SCANLINES: u16 = 261;
+const PAL_MAX_SCANLINES: u16 = 311;
 
-type MapperRc = Rc<RefCell<Box<dyn Mapper>>>;
+// Number of scanlines during which the zapper keeps sensing a bright pixel after it is drawn
+const LIGHT_SENSE_SCANLINES: u16 = 20;
+// Minimum brightness (sum of the color components) of a pixel sensed by the zapper
+const LIGHT_SENSE_BRIGHTNESS: u16 = 0x180;
+
+// Number of sprites drawn on a scanline by the hardware, and with the sprite limit disabled
+const HARDWARE_SPRITE_LIMIT: usize = 8;
+const MAX_SPRITE_LIMIT: usize = 64;
 
 // ===== STRUCT =====
 
@@ -53,11 +68,15 @@ pub struct Ppu {
     current_sprite_count: u8,
     next_contains_sprite_0: bool,
     current_contains_sprite_0: bool,
+    // Byte of the sprite read as its y coordinate when looking for a sprite overflow
+    sprite_overflow_byte: u8,
 
-    // Variables for displaying sprites
-    sprite_shifters: [[u8; 2]; 8],
-    sprite_x: [u8; 8],
-    sprite_attributes: [u8; 8],
+    // Variables for displaying sprites, with one entry for each sprite allowed on a scanline
+    sprite_shifters: Vec<[u8; 2]>,
+    sprite_x: Vec<u8>,
+    sprite_attributes: Vec<u8>,
+    // Maximum number of sprites drawn on a scanline
+    sprite_limit: usize,
 
     // Data for the next 8 pixels
     next_name_table_byte: u8,
@@ -73,33 +92,28 @@ pub struct Ppu {
     cycles: u16,
     scanline: u16,
     odd_frame: bool,
+    region: Region,
+    max_scanlines: u16,
 
     total_clock: u64,
 
-    // Current frame infos
-    frame_buffer: [ARGBColor; 61_440],
+    // Current frame infos, the frame buffer is boxed to keep the NES small on the stack
+    frame_buffer: Box<[ARGBColor; FRAME_BUFFER_LEN]>,
     is_frame_ready: bool,
+    // When disabled, the frame buffer is not updated but the timings and flags still are
+    render_enabled: bool,
 
     // Debug
     debug_palette_id: u8,
 }
 
 impl Ppu {
-    pub fn new(palette_path: &Option<String>) -> Self {
-        let palette_path = match palette_path {
-            Some(p) => p,
-            None => "./palette.pal",
-        };
-        let palettes = match Palette::from_file(palette_path) {
-            Ok(p) => p,
-            Err(_) => {
-                warn!(
-                    "Unable to load palette at {}, using default palette.",
-                    palette_path
-                );
-                Palette::default()
-            }
-        };
+    pub fn new(
+        palette_path: &Option<String>,
+        palette_data: &Option<Vec<u8>>,
+        region: Region,
+    ) -> Self {
+        let palettes = load_palette(palette_path, palette_data);
 
         Ppu {
             registers: Registers::new(),
@@ -115,11 +129,13 @@ impl Ppu {
             current_sprite_count: 0,
             next_contains_sprite_0: false,
             current_contains_sprite_0: false,
+            sprite_overflow_byte: 0,
             is_sprite_0_rendered: false,
 
-            sprite_shifters: [[0; 2]; 8],
-            sprite_x: [0; 8],
-            sprite_attributes: [0; 8],
+            sprite_shifters: vec![[0; 2]; HARDWARE_SPRITE_LIMIT],
+            sprite_x: vec![0; HARDWARE_SPRITE_LIMIT],
+            sprite_attributes: vec![0; HARDWARE_SPRITE_LIMIT],
+            sprite_limit: HARDWARE_SPRITE_LIMIT,
 
             next_name_table_byte: 0,
             next_attribute_table_byte: 0,
@@ -131,30 +147,100 @@ impl Ppu {
             cycles: 0,
             scanline: 0,
             odd_frame: false,
+            region,
+            max_scanlines: match region {
+                Region::Ntsc => NTSC_MAX_SCANLINES,
+                Region::Pal => PAL_MAX_SCANLINES,
+            },
 
             total_clock: 0,
 
-            frame_buffer: [ARGBColor::black(); 61_440],
+            frame_buffer: Box::new([ARGBColor::black(); FRAME_BUFFER_LEN]),
             is_frame_ready: false,
+            render_enabled: true,
 
             debug_palette_id: 0,
         }
     }
 
-    pub fn from_state(state: &PpuState, palette_path: &Option<String>) -> Self {
-        let mut ppu = Ppu::new(palette_path);
+    pub fn from_state(
+        state: &PpuState,
+        palette_path: &Option<String>,
+        palette_data: &Option<Vec<u8>>,
+        region: Region,
+    ) -> Self {
+        let mut ppu = Ppu::new(palette_path, palette_data, region);
         ppu.set_state(state);
         ppu
     }
 
-    pub fn set_mapper(&mut self, p_mapper: MapperRc) {
-        self.ppu_bus.set_mapper(p_mapper);
+    pub fn set_mapper(&mut self, mapper: Box<dyn Mapper>) {
+        self.ppu_bus.set_mapper(mapper);
+    }
+
+    #[cfg(feature = "std")]
+    pub fn take_mapper(&mut self) -> Option<Box<dyn Mapper>> {
+        self.ppu_bus.o_mapper.take()
+    }
+
+    pub fn clear_memory(&mut self) {
+        self.ppu_bus.clear_memory();
+    }
+
+    // Reset the registers as the reset button does, the VRAM, palette RAM and OAM are kept
+    pub fn reset(&mut self) {
+        self.registers.reset();
+        self.ppu_bus.tmp_vram_address.address = 0;
+        self.odd_frame = false;
+    }
+
+    // The cartridge is plugged on the PPU bus, the CPU bus reaches it from here
+    pub fn get_mapper(&self) -> Option<&dyn Mapper> {
+        self.ppu_bus.o_mapper.as_deref()
+    }
+
+    pub fn get_mapper_mut(&mut self) -> Option<&mut (dyn Mapper + 'static)> {
+        self.ppu_bus.o_mapper.as_deref_mut()
+    }
+
+    pub fn set_mirroring_override(&mut self, o_mirroring: Option<Mirroring>) {
+        self.ppu_bus.o_mirroring_override = o_mirroring;
+    }
+
+    pub fn get_mirroring_override(&self) -> Option<Mirroring> {
+        self.ppu_bus.o_mirroring_override
     }
 
     pub fn set_debug_palette_id(&mut self, debug_palette_id: u8) {
         self.debug_palette_id = debug_palette_id;
     }
 
+    // The limit is at least the hardware one, None disables it
+    pub fn set_sprite_limit(&mut self, o_limit: Option<u8>) {
+        self.sprite_limit = match o_limit {
+            Some(limit) => (limit as usize).clamp(HARDWARE_SPRITE_LIMIT, MAX_SPRITE_LIMIT),
+            None => MAX_SPRITE_LIMIT,
+        };
+        // The storage is never shrunk, as it may be in use by the current scanline
+        if self.sprite_shifters.len() < self.sprite_limit {
+            self.sprite_shifters.resize(self.sprite_limit, [0; 2]);
+            self.sprite_x.resize(self.sprite_limit, 0);
+            self.sprite_attributes.resize(self.sprite_limit, 0);
+        }
+    }
+
+    pub fn get_sprite_limit(&self) -> u8 {
+        self.sprite_limit as u8
+    }
+
+    pub fn set_render_enabled(&mut self, render_enabled: bool) {
+        self.render_enabled = render_enabled;
+    }
+
+    pub fn is_render_enabled(&self) -> bool {
+        self.render_enabled
+    }
+
     pub fn get_scanline(&self) -> u16 {
         self.scanline
     }
@@ -163,30 +249,60 @@ impl Ppu {
         self.cycles
     }
 
+    // Check if the pixel at (x, y) is bright and has been drawn recently enough to be sensed
+    // by a zapper. The pixel is only sensed during a few scanlines after it is drawn.
+    pub fn is_light_detected(&self, x: u8, y: u8) -> bool {
+        let y = y as u16;
+        if y >= 240 || self.scanline < y || self.scanline >= y + LIGHT_SENSE_SCANLINES {
+            return false;
+        }
+        if self.scanline == y && self.cycles <= x as u16 {
+            return false;
+        }
+        let color = self.frame_buffer[(256 * y + x as u16) as usize];
+        color.red as u16 + color.green as u16 + color.blue as u16 >= LIGHT_SENSE_BRIGHTNESS
+    }
+
+    pub fn is_in_vblank(&self) -> bool {
+        self.registers.get_status_flag(StatusFlag::VBlank)
+    }
+
     pub fn is_frame_ready(&self) -> bool {
         self.is_frame_ready
     }
 
-    pub fn get_frame_buffer(&mut self) -> [ARGBColor; 61_440] {
+    pub fn get_frame_buffer(&mut self) -> [ARGBColor; FRAME_BUFFER_LEN] {
+        self.is_frame_ready = false;
+        *self.frame_buffer
+    }
+
+    // Borrow the frame buffer and mark the frame as consumed
+    pub fn consume_frame_buffer(&mut self) -> &[ARGBColor; FRAME_BUFFER_LEN] {
         self.is_frame_ready = false;
-        self.frame_buffer
+        &self.frame_buffer
+    }
+
+    // Get the frame buffer without marking the frame as consumed
+    pub fn peek_frame_buffer(&self) -> &[ARGBColor; FRAME_BUFFER_LEN] {
+        &self.frame_buffer
     }
 
     // ===== CLOCK =====
 
-    // Executes a clock cycle
-    pub fn clock(&mut self) {
-        // This cycle is skipped
+    // Executes a clock cycle, returns true if a frame has just been completed
+    pub fn clock(&mut self) -> bool {
+        // This cycle is skipped (NTSC only)
         if self.scanline == 0
             && self.cycles == 0
             && self.odd_frame
+            && self.region == Region::Ntsc
             && self.registers.get_mask_flag(MaskFlag::ShowBackground)
         {
             self.cycles = 1;
         }
 
         // Get the next 8 pixels colors
-        if self.scanline < 240 || self.scanline == 261 {
+        if self.scanline < 240 || self.scanline == self.max_scanlines {
             // === BACKGROUND ===
 
             if self.cycles >= 2 && self.cycles <= 257 || (self.cycles > 320 && self.cycles < 338) {
@@ -318,8 +434,10 @@ impl Ppu {
             // Sprite evaluation
             if self.cycles > 64 && self.cycles < 257 {
                 if self.cycles == 65 {
+                    self.oam.extra.clear();
                     self.next_sprite_count = 0;
                     self.next_contains_sprite_0 = false;
+                    self.sprite_overflow_byte = 0;
                 }
                 self.evaluate_sprites();
             }
@@ -327,9 +445,9 @@ impl Ppu {
             // Sprite data fetch
             if self.cycles > 256 && self.cycles < 321 {
                 if self.cycles == 257 {
-                    self.sprite_shifters = [[0; 2]; 8];
-                    self.sprite_x = [0; 8];
-                    self.sprite_attributes = [0; 8];
+                    self.sprite_shifters.fill([0; 2]);
+                    self.sprite_x.fill(0);
+                    self.sprite_attributes.fill(0);
                 }
                 self.fetch_sprite_data();
             }
@@ -359,7 +477,7 @@ impl Ppu {
         }
 
         // Clear the v blank flag at the end of the v blank period
-        if self.scanline == 261 && self.cycles == 1 {
+        if self.scanline == self.max_scanlines && self.cycles == 1 {
             self.registers.set_status_flag(StatusFlag::VBlank, false);
             self.registers
                 .set_status_flag(StatusFlag::Sprite0Hit, false);
@@ -367,7 +485,7 @@ impl Ppu {
                 .set_status_flag(StatusFlag::SpriteOverflow, false);
         }
 
-        if self.scanline == 261 && (self.cycles > 279 && self.cycles < 305) {
+        if self.scanline == self.max_scanlines && (self.cycles > 279 && self.cycles < 305) {
             self.copy_tmp_y_to_vram_address();
         }
 
@@ -405,6 +523,24 @@ impl Ppu {
                 }
             }
 
+            // Hide the leftmost 8 pixels if clipping is enabled
+            if self.cycles < 9 {
+                if !self
+                    .registers
+                    .get_mask_flag(MaskFlag::ShowLeftScreenBackground)
+                {
+                    bg_palette = 0;
+                    bg_pattern = 0;
+                }
+                if !self
+                    .registers
+                    .get_mask_flag(MaskFlag::ShowLeftScreenSprites)
+                {
+                    fg_palette = 0;
+                    fg_pattern = 0;
+                }
+            }
+
             // Calculates the final pixel color
             let palette: u8;
             let pattern: u8;
@@ -433,39 +569,32 @@ impl Ppu {
                     && self.registers.get_mask_flag(MaskFlag::ShowSprites)
                     && self.cycles != 256
                 {
-                    if !self
-                        .registers
-                        .get_mask_flag(MaskFlag::ShowLeftScreenBackground)
-                        || !self
-                            .registers
-                            .get_mask_flag(MaskFlag::ShowLeftScreenSprites)
-                    {
-                        if self.cycles >= 9 {
-                            self.registers.set_status_flag(StatusFlag::Sprite0Hit, true);
-                        }
-                    } else {
-                        self.registers.set_status_flag(StatusFlag::Sprite0Hit, true);
-                    }
+                    // Pixels hidden by the left clipping are transparent and never reach this point
+                    self.registers.set_status_flag(StatusFlag::Sprite0Hit, true);
                 }
             }
 
             // Renders pixel
-            self.frame_buffer[(256 * self.scanline as u32 + self.cycles as u32 - 1) as usize] =
-                self.get_pixel_color(palette, pattern);
+            if self.render_enabled {
+                self.frame_buffer[(256 * self.scanline as u32 + self.cycles as u32 - 1) as usize] =
+                    self.get_pixel_color(palette, pattern);
+            }
         }
 
-        // Increasing cycles and scanlines to reach a 341*262 matrix
+        // Increasing cycles and scanlines to reach a 341*262 matrix (341*312 for PAL)
         // Only the 256*240 matrix in the top left corner is used for displaying the screen
+        let mut frame_completed = false;
         self.total_clock += 1;
         self.cycles += 1;
         if self.cycles > MAX_CYCLES {
             self.scanline += 1;
             self.cycles = 0;
-            if self.scanline > MAX_SCANLINES {
+            if self.scanline > self.max_scanlines {
                 self.scanline = 0;
                 self.odd_frame = !self.odd_frame;
 
                 self.is_frame_ready = true;
+                frame_completed = true;
             }
         }
 
@@ -476,22 +605,37 @@ impl Ppu {
             self.registers.decay = 0;
             self.registers.decay_timer = 0;
         }
+
+        frame_completed
     }
 
     // ===== READ =====
 
-    fn read_bus(&self, address: u16) -> u8 {
+    fn read_bus(&mut self, address: u16) -> u8 {
         match self.ppu_bus.read(address) {
             Ok(byte) => byte,
             Err(e) => panic!("{}", e),
         }
     }
 
+    // Reads that are not done by the PPU rendering itself, they are not seen by the mapper
+    fn read_only_bus(&self, address: u16) -> u8 {
+        match self.ppu_bus.read_only(address) {
+            Ok(byte) => byte,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    // Read the PPU bus for debugging, without side effects
+    pub fn read_memory(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+        self.ppu_bus.read_only(address)
+    }
+
     // ===== GET COLOR METHOD =====
 
     fn get_pixel_color(&self, palette: u8, color: u8) -> ARGBColor {
         let address: u16 = ((palette as u16) << 2) + (color as u16) + 0x3F00;
-        let mut palette_index = (self.read_bus(address) & 0x3F) as usize;
+        let mut palette_index = (self.read_only_bus(address) & 0x3F) as usize;
         if self.registers.get_mask_flag(MaskFlag::GreyScale) {
             palette_index &= 0x30;
         }
@@ -540,31 +684,50 @@ impl Ppu {
                 1 => 16,
                 _ => panic!("Invalid sprite size value"),
             };
-            // If the sprite should appear on the next scanline
-            if self.scanline % 261 >= (self.oam.primary[sprite_index].y as u16)
-                && self.scanline % 261 < (self.oam.primary[sprite_index].y as u16) + sprite_size
-            {
-                // If more than 8 sprites has been found
-                if self.next_sprite_count >= 8 {
+            let line = self.scanline % self.max_scanlines;
+            let is_on_next_line = |y: u8| line >= y as u16 && line < y as u16 + sprite_size;
+
+            let is_in_range = is_on_next_line(self.oam.primary[sprite_index].y)
+                && self.scanline != self.max_scanlines;
+
+            if (self.next_sprite_count as usize) < HARDWARE_SPRITE_LIMIT {
+                // If the sprite should appear on the next scanline
+                if is_in_range {
+                    self.oam.secondary[self.next_sprite_count as usize] =
+                        self.oam.primary[sprite_index];
+                    if sprite_index == 0 {
+                        self.next_contains_sprite_0 = true;
+                    }
+                    self.next_sprite_count += 1;
+                }
+            } else {
+                // Once 8 sprites have been found, the hardware increments the byte index
+                // along with the sprite index when a sprite is not in range
+                // This reads tile ids, attributes or x positions as y coordinates
+                let y = self
+                    .oam
+                    .read_primary(sprite_index as u8 * 4 + self.sprite_overflow_byte);
+                if is_on_next_line(y) {
                     if self.registers.get_mask_flag(MaskFlag::ShowSprites)
                         || self.registers.get_mask_flag(MaskFlag::ShowBackground)
                     {
                         self.registers
                             .set_status_flag(StatusFlag::SpriteOverflow, true);
                     }
-                } else if self.scanline != 261 {
-                    self.oam.secondary[self.next_sprite_count as usize] =
-                        self.oam.primary[sprite_index];
-                    if sprite_index == 0 {
-                        self.next_contains_sprite_0 = true;
-                    }
+                } else {
+                    self.sprite_overflow_byte = (self.sprite_overflow_byte + 1) & 0x03;
+                }
+
+                // The sprites above the hardware limit do not change the overflow detection
+                if is_in_range && (self.next_sprite_count as usize) < self.sprite_limit {
+                    self.oam.extra.push(self.oam.primary[sprite_index]);
                     self.next_sprite_count += 1;
                 }
             }
         }
 
         // The first empty entry in the secondary OAM has the 63 sprite y as its y coordinate
-        if self.cycles == 256 && self.next_sprite_count < 8 {
+        if self.cycles == 256 && (self.next_sprite_count as usize) < HARDWARE_SPRITE_LIMIT {
             self.oam
                 .write_secondary(self.next_sprite_count * 4, self.oam.primary[63].y);
         }
@@ -577,114 +740,7 @@ impl Ppu {
         if (sprite_index as u8) < self.next_sprite_count {
             match (self.cycles - 257) % 8 {
                 // Populate sprite shifters
-                0 => {
-                    let lo_address: u16; // Address of the low byte of the sprite
-                    let v_flip: bool = self.oam.secondary[sprite_index]
-                        .get_attribute_flag(SpriteAttribute::FlipVertically)
-                        == 1;
-                    // 8x8 sprites
-                    if self.registers.get_control_flag(ControlFlag::SpriteSize) == 0 {
-                        // Do not flip sprite vertically
-                        if !v_flip {
-                            lo_address = ((self
-                                .registers
-                                .get_control_flag(ControlFlag::SpritePatternTableAddress)
-                                as u16)
-                                << 12)
-                                | ((self.oam.secondary[sprite_index].id as u16) << 4)
-                                | (self.scanline as i16
-                                    - (self.oam.secondary[sprite_index].y as i16))
-                                    as u16;
-                        }
-                        // Flip sprite vertically
-                        else {
-                            lo_address = ((self
-                                .registers
-                                .get_control_flag(ControlFlag::SpritePatternTableAddress)
-                                as u16)
-                                << 12)
-                                | ((self.oam.secondary[sprite_index].id as u16) << 4)
-                                | (7 - (self.scanline as i16
-                                    - (self.oam.secondary[sprite_index].y as i16)))
-                                    as u16;
-                        }
-                    }
-                    // 8x16 sprites
-                    else {
-                        // Do not flip sprite vertically
-                        if !v_flip {
-                            // First half of the sprite
-                            if self.scanline - (self.oam.secondary[sprite_index].y as u16) < 8 {
-                                lo_address = (((self.oam.secondary[sprite_index].id & 0x01)
-                                    as u16)
-                                    << 12)
-                                    | (((self.oam.secondary[sprite_index].id & 0xFE) as u16) << 4)
-                                    | ((self.scanline as i16
-                                        - (self.oam.secondary[sprite_index].y as i16))
-                                        & 0x07) as u16;
-                            }
-                            // Second half of the sprite
-                            else {
-                                lo_address = (((self.oam.secondary[sprite_index].id & 0x01)
-                                    as u16)
-                                    << 12)
-                                    | ((((self.oam.secondary[sprite_index].id & 0xFE) as u16) + 1)
-                                        << 4)
-                                    | ((self.scanline as i16
-                                        - (self.oam.secondary[sprite_index].y as i16))
-                                        & 0x07) as u16;
-                            }
-                        }
-                        // Flip sprite vertically
-                        else {
-                            // Second half of the sprite
-                            if self.scanline - (self.oam.secondary[sprite_index].y as u16) < 8 {
-                                lo_address = (((self.oam.secondary[sprite_index].id & 0x01)
-                                    as u16)
-                                    << 12)
-                                    | ((((self.oam.secondary[sprite_index].id & 0xFE) as u16) + 1)
-                                        << 4)
-                                    | ((7
-                                        - (self.scanline as i16
-                                            - (self.oam.secondary[sprite_index].y as i16)))
-                                        & 0x07) as u16;
-                            }
-                            // First half of the sprite
-                            else {
-                                lo_address = (((self.oam.secondary[sprite_index].id & 0x01)
-                                    as u16)
-                                    << 12)
-                                    | (((self.oam.secondary[sprite_index].id & 0xFE) as u16) << 4)
-                                        as u16
-                                    | (7 - ((self.scanline as i16
-                                        - (self.oam.secondary[sprite_index].y as i16))
-                                        & 0x07)) as u16;
-                            }
-                        }
-                    }
-
-                    // Get low and high bytes of the sprite
-                    let mut lo_sprite: u8 = self.read_bus(lo_address);
-                    let mut hi_sprite: u8 = self.read_bus(lo_address + 8);
-
-                    // Flip horizontally
-                    if self.oam.secondary[sprite_index]
-                        .get_attribute_flag(SpriteAttribute::FlipHorizontally)
-                        == 1
-                    {
-                        lo_sprite = ((lo_sprite & 0xF0) >> 4) | ((lo_sprite & 0x0F) << 4);
-                        lo_sprite = ((lo_sprite & 0xCC) >> 2) | ((lo_sprite & 0x33) << 2);
-                        lo_sprite = ((lo_sprite & 0xAA) >> 1) | ((lo_sprite & 0x55) << 1);
-
-                        hi_sprite = ((hi_sprite & 0xF0) >> 4) | ((hi_sprite & 0x0F) << 4);
-                        hi_sprite = ((hi_sprite & 0xCC) >> 2) | ((hi_sprite & 0x33) << 2);
-                        hi_sprite = ((hi_sprite & 0xAA) >> 1) | ((hi_sprite & 0x55) << 1);
-                    }
-
-                    // Finally write the result into our shifters
-                    self.sprite_shifters[sprite_index][0] = lo_sprite;
-                    self.sprite_shifters[sprite_index][1] = hi_sprite;
-                }
+                0 => self.load_sprite_pattern(sprite_index, true),
                 // Populate X sprite shifters
                 1 => {
                     self.sprite_x[sprite_index] = self.oam.secondary[sprite_index].x;
@@ -698,6 +754,123 @@ impl Ppu {
                 _ => unreachable!(),
             }
         }
+        // Empty sprite slots still fetch the pattern of the sprite 0xFF
+        else if (self.cycles - 257) % 8 == 0
+            && (self.registers.get_mask_flag(MaskFlag::ShowSprites)
+                || self.registers.get_mask_flag(MaskFlag::ShowBackground))
+        {
+            let lo_address: u16 = if self.registers.get_control_flag(ControlFlag::SpriteSize) == 0 {
+                ((self
+                    .registers
+                    .get_control_flag(ControlFlag::SpritePatternTableAddress)
+                    as u16)
+                    << 12)
+                    | 0x0FF0
+            } else {
+                0x1FE0
+            };
+            self.read_bus(lo_address);
+            self.read_bus(lo_address + 8);
+        }
+
+        // The sprites above the hardware limit are fetched after the others, without the mapper
+        // seeing these accesses which do not happen on hardware
+        if self.cycles == 320 {
+            for sprite_index in HARDWARE_SPRITE_LIMIT..self.next_sprite_count as usize {
+                self.load_sprite_pattern(sprite_index, false);
+                let sprite = self.oam.get_selected(sprite_index);
+                self.sprite_x[sprite_index] = sprite.x;
+                self.sprite_attributes[sprite_index] = sprite.attribute;
+            }
+        }
+    }
+
+    // Load the pattern of the sprite selected for the next scanline in its shifters
+    fn load_sprite_pattern(&mut self, sprite_index: usize, visible_to_mapper: bool) {
+        let sprite = self.oam.get_selected(sprite_index);
+        let lo_address: u16; // Address of the low byte of the sprite
+        let v_flip: bool = sprite.get_attribute_flag(SpriteAttribute::FlipVertically) == 1;
+        // 8x8 sprites
+        if self.registers.get_control_flag(ControlFlag::SpriteSize) == 0 {
+            // Do not flip sprite vertically
+            if !v_flip {
+                lo_address = ((self
+                    .registers
+                    .get_control_flag(ControlFlag::SpritePatternTableAddress)
+                    as u16)
+                    << 12)
+                    | ((sprite.id as u16) << 4)
+                    | (self.scanline as i16 - (sprite.y as i16)) as u16;
+            }
+            // Flip sprite vertically
+            else {
+                lo_address = ((self
+                    .registers
+                    .get_control_flag(ControlFlag::SpritePatternTableAddress)
+                    as u16)
+                    << 12)
+                    | ((sprite.id as u16) << 4)
+                    | (7 - (self.scanline as i16 - (sprite.y as i16))) as u16;
+            }
+        }
+        // 8x16 sprites
+        else {
+            // Do not flip sprite vertically
+            if !v_flip {
+                // First half of the sprite
+                if self.scanline - (sprite.y as u16) < 8 {
+                    lo_address = (((sprite.id & 0x01) as u16) << 12)
+                        | (((sprite.id & 0xFE) as u16) << 4)
+                        | ((self.scanline as i16 - (sprite.y as i16)) & 0x07) as u16;
+                }
+                // Second half of the sprite
+                else {
+                    lo_address = (((sprite.id & 0x01) as u16) << 12)
+                        | ((((sprite.id & 0xFE) as u16) + 1) << 4)
+                        | ((self.scanline as i16 - (sprite.y as i16)) & 0x07) as u16;
+                }
+            }
+            // Flip sprite vertically
+            else {
+                // Second half of the sprite
+                if self.scanline - (sprite.y as u16) < 8 {
+                    lo_address = (((sprite.id & 0x01) as u16) << 12)
+                        | ((((sprite.id & 0xFE) as u16) + 1) << 4)
+                        | ((7 - (self.scanline as i16 - (sprite.y as i16))) & 0x07) as u16;
+                }
+                // First half of the sprite
+                else {
+                    lo_address = (((sprite.id & 0x01) as u16) << 12)
+                        | (((sprite.id & 0xFE) as u16) << 4) as u16
+                        | (7 - ((self.scanline as i16 - (sprite.y as i16)) & 0x07)) as u16;
+                }
+            }
+        }
+
+        // Get low and high bytes of the sprite
+        let (mut lo_sprite, mut hi_sprite) = if visible_to_mapper {
+            (self.read_bus(lo_address), self.read_bus(lo_address + 8))
+        } else {
+            (
+                self.read_only_bus(lo_address),
+                self.read_only_bus(lo_address + 8),
+            )
+        };
+
+        // Flip horizontally
+        if sprite.get_attribute_flag(SpriteAttribute::FlipHorizontally) == 1 {
+            lo_sprite = ((lo_sprite & 0xF0) >> 4) | ((lo_sprite & 0x0F) << 4);
+            lo_sprite = ((lo_sprite & 0xCC) >> 2) | ((lo_sprite & 0x33) << 2);
+            lo_sprite = ((lo_sprite & 0xAA) >> 1) | ((lo_sprite & 0x55) << 1);
+
+            hi_sprite = ((hi_sprite & 0xF0) >> 4) | ((hi_sprite & 0x0F) << 4);
+            hi_sprite = ((hi_sprite & 0xCC) >> 2) | ((hi_sprite & 0x33) << 2);
+            hi_sprite = ((hi_sprite & 0xAA) >> 1) | ((hi_sprite & 0x55) << 1);
+        }
+
+        // Finally write the result into our shifters
+        self.sprite_shifters[sprite_index][0] = lo_sprite;
+        self.sprite_shifters[sprite_index][1] = hi_sprite;
     }
 
     fn get_sprite_shifters_value(&self, sprite_index: usize) -> u8 {
@@ -892,6 +1065,14 @@ impl Ppu {
     // ===== DEBUGGING =====
 
     pub fn get_pattern_table(&self, number: u16) -> Result<[ARGBColor; 16384], Box<dyn Error>> {
+        self.get_pattern_table_with_palette(number, self.debug_palette_id)
+    }
+
+    pub fn get_pattern_table_with_palette(
+        &self,
+        number: u16,
+        palette_id: u8,
+    ) -> Result<[ARGBColor; 16384], Box<dyn Error>> {
         if number > 1 {
             return Err("Pattern table number must be either 0 or 1".into());
         }
@@ -899,7 +1080,7 @@ impl Ppu {
         for n_tile_y in 0..16 {
             for n_tile_x in 0..16 {
                 let buffer_offset = (n_tile_y * 128 + n_tile_x) * 8;
-                let tile = self.get_tile(n_tile_x, n_tile_y, number)?;
+                let tile = self.get_tile(n_tile_x, n_tile_y, number, palette_id)?;
                 for (i, pixel) in tile.iter().enumerate() {
                     buffer[buffer_offset + (i / 8) * 128 + i % 8] = *pixel;
                 }
@@ -913,6 +1094,7 @@ impl Ppu {
         n_tile_x: usize,
         n_tile_y: usize,
         pattern_table: u16,
+        palette_id: u8,
     ) -> Result<[ARGBColor; 64], Box<dyn Error>> {
         if n_tile_x >= 16 || n_tile_y >= 16 {
             return Err("Tile coordinates must be in [0;15]".into());
@@ -922,25 +1104,258 @@ impl Ppu {
         let mut buffer = [ARGBColor::black(); 64];
         let n_offset = n_tile_y * 256 + n_tile_x * 16;
         for row in 0..8 {
-            let mut tile_low: u8 = self.read_bus(pattern_table * 0x1000 + (n_offset + row) as u16);
+            let mut tile_low: u8 =
+                self.read_only_bus(pattern_table * 0x1000 + (n_offset + row) as u16);
             let mut tile_high: u8 =
-                self.read_bus(pattern_table * 0x1000 + (n_offset + row) as u16 + 0x0008);
+                self.read_only_bus(pattern_table * 0x1000 + (n_offset + row) as u16 + 0x0008);
             for col in 0..8 {
                 let color: u8 = (tile_low & 0x01) | ((tile_high & 0x01) << 1);
                 tile_high >>= 1;
                 tile_low >>= 1;
-                buffer[row * 8 + (7 - col)] = self.get_pixel_color(self.debug_palette_id, color);
+                buffer[row * 8 + (7 - col)] = self.get_pixel_color(palette_id, color);
             }
         }
         Ok(buffer)
     }
 
+    pub fn get_nametable(&self, index: u8) -> Result<Vec<ARGBColor>, Box<dyn Error>> {
+        if index > 3 {
+            return Err("Nametable index must be between 0 and 3".into());
+        }
+        // The address goes through the mirroring of the cartridge
+        let base_address: u16 = 0x2000 + 0x0400 * index as u16;
+        let pattern_table: u16 = (self
+            .registers
+            .get_control_flag(ControlFlag::BackgroundPatternTableAddress)
+            as u16)
+            << 12;
+        let mut buffer = vec![ARGBColor::black(); 256 * 240];
+        for tile_y in 0..30 {
+            for tile_x in 0..32 {
+                let tile_id = self.read_only_bus(base_address + tile_y * 32 + tile_x);
+                // Same attribute table lookup as when rendering the background
+                let mut attribute =
+                    self.read_only_bus(base_address + 0x03C0 + (tile_y >> 2) * 8 + (tile_x >> 2));
+                if tile_y & 0x02 > 0 {
+                    attribute >>= 4;
+                }
+                if tile_x & 0x02 > 0 {
+                    attribute >>= 2;
+                }
+                let palette = attribute & 0x03;
+                for row in 0..8 {
+                    let address = pattern_table + ((tile_id as u16) << 4) + row;
+                    let tile_low = self.read_only_bus(address);
+                    let tile_high = self.read_only_bus(address + 8);
+                    for col in 0..8 {
+                        let color = ((tile_low >> (7 - col)) & 0x01)
+                            | (((tile_high >> (7 - col)) & 0x01) << 1);
+                        // Transparent pixels show the universal background color
+                        let palette = if color == 0 { 0 } else { palette };
+                        buffer[((tile_y * 8 + row) * 256 + tile_x * 8 + col) as usize] =
+                            self.get_pixel_color(palette, color);
+                    }
+                }
+            }
+        }
+        Ok(buffer)
+    }
+
+    pub fn get_nametable_tiles(&self, index: u8) -> Result<[[u8; 32]; 30], Box<dyn Error>> {
+        if index > 3 {
+            return Err("Nametable index must be between 0 and 3".into());
+        }
+        let base_address: u16 = 0x2000 + 0x0400 * index as u16;
+        let mut tiles = [[0; 32]; 30];
+        for (tile_y, row) in tiles.iter_mut().enumerate() {
+            for (tile_x, tile) in row.iter_mut().enumerate() {
+                *tile = self.read_only_bus(base_address + (tile_y * 32 + tile_x) as u16);
+            }
+        }
+        Ok(tiles)
+    }
+
+    // Predict the scanline of the sprite 0 hit from the OAM, the scrolling and the memory,
+    // assuming they are not changed while the frame is rendered
+    // This follows the sprite 0 hit detection done in `clock`
+    pub fn predict_sprite_0_hit_scanline(&self) -> Option<u16> {
+        if !self.registers.get_mask_flag(MaskFlag::ShowBackground)
+            || !self.registers.get_mask_flag(MaskFlag::ShowSprites)
+        {
+            return None;
+        }
+        let left_clipping = !self
+            .registers
+            .get_mask_flag(MaskFlag::ShowLeftScreenBackground)
+            || !self
+                .registers
+                .get_mask_flag(MaskFlag::ShowLeftScreenSprites);
+
+        let sprite = self.oam.primary[0];
+        let sprite_size: u16 = match self.registers.get_control_flag(ControlFlag::SpriteSize) {
+            0 => 8,
+            _ => 16,
+        };
+        let h_flip = sprite.get_attribute_flag(SpriteAttribute::FlipHorizontally) == 1;
+        let v_flip = sprite.get_attribute_flag(SpriteAttribute::FlipVertically) == 1;
+
+        for row in 0..sprite_size {
+            // Sprites are evaluated on the scanline before the one they are drawn on
+            let scanline = sprite.y as u16 + 1 + row;
+            if scanline >= 240 {
+                break;
+            }
+            let sprite_row = if v_flip { sprite_size - 1 - row } else { row };
+            let address = if sprite_size == 8 {
+                ((self
+                    .registers
+                    .get_control_flag(ControlFlag::SpritePatternTableAddress)
+                    as u16)
+                    << 12)
+                    | ((sprite.id as u16) << 4)
+                    | sprite_row
+            } else {
+                (((sprite.id & 0x01) as u16) << 12)
+                    | ((((sprite.id & 0xFE) as u16) + (sprite_row >> 3)) << 4)
+                    | (sprite_row & 0x07)
+            };
+            let sprite_low = self.read_only_bus(address);
+            let sprite_high = self.read_only_bus(address + 8);
+
+            for col in 0..8 {
+                let x = sprite.x as u16 + col;
+                // The hit is never detected on the last pixel of the scanline
+                if x >= 255 {
+                    break;
+                }
+                if x < 8 && left_clipping {
+                    continue;
+                }
+                let bit = if h_flip { col } else { 7 - col };
+                let sprite_pattern =
+                    ((sprite_low >> bit) & 0x01) | (((sprite_high >> bit) & 0x01) << 1);
+                if sprite_pattern != 0 && self.get_background_pattern(x, scanline) != 0 {
+                    return Some(scanline);
+                }
+            }
+        }
+        None
+    }
+
+    // Pattern of the background pixel at the given screen position,
+    // with the scrolling copied to the VRAM address at the start of the frame
+    fn get_background_pattern(&self, x: u16, y: u16) -> u8 {
+        let tmp_vram_address = &self.ppu_bus.tmp_vram_address;
+        let coarse_y = tmp_vram_address.get_address_part(VRAMAddressMask::CoarseYScroll);
+        let mut nametable = tmp_vram_address.get_address_part(VRAMAddressMask::NametableSelect);
+        let mut world_x = tmp_vram_address.get_address_part(VRAMAddressMask::CoarseXScroll) * 8
+            + self.registers.fine_x as u16
+            + x;
+        let mut world_y =
+            coarse_y * 8 + tmp_vram_address.get_address_part(VRAMAddressMask::FineY) + y;
+        if world_x >= 256 {
+            world_x -= 256;
+            nametable ^= 0x01;
+        }
+        // Coarse y goes to the next nametable after the row 29, but stays in the same
+        // nametable when scrolling starts in the attribute rows 30 and 31
+        if coarse_y < 30 && world_y >= 240 {
+            world_y -= 240;
+            nametable ^= 0x02;
+        } else if world_y >= 256 {
+            world_y -= 256;
+        }
+
+        let tile_id =
+            self.read_only_bus(0x2000 + (nametable << 10) + (world_y >> 3) * 32 + (world_x >> 3));
+        let address = ((self
+            .registers
+            .get_control_flag(ControlFlag::BackgroundPatternTableAddress)
+            as u16)
+            << 12)
+            + ((tile_id as u16) << 4)
+            + (world_y & 0x07);
+        let bit = 7 - (world_x & 0x07);
+        ((self.read_only_bus(address) >> bit) & 0x01)
+            | (((self.read_only_bus(address + 8) >> bit) & 0x01) << 1)
+    }
+
+    pub fn get_oam(&self) -> [SpriteEntry; 64] {
+        self.oam.primary.map(|sprite| sprite.to_entry())
+    }
+
+    pub fn get_secondary_oam(&self) -> [SpriteEntry; 8] {
+        self.oam.secondary.map(|sprite| sprite.to_entry())
+    }
+
+    pub fn get_scroll_state(&self) -> PpuScrollState {
+        let vram_address = &self.ppu_bus.vram_address;
+        PpuScrollState {
+            vram_address: vram_address.address,
+            tmp_vram_address: self.ppu_bus.tmp_vram_address.address,
+            coarse_x: vram_address.get_address_part(VRAMAddressMask::CoarseXScroll) as u8,
+            coarse_y: vram_address.get_address_part(VRAMAddressMask::CoarseYScroll) as u8,
+            fine_x: self.registers.fine_x,
+            fine_y: vram_address.get_address_part(VRAMAddressMask::FineY) as u8,
+            nametable: vram_address.get_address_part(VRAMAddressMask::NametableSelect) as u8,
+            write_toggle: self.registers.get_write_toggle(),
+        }
+    }
+
     pub fn get_palette(&self) -> Result<[ARGBColor; 32], Box<dyn Error>> {
         let mut buffer = [ARGBColor::black(); 32];
         let address_offset = 0x3F00;
         for (i, pixel) in buffer.iter_mut().enumerate() {
-            *pixel = self.palettes.base[(self.read_bus(address_offset + i as u16) & 0x3F) as usize];
+            *pixel =
+                self.palettes.base[(self.read_only_bus(address_offset + i as u16) & 0x3F) as usize];
         }
         Ok(buffer)
     }
+
+    // The palette RAM only stores 6 bits per entry
+    pub fn get_palette_indices(&self) -> [u8; 32] {
+        let mut indices = [0; 32];
+        for (i, index) in indices.iter_mut().enumerate() {
+            *index = self.read_only_bus(0x3F00 + i as u16) & 0x3F;
+        }
+        indices
+    }
+}
+
+// Load the palette from the in-memory data if provided, from the palette file otherwise
+fn load_palette(palette_path: &Option<String>, palette_data: &Option<Vec<u8>>) -> Palette {
+    if let Some(data) = palette_data {
+        return match Palette::from_bytes(data) {
+            Ok(p) => p,
+            Err(_) => {
+                warn!("Unable to load palette from data, using default palette.");
+                Palette::default()
+            }
+        };
+    }
+    load_palette_file(palette_path)
+}
+
+#[cfg(feature = "std")]
+fn load_palette_file(palette_path: &Option<String>) -> Palette {
+    let palette_path = match palette_path {
+        Some(p) => p,
+        None => "./palette.pal",
+    };
+    match Palette::from_file(palette_path) {
+        Ok(p) => p,
+        Err(_) => {
+            warn!(
+                "Unable to load palette at {}, using default palette.",
+                palette_path
+            );
+            Palette::default()
+        }
+    }
+}
+
+// Palette files cannot be read without the filesystem
+#[cfg(not(feature = "std"))]
+fn load_palette_file(_palette_path: &Option<String>) -> Palette {
+    Palette::default()
 }
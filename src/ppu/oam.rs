@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
 use super::sprite::Sprite;
+use crate::prelude::*;
 
 #[serde_as]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -11,6 +12,9 @@ pub struct Oam {
     #[serde_as(as = "[_; 64]")]
     pub primary: [Sprite; 64],
     pub secondary: [Sprite; 8],
+    // Sprites in range found after the 8 of the secondary OAM, rendered when the sprite limit is raised
+    #[serde(default)]
+    pub extra: Vec<Sprite>,
 }
 
 impl Oam {
@@ -18,6 +22,7 @@ impl Oam {
         Oam {
             primary: [Sprite::default(); 64],
             secondary: [Sprite::default(); 8],
+            extra: Vec::new(),
         }
     }
 
@@ -51,6 +56,14 @@ impl Oam {
         }
     }
 
+    // Get a sprite selected for the next scanline, including the ones above the hardware limit
+    pub fn get_selected(&self, index: usize) -> Sprite {
+        match self.secondary.get(index) {
+            Some(sprite) => *sprite,
+            None => self.extra[index - self.secondary.len()],
+        }
+    }
+
     pub fn write_secondary(&mut self, address: u8, data: u8) {
         let sprite_index: usize = (address / 4) as usize;
         match address % 4 {
@@ -1,7 +1,10 @@
 // Implements the different color palettes of the NES
 
+use crate::prelude::*;
 use crate::utils::ARGBColor;
-use std::{error::Error, fs};
+use core::error::Error;
+#[cfg(feature = "std")]
+use std::fs;
 
 pub struct Palette {
     pub base: [ARGBColor; 64],
@@ -29,7 +32,12 @@ impl Palette {
         }
     }
 
+    #[cfg(feature = "std")]
     pub fn from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        Palette::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn from_bytes(raw: &[u8]) -> Result<Self, Box<dyn Error>> {
         fn parse_palette_bytes(palette: &[u8]) -> [ARGBColor; 64] {
             let mut p = [ARGBColor::black(); 64];
             for (i, color) in palette.chunks(3).enumerate() {
@@ -38,15 +46,13 @@ impl Palette {
             p
         }
 
-        let raw = fs::read(path)?;
-
         // Palette file can contain a base palette and all emphasized versions (8 in total)
         // Each palette has 64 colors, and each color is composed of 3 bytes (r, g, b) => 1536 bytes
         // Or just contain a base palette, used for all other emphasized components => 192 bytes
         let is_full_palette = match raw.len() {
             1536 => true,
             192 => false,
-            _ => return Err("Palette file has an incorrect format".into()),
+            _ => return Err("Palette data has an incorrect format".into()),
         };
 
         let mut palettes = vec![];
@@ -56,7 +62,7 @@ impl Palette {
             }
         } else {
             for _ in 0..8 {
-                palettes.push(parse_palette_bytes(&raw));
+                palettes.push(parse_palette_bytes(raw));
             }
         }
 
@@ -1,5 +1,6 @@
-use std::error::Error;
+use core::error::Error;
 
+use log::debug;
 use serde::{Deserialize, Serialize};
 
 use crate::errors::{InvalidPPURegisterReadError, InvalidPPURegisterWriteError};
@@ -9,6 +10,7 @@ use super::{
     enums::{ControlFlag, MaskFlag, StatusFlag, VRAMAddressMask},
     oam::Oam,
 };
+use crate::prelude::*;
 
 // Reprensents the PPU registers
 
@@ -75,6 +77,19 @@ impl Registers {
         }
     }
 
+    // The reset signal clears the control, mask and scroll registers, the write toggle and
+    // the read buffer, the status, OAM address and VRAM address are kept
+    pub fn reset(&mut self) {
+        self.ctrl = 0;
+        self.mask = 0;
+        self.scroll = 0;
+        self.data_buffer = 0;
+        self.w = false;
+        self.fine_x = 0;
+        self.emit_nmi = false;
+        self.clocks_before_emiting = 0;
+    }
+
     // Writes value to one of the PPU registers
     pub fn write_register(
         &mut self,
@@ -152,9 +167,10 @@ impl Registers {
             }
             0x2007 => {
                 self.data = value;
-                ppu_bus
-                    .write(ppu_bus.vram_address.address & 0x3FFF, value)
-                    .unwrap();
+                // Writes to CHR ROM are ignored
+                if let Err(e) = ppu_bus.write(ppu_bus.vram_address.address & 0x3FFF, value) {
+                    debug!("{}", e);
+                }
                 if self.get_control_flag(ControlFlag::VRAMAddressIncrement) == 0 {
                     ppu_bus.vram_address.address += 1; // Horizontal scrolling
                 } else {
@@ -200,6 +216,7 @@ impl Registers {
             0x2006 => Ok(self.decay),
             0x2007 => {
                 // Read to 2007 is delayed by one read except for the palette
+                // The stored palette entry is returned, grayscale and emphasis only apply to rendering
                 let mut value = self.data_buffer;
                 self.data_buffer = ppu_bus.read(ppu_bus.vram_address.address).unwrap();
                 if ppu_bus.vram_address.address >= 0x3F00 {
@@ -230,12 +247,17 @@ impl Registers {
         }
     }
 
-    fn get_status_flag(&self, flag: StatusFlag) -> bool {
+    pub fn get_status_flag(&self, flag: StatusFlag) -> bool {
         (self.status & (flag as u8)) == (flag as u8)
     }
 
+    // Second write to 0x2005 or 0x2006
+    pub fn get_write_toggle(&self) -> bool {
+        self.w
+    }
+
     // Get the flags from the control register
-    pub fn get_control_flag(&mut self, flag: ControlFlag) -> u8 {
+    pub fn get_control_flag(&self, flag: ControlFlag) -> u8 {
         if flag != ControlFlag::NametableAddress {
             ((self.ctrl & (flag as u8)) == (flag as u8)) as u8
         } else {
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use super::enums::SpriteAttribute;
+use super::{enums::SpriteAttribute, state::SpriteEntry};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct Sprite {
@@ -27,4 +27,13 @@ impl Sprite {
             self.attribute & 0x03
         }
     }
+
+    pub fn to_entry(self) -> SpriteEntry {
+        SpriteEntry {
+            y: self.y,
+            tile_id: self.id,
+            attributes: self.attribute,
+            x: self.x,
+        }
+    }
 }
@@ -7,13 +7,42 @@ use super::{
     bus::{PPUBus, VRAMAddress},
     oam::Oam,
     registers::Registers,
+    HARDWARE_SPRITE_LIMIT,
 };
+use crate::prelude::*;
+
+/// A sprite of the OAM (Object Attribute Memory).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteEntry {
+    pub y: u8,
+    pub tile_id: u8,
+    pub attributes: u8,
+    pub x: u8,
+}
+
+/// Scrolling registers of the PPU.
+/// The scroll values are decoded from the current VRAM address ("loopy v").
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PpuScrollState {
+    /// Current VRAM address.
+    pub vram_address: u16,
+    /// Temporary VRAM address, copied to the current one during rendering.
+    pub tmp_vram_address: u16,
+    pub coarse_x: u8,
+    pub coarse_y: u8,
+    pub fine_x: u8,
+    pub fine_y: u8,
+    /// Index of the selected nametable (0 to 3).
+    pub nametable: u8,
+    /// True if the next write to 0x2005 or 0x2006 is the second one.
+    pub write_toggle: bool,
+}
 
 #[serde_as]
 #[derive(Serialize, Deserialize)]
 pub struct PpuBusState {
-    #[serde_as(as = "[[_; 0x0400]; 2]")]
-    pub name_tables: [[u8; 0x0400]; 2],
+    #[serde_as(as = "[[_; 0x0400]; 4]")]
+    pub name_tables: [[u8; 0x0400]; 4],
     #[serde_as(as = "[_; 0x20]")]
     pub palette_table: [u8; 0x20],
     pub vram_address: VRAMAddress,
@@ -30,9 +59,18 @@ pub struct PpuState {
     current_sprite_count: u8,
     next_contains_sprite_0: bool,
     current_contains_sprite_0: bool,
+    #[serde(default)]
+    sprite_overflow_byte: u8,
     sprite_shifters: [[u8; 2]; 8],
     sprite_x: [u8; 8],
     sprite_attributes: [u8; 8],
+    // Sprites above the hardware limit, rendered when the sprite limit is raised
+    #[serde(default)]
+    extra_sprite_shifters: Vec<[u8; 2]>,
+    #[serde(default)]
+    extra_sprite_x: Vec<u8>,
+    #[serde(default)]
+    extra_sprite_attributes: Vec<u8>,
     next_name_table_byte: u8,
     next_attribute_table_byte: u8,
     next_low_background_byte: u8,
@@ -59,9 +97,17 @@ impl Stateful for super::Ppu {
             current_sprite_count: self.current_sprite_count,
             next_contains_sprite_0: self.next_contains_sprite_0,
             current_contains_sprite_0: self.current_contains_sprite_0,
-            sprite_shifters: self.sprite_shifters,
-            sprite_x: self.sprite_x,
-            sprite_attributes: self.sprite_attributes,
+            sprite_overflow_byte: self.sprite_overflow_byte,
+            sprite_shifters: self.sprite_shifters[..HARDWARE_SPRITE_LIMIT]
+                .try_into()
+                .unwrap(),
+            sprite_x: self.sprite_x[..HARDWARE_SPRITE_LIMIT].try_into().unwrap(),
+            sprite_attributes: self.sprite_attributes[..HARDWARE_SPRITE_LIMIT]
+                .try_into()
+                .unwrap(),
+            extra_sprite_shifters: self.sprite_shifters[HARDWARE_SPRITE_LIMIT..].to_vec(),
+            extra_sprite_x: self.sprite_x[HARDWARE_SPRITE_LIMIT..].to_vec(),
+            extra_sprite_attributes: self.sprite_attributes[HARDWARE_SPRITE_LIMIT..].to_vec(),
             next_name_table_byte: self.next_name_table_byte,
             next_attribute_table_byte: self.next_attribute_table_byte,
             next_low_background_byte: self.next_low_background_byte,
@@ -85,9 +131,11 @@ impl Stateful for super::Ppu {
         self.current_sprite_count = state.current_sprite_count;
         self.next_contains_sprite_0 = state.next_contains_sprite_0;
         self.current_contains_sprite_0 = state.current_contains_sprite_0;
-        self.sprite_shifters = state.sprite_shifters;
-        self.sprite_x = state.sprite_x;
-        self.sprite_attributes = state.sprite_attributes;
+        self.sprite_overflow_byte = state.sprite_overflow_byte;
+        self.sprite_shifters = [&state.sprite_shifters[..], &state.extra_sprite_shifters].concat();
+        self.sprite_x = [&state.sprite_x[..], &state.extra_sprite_x].concat();
+        self.sprite_attributes =
+            [&state.sprite_attributes[..], &state.extra_sprite_attributes].concat();
         self.next_name_table_byte = state.next_name_table_byte;
         self.next_attribute_table_byte = state.next_attribute_table_byte;
         self.next_low_background_byte = state.next_low_background_byte;
@@ -1,10 +1,16 @@
+use core::ops::Range;
+
+#[cfg(feature = "std")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use serde_json::Value;
 
 use crate::apu::state::ApuState;
 use crate::bus::BusState;
 use crate::cartridge::mapper::MapperState;
 use crate::cpu::state::CpuState;
 use crate::ppu::state::PpuState;
+use crate::prelude::*;
 
 pub trait Stateful {
     type State;
@@ -13,18 +19,126 @@ pub trait Stateful {
     fn set_state(&mut self, state: &Self::State);
 }
 
-#[derive(Serialize, Deserialize)]
+// The mapper state is serialized with typetag, which needs std
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
 pub struct NesState {
     pub bus: BusState,
     pub cpu: CpuState,
     pub ppu: PpuState,
     pub apu: ApuState,
+    // Only restored by the serialized states, which need std
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
     pub mapper: Box<dyn MapperState>,
     pub total_clock: u64,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub frame_count: u64,
     pub dma_started: bool,
     pub dma_hi_address: u8,
     pub dma_base_address: u8,
     pub dma_address_offset: u8,
     pub dma_data: u8,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub dmc_dma_cycles: u8,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub dmc_dma_requested: bool,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub dmc_dma_halt_cycles: u8,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub o_nmi_cycles_before_poll: Option<u8>,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub nmi_delayed: bool,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub nmi_polled: bool,
+    #[cfg_attr(feature = "std", serde(default))]
+    pub irq_triggered: bool,
     pub add_samples: bool,
 }
+
+/// A field that differs between two NES states, as reported by `NES::state_diff`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Path of the field, starting with the subsystem it belongs to ("cpu", "ppu", "apu", "bus",
+    /// "mapper"), such as "cpu.a" or "bus.cpu_ram". Elements of arrays are given as "field[index]".
+    pub path: String,
+    /// Indices of a run of consecutive differing values, for arrays of values such as the RAM.
+    pub o_range: Option<Range<usize>>,
+}
+
+#[cfg(feature = "std")]
+impl NesState {
+    // Compare the states field by field, through their JSON representation
+    pub fn diff(&self, other: &NesState) -> Result<Vec<StateDiff>, serde_json::Error> {
+        let mut diffs = vec![];
+        diff_values(
+            &serde_json::to_value(self)?,
+            &serde_json::to_value(other)?,
+            "",
+            &mut diffs,
+        );
+        Ok(diffs)
+    }
+}
+
+#[cfg(feature = "std")]
+fn diff_values(value: &Value, other: &Value, path: &str, diffs: &mut Vec<StateDiff>) {
+    match (value, other) {
+        (Value::Object(fields), Value::Object(other_fields)) => {
+            let field_path = |key: &str| match path {
+                "" => key.to_owned(),
+                _ => format!("{}.{}", path, key),
+            };
+            for (key, field) in fields {
+                match other_fields.get(key) {
+                    Some(other_field) => diff_values(field, other_field, &field_path(key), diffs),
+                    None => diffs.push(StateDiff {
+                        path: field_path(key),
+                        o_range: None,
+                    }),
+                }
+            }
+            for key in other_fields.keys().filter(|k| !fields.contains_key(*k)) {
+                diffs.push(StateDiff {
+                    path: field_path(key),
+                    o_range: None,
+                });
+            }
+        }
+        (Value::Array(elements), Value::Array(other_elements))
+            if elements.len() == other_elements.len() =>
+        {
+            // Consecutive differing values are reported together, nested arrays and objects
+            // are compared element by element
+            let mut o_start = None;
+            for (i, (element, other_element)) in elements.iter().zip(other_elements).enumerate() {
+                let is_nested = element.is_array() || element.is_object();
+                if is_nested || element == other_element {
+                    if let Some(start) = o_start.take() {
+                        diffs.push(StateDiff {
+                            path: path.to_owned(),
+                            o_range: Some(start..i),
+                        });
+                    }
+                }
+                if is_nested {
+                    diff_values(element, other_element, &format!("{}[{}]", path, i), diffs);
+                } else if element != other_element && o_start.is_none() {
+                    o_start = Some(i);
+                }
+            }
+            if let Some(start) = o_start {
+                diffs.push(StateDiff {
+                    path: path.to_owned(),
+                    o_range: Some(start..elements.len()),
+                });
+            }
+        }
+        _ => {
+            if value != other {
+                diffs.push(StateDiff {
+                    path: path.to_owned(),
+                    o_range: None,
+                });
+            }
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use serde::{Deserialize, Serialize};
 
+use crate::errors::NesError;
+use crate::prelude::*;
+
 /// A utiliy struct to represent an color.
 /// The alpha channel is not calculated by the emulator (set to 255).
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -44,3 +47,64 @@ pub enum ControllerInput {
     B = 0b0100_0000,
     A = 0b1000_0000,
 }
+
+/// One of the two controller ports of the NES.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControllerPort {
+    One,
+    Two,
+}
+
+impl ControllerPort {
+    pub(crate) fn index(self) -> usize {
+        match self {
+            ControllerPort::One => 0,
+            ControllerPort::Two => 1,
+        }
+    }
+}
+
+impl TryFrom<usize> for ControllerPort {
+    type Error = NesError;
+
+    /// Get the port from its index, 0 or 1.
+    fn try_from(id: usize) -> Result<Self, Self::Error> {
+        match id {
+            0 => Ok(ControllerPort::One),
+            1 => Ok(ControllerPort::Two),
+            _ => Err(NesError::InvalidArgument(String::from(
+                "Controller id must be either 0 or 1",
+            ))),
+        }
+    }
+}
+
+/// A button of a NES controller, to be used with `NES::set_button`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Button {
+    // Bit of the button in the byte read from the controller
+    pub(crate) fn mask(self) -> u8 {
+        let input = match self {
+            Button::A => ControllerInput::A,
+            Button::B => ControllerInput::B,
+            Button::Select => ControllerInput::Select,
+            Button::Start => ControllerInput::Start,
+            Button::Up => ControllerInput::Up,
+            Button::Down => ControllerInput::Down,
+            Button::Left => ControllerInput::Left,
+            Button::Right => ControllerInput::Right,
+        };
+        input as u8
+    }
+}
@@ -1,5 +1,24 @@
 mod common;
 
+use common::ines;
+use nesmulator_core::{nes::NES, Config};
+
+// Cartridge looping forever without touching the APU
+fn idle_nes() -> NES {
+    idle_nes_with_config(Config::default())
+}
+
+fn idle_nes_with_config(config: Config) -> NES {
+    let mut rom = ines(0, 0x00, 1, 0);
+    let prg_rom = &mut rom[16..];
+    prg_rom[..3].copy_from_slice(&[0x4C, 0x00, 0x80]); // JMP $8000
+    prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    let mut nes = NES::from_config(config);
+    nes.insert_cartdrige_from_bytes(&rom).unwrap();
+    nes
+}
+
 mod apu_test {
     use crate::common::run_rom;
     use crate::common::ROM_PATH_PREFIX;
@@ -96,3 +115,590 @@ mod apu_reset {
         format!("{}{}{}", ROM_PATH_PREFIX, DIR_PATH, rom)
     }
 }
+
+// Same cycle writes and clocks of the length counters, as tested by the len_halt_timing and
+// len_reload_timing tests of blargg's APU tests
+mod length_counter_timing {
+    use nesmulator_core::nes::NES;
+
+    use crate::idle_nes;
+
+    // Length counter loads of 254 and 2
+    const LENGTH_254: u8 = 0x08;
+    const LENGTH_2: u8 = 0x18;
+
+    #[test]
+    fn reload_ignored_when_clocked() {
+        let mut nes = enabled_pulse1();
+        write_apu(&mut nes, 0x4003, LENGTH_2);
+        clock_half_frame(&mut nes);
+
+        // The length counter goes from 1 to 0 instead of being reloaded with 254
+        nes.write_memory_at(0x4003, LENGTH_254).unwrap();
+        clock_half_frame(&mut nes);
+        assert!(!is_pulse1_playing(&mut nes));
+    }
+
+    #[test]
+    fn reload_when_zero() {
+        let mut nes = enabled_pulse1();
+
+        // The clock does not decrement the length counter, so it is reloaded
+        nes.write_memory_at(0x4003, LENGTH_2).unwrap();
+        clock_half_frame(&mut nes);
+        clock_half_frame(&mut nes);
+        assert!(is_pulse1_playing(&mut nes));
+        clock_half_frame(&mut nes);
+        assert!(!is_pulse1_playing(&mut nes));
+    }
+
+    #[test]
+    fn halt_after_clock() {
+        // The clock still decrements the length counter when the halt flag is set
+        let mut nes = enabled_pulse1();
+        write_apu(&mut nes, 0x4003, LENGTH_2);
+        nes.write_memory_at(0x4000, 0x20).unwrap();
+        clock_half_frame(&mut nes);
+        clock_half_frame(&mut nes);
+        write_apu(&mut nes, 0x4000, 0x00);
+        clock_half_frame(&mut nes);
+        assert!(!is_pulse1_playing(&mut nes));
+
+        // The clock does not decrement the length counter when the halt flag is cleared
+        let mut nes = enabled_pulse1();
+        write_apu(&mut nes, 0x4000, 0x20);
+        write_apu(&mut nes, 0x4003, LENGTH_2);
+        nes.write_memory_at(0x4000, 0x00).unwrap();
+        clock_half_frame(&mut nes);
+        clock_half_frame(&mut nes);
+        assert!(is_pulse1_playing(&mut nes));
+    }
+
+    // Pulse 1 enabled, with its length counter not halted
+    fn enabled_pulse1() -> NES {
+        let mut nes = idle_nes();
+        write_apu(&mut nes, 0x4015, 0x01);
+        write_apu(&mut nes, 0x4000, 0x00);
+        nes
+    }
+
+    // Write to an APU register and let the write take effect
+    fn write_apu(nes: &mut NES, address: u16, value: u8) {
+        nes.write_memory_at(address, value).unwrap();
+        nes.clock_n(3).unwrap();
+    }
+
+    // Writing 0x80 to 0x4017 clocks the length counters on the next CPU cycle, which is also
+    // the cycle on which the writes made before this call take effect
+    fn clock_half_frame(nes: &mut NES) {
+        nes.write_memory_at(0x4017, 0x80).unwrap();
+        nes.clock_n(3).unwrap();
+    }
+
+    fn is_pulse1_playing(nes: &mut NES) -> bool {
+        nes.read_memory_at(0x4015).unwrap() & 0x01 > 0
+    }
+}
+
+mod apu_status {
+    use crate::idle_nes;
+
+    #[test]
+    fn frame_interrupt_not_acknowledged() {
+        let mut nes = idle_nes();
+        // Enable the frame interrupt and wait for a whole frame counter sequence
+        nes.write_memory_at(0x4017, 0x00).unwrap();
+        nes.clock_n(30_000 * 3).unwrap();
+
+        assert_eq!(nes.get_apu_status() & 0x40, 0x40);
+        assert_eq!(nes.get_apu_status() & 0x40, 0x40);
+        assert_eq!(nes.read_memory_at(0x4015).unwrap() & 0x40, 0x40);
+        assert_eq!(nes.get_apu_status() & 0x40, 0x00);
+    }
+
+    #[test]
+    fn dmc_interrupt_not_acknowledged_by_read() {
+        let mut nes = idle_nes();
+        // Play a 1 byte sample with the DMC interrupt enabled
+        nes.write_memory_at(0x4010, 0x8F).unwrap();
+        nes.write_memory_at(0x4013, 0x00).unwrap();
+        nes.write_memory_at(0x4015, 0x10).unwrap();
+        nes.clock_n(1_000 * 3).unwrap();
+
+        // As checked by blargg's dmc_basics test, reading 0x4015 does not clear the flag
+        assert_eq!(nes.read_memory_at(0x4015).unwrap() & 0x80, 0x80);
+        assert_eq!(nes.read_memory_at(0x4015).unwrap() & 0x80, 0x80);
+        nes.write_memory_at(0x4015, 0x00).unwrap();
+        assert_eq!(nes.read_memory_at(0x4015).unwrap() & 0x80, 0x00);
+    }
+}
+
+mod speed_multiplier {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+
+    const FRAMES: usize = 10;
+
+    #[test]
+    fn fast_forward_produces_less_samples() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}apu_test/1-len_ctr.nes", ROM_PATH_PREFIX))
+            .unwrap();
+
+        let real_time_samples = count_samples(&mut nes);
+        nes.set_speed_multiplier(4.0);
+        let fast_forward_samples = count_samples(&mut nes);
+
+        // 44100 Hz at 60 frames per second
+        assert!((7000..7700).contains(&real_time_samples));
+        assert!((real_time_samples / 5..real_time_samples / 3).contains(&fast_forward_samples));
+    }
+
+    fn count_samples(nes: &mut NES) -> usize {
+        nes.get_samples();
+        for _ in 0..FRAMES {
+            nes.run_one_frame().unwrap();
+        }
+        nes.get_samples().len()
+    }
+}
+
+mod samples_for_frame {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn partitioned_by_frame() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}apu_test/1-len_ctr.nes", ROM_PATH_PREFIX))
+            .unwrap();
+        nes.run_one_frame().unwrap();
+
+        nes.get_samples();
+        nes.run_one_frame().unwrap();
+        let frame_samples = nes.get_samples_for_frame();
+        // 44100 Hz at 60 frames per second
+        assert!((700..770).contains(&frame_samples.len()));
+        assert_eq!(frame_samples, nes.get_samples());
+
+        // The samples of a frame are only returned once
+        assert!(nes.get_samples_for_frame().is_empty());
+    }
+}
+
+mod audio_clock_drift {
+    use crate::idle_nes;
+
+    const CPU_CLOCK_FREQUENCY: f64 = 5_369_318.0 / 3.0;
+    const SAMPLE_RATE: f64 = 44_100.0;
+
+    #[test]
+    fn drift_from_sample_rate() {
+        let mut nes = idle_nes();
+        assert_eq!(nes.audio_clock_drift(), 0.0);
+        for _ in 0..60 {
+            nes.run_one_frame().unwrap();
+        }
+
+        let produced = nes.get_samples().len() as f64;
+        let expected = (nes.total_clock() / 3) as f64 * SAMPLE_RATE / CPU_CLOCK_FREQUENCY;
+        let drift = nes.audio_clock_drift();
+        assert!((drift - (produced - expected)).abs() < 0.1, "{}", drift);
+        // A sample is produced every 40 CPU cycles instead of 40.58
+        assert!(drift > 0.0);
+
+        // The drift is counted again from the reset
+        nes.reset();
+        assert_eq!(nes.audio_clock_drift(), 0.0);
+    }
+}
+
+mod sample_rate {
+    use nesmulator_core::Config;
+
+    use crate::idle_nes_with_config;
+
+    #[test]
+    fn out_of_range_sample_rates() {
+        // Clamped to one sample per CPU cycle
+        let mut nes = idle_nes_with_config(Config {
+            sample_rate: 10_000_000,
+            ..Config::default()
+        });
+        nes.clock_n(3 * 100).unwrap();
+        assert_eq!(nes.get_samples().len(), 100);
+
+        let mut nes = idle_nes_with_config(Config {
+            sample_rate: 0,
+            ..Config::default()
+        });
+        nes.run_one_frame().unwrap();
+        assert!(nes.get_samples().iter().all(|s| s.is_finite()));
+    }
+}
+
+mod channel_outputs {
+    use std::sync::{Arc, Mutex};
+
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::{nes::NES, ApuChannel};
+
+    #[test]
+    fn dmc_direct_load() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}apu_test/1-len_ctr.nes", ROM_PATH_PREFIX))
+            .unwrap();
+
+        // Writing to 0x4011 directly sets the DMC output level
+        nes.write_memory_at(0x4011, 0x45).unwrap();
+        assert_eq!(nes.get_apu_channel_outputs().dmc, 0x45);
+
+        nes.set_channel_enabled(ApuChannel::Dmc, false);
+        assert_eq!(nes.get_apu_channel_outputs().dmc, 0);
+    }
+
+    #[test]
+    fn dmc_sample_callback() {
+        let mut nes = crate::idle_nes();
+        let levels = Arc::new(Mutex::new(vec![]));
+        let levels_clone = Arc::clone(&levels);
+        nes.set_dmc_sample_callback(Box::new(move |level| {
+            levels_clone.lock().unwrap().push(level)
+        }));
+
+        nes.write_memory_at(0x4011, 0x40).unwrap();
+        assert_eq!(*levels.lock().unwrap(), vec![0x40]);
+
+        // The sample is made of zeros, which lowers the output level
+        nes.write_memory_at(0x4010, 0x0F).unwrap();
+        nes.write_memory_at(0x4013, 0x00).unwrap();
+        nes.write_memory_at(0x4015, 0x10).unwrap();
+        nes.clock_n(10_000).unwrap();
+        assert_eq!(levels.lock().unwrap()[..4], [0x40, 0x3E, 0x3C, 0x3A]);
+    }
+
+    #[test]
+    fn triangle_ultrasonic() {
+        let mut nes = crate::idle_nes();
+
+        // Play the triangle with a period of 0
+        nes.write_memory_at(0x4015, 0x04).unwrap();
+        nes.write_memory_at(0x4008, 0xFF).unwrap();
+        nes.write_memory_at(0x400A, 0x00).unwrap();
+        nes.write_memory_at(0x400B, 0x08).unwrap();
+
+        // Wait for the linear counter to be reloaded
+        nes.clock_n(100_000).unwrap();
+
+        // The sequencer is not advanced so the output holds its level
+        let output = nes.get_apu_channel_outputs().triangle;
+        for _ in 0..1_000 {
+            nes.clock().unwrap();
+            assert_eq!(nes.get_apu_channel_outputs().triangle, output);
+        }
+    }
+}
+
+mod noise_shift_register {
+    use nesmulator_core::nes::NES;
+
+    // The shift register starts at 1, its sequence repeats every 32767 steps in the
+    // long mode, and every 93 steps in the short mode when starting from 1
+    #[test]
+    fn long_mode() {
+        assert_eq!(sequence_period(0x00, 32_767 * 3), 32_767);
+    }
+
+    #[test]
+    fn short_mode() {
+        assert_eq!(sequence_period(0x80, 93 * 3), 93);
+    }
+
+    #[test]
+    fn muted_when_bit_0_set() {
+        let mut nes = play_noise(0x00);
+        assert_eq!(nes.get_apu_channel_outputs().noise, 0);
+        nes.clock_n(3 * 16).unwrap();
+        assert_eq!(nes.get_apu_channel_outputs().noise, 15);
+    }
+
+    // Play the noise channel with the shortest period and find the period of the sequence
+    // of its outputs, one output for each step of the shift register. The sequences have about
+    // one run of identical outputs every two steps, `n_runs` must cover more than two periods.
+    fn sequence_period(mode: u8, n_runs: usize) -> usize {
+        let mut nes = play_noise(mode);
+        let mut runs: Vec<(u8, usize)> = vec![];
+        while runs.len() < n_runs {
+            let output = nes.get_apu_channel_outputs().noise;
+            match runs.last_mut() {
+                Some((value, length)) if *value == output => *length += 1,
+                _ => runs.push((output, 1)),
+            }
+            nes.clock_n(3).unwrap();
+        }
+
+        // The first and last runs may be cut, the others last a multiple of the step duration
+        let runs = &runs[1..runs.len() - 1];
+        let step = runs.iter().map(|&(_, l)| l).reduce(gcd).unwrap();
+        let outputs: Vec<u8> = runs
+            .iter()
+            .flat_map(|&(value, length)| std::iter::repeat_n(value, length / step))
+            .collect();
+        (1..outputs.len() / 2)
+            .find(|&p| (p..outputs.len()).all(|i| outputs[i] == outputs[i - p]))
+            .unwrap()
+    }
+
+    fn play_noise(mode: u8) -> NES {
+        let mut nes = crate::idle_nes();
+        nes.write_memory_at(0x4015, 0x08).unwrap();
+        nes.write_memory_at(0x400C, 0x3F).unwrap(); // Length counter halted, constant volume 15
+        nes.write_memory_at(0x400E, mode).unwrap();
+        nes.write_memory_at(0x400F, 0x08).unwrap();
+        nes
+    }
+
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+}
+
+mod stereo {
+    use nesmulator_core::{nes::NES, ApuChannel};
+
+    use crate::idle_nes;
+
+    #[test]
+    fn panned_channel() {
+        let mut nes = idle_nes();
+        nes.set_stereo_output(true);
+        nes.set_channel_pan(ApuChannel::Dmc, -1.0);
+
+        let samples = play_dmc(&mut nes);
+        assert!(nes.get_samples().is_empty());
+        assert!(samples.iter().any(|&(left, _)| left != 0.0));
+        assert!(samples.iter().all(|&(_, right)| right == 0.0));
+    }
+
+    #[test]
+    fn centered_channel() {
+        let mut mono = idle_nes();
+        play_dmc(&mut mono);
+        let mono_samples = mono.get_samples();
+
+        let mut stereo = idle_nes();
+        stereo.set_stereo_output(true);
+        let stereo_samples = play_dmc(&mut stereo);
+
+        assert_eq!(mono_samples.len(), stereo_samples.len());
+        for (mono_sample, (left, right)) in mono_samples.into_iter().zip(stereo_samples) {
+            assert_eq!(mono_sample, left);
+            assert_eq!(mono_sample, right);
+        }
+    }
+
+    // Set the DMC output level and return the stereo samples produced
+    fn play_dmc(nes: &mut NES) -> Vec<(f32, f32)> {
+        nes.write_memory_at(0x4011, 0x7F).unwrap();
+        nes.clock_n(10_000).unwrap();
+        nes.get_stereo_samples()
+    }
+}
+
+mod audio_filters {
+    const CLOCKS: u64 = 10_000;
+
+    #[test]
+    fn flush_audio_filters() {
+        let mut played = crate::idle_nes();
+        let mut silent = crate::idle_nes();
+
+        // Play a DC step on the DMC, then get back to silence
+        played.write_memory_at(0x4011, 0x7F).unwrap();
+        played.clock_n(CLOCKS).unwrap();
+        silent.clock_n(CLOCKS).unwrap();
+        played.write_memory_at(0x4011, 0x00).unwrap();
+        played.get_samples();
+        silent.get_samples();
+
+        // The filters still output the end of the step
+        played.clock_n(CLOCKS).unwrap();
+        silent.clock_n(CLOCKS).unwrap();
+        assert_ne!(played.get_samples(), silent.get_samples());
+
+        played.flush_audio_filters();
+        silent.flush_audio_filters();
+        played.clock_n(CLOCKS).unwrap();
+        silent.clock_n(CLOCKS).unwrap();
+        assert_eq!(played.get_samples(), silent.get_samples());
+    }
+}
+
+mod nsf {
+    use nesmulator_core::{nes::NES, NesError};
+
+    #[test]
+    fn play_nsf() {
+        let mut nes = NES::new();
+        let info = nes.load_nsf(&build_nsf()).unwrap();
+        assert_eq!(info.name, "Test tune");
+        assert_eq!(info.n_songs, 2);
+        assert_eq!(info.starting_song, 1);
+        assert_eq!(info.play_address, 0x8007);
+
+        // The init routine is called with the song in A and the region in X
+        for _ in 0..10 {
+            nes.run_one_frame().unwrap();
+        }
+        assert_eq!(nes.read_memory_at(0x0300).unwrap(), 1);
+        assert_eq!(nes.read_memory_at(0x0301).unwrap(), 0);
+        // The play routine is called once per frame
+        let plays = nes.read_memory_at(0x0302).unwrap();
+        assert!((8..=10).contains(&plays), "{}", plays);
+
+        nes.play_nsf_track(0).unwrap();
+        nes.run_one_frame().unwrap();
+        assert_eq!(nes.read_memory_at(0x0300).unwrap(), 0);
+        assert!(nes.read_memory_at(0x0302).unwrap() <= 1);
+
+        assert!(matches!(
+            nes.play_nsf_track(2),
+            Err(NesError::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            nes.load_nsf(&[0; 0x100]),
+            Err(NesError::InvalidRom(_))
+        ));
+    }
+
+    fn build_nsf() -> Vec<u8> {
+        let mut nsf = vec![0; 0x80];
+        nsf[0..5].copy_from_slice(b"NESM\x1A");
+        nsf[0x05] = 1; // Version
+        nsf[0x06] = 2; // Number of songs
+        nsf[0x07] = 2; // Starting song
+        nsf[0x08..0x0E].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x07, 0x80]);
+        nsf[0x0E..0x17].copy_from_slice(b"Test tune");
+        nsf[0x6E..0x70].copy_from_slice(&16_639u16.to_le_bytes());
+        nsf.extend([
+            0x8D, 0x00, 0x03, // Init: STA $0300
+            0x8E, 0x01, 0x03, // STX $0301
+            0x60, // RTS
+            0xEE, 0x02, 0x03, // Play: INC $0302
+            0x60, // RTS
+        ]);
+        nsf
+    }
+}
+
+mod dmc_controller_conflict {
+    use crate::common::ines;
+    use nesmulator_core::{nes::NES, utils::ControllerPort};
+
+    #[test]
+    fn bits_deleted_during_playback() {
+        assert!(count_corrupted_reads(true) > 0);
+    }
+
+    #[test]
+    fn no_conflict_without_playback() {
+        assert_eq!(count_corrupted_reads(false), 0);
+    }
+
+    // Strobe and read the first controller in a loop, counting the reads that do not return
+    // the pressed buttons in 0x0002
+    fn count_corrupted_reads(dmc_enabled: bool) -> u8 {
+        #[rustfmt::skip]
+        let program = [
+            0xA9, 0x4F, // LDA #$4F
+            0x8D, 0x10, 0x40, // STA $4010 (loop, highest rate)
+            0xA9, 0x00, // LDA #$00
+            0x8D, 0x12, 0x40, // STA $4012
+            0xA9, 0xFF, // LDA #$FF
+            0x8D, 0x13, 0x40, // STA $4013
+            0xA9, (dmc_enabled as u8) << 4, // LDA #$10
+            0x8D, 0x15, 0x40, // STA $4015
+            // loop:
+            0xA2, 0x01, // LDX #$01
+            0x8E, 0x16, 0x40, // STX $4016
+            0xCA, // DEX
+            0x8E, 0x16, 0x40, // STX $4016
+            0xA0, 0x08, // LDY #$08
+            // read:
+            0xAD, 0x16, 0x40, // LDA $4016
+            0x4A, // LSR A
+            0x26, 0x00, // ROL $00
+            0x88, // DEY
+            0xD0, 0xF7, // BNE read
+            0xA5, 0x00, // LDA $00
+            0xC9, 0xAA, // CMP #$AA
+            0xF0, 0x02, // BEQ +2
+            0xE6, 0x02, // INC $02
+            0x24, 0x00, // BIT $00 (the loop is not aligned with the DMA period)
+            0x4C, 0x14, 0x80, // JMP loop
+        ];
+        let mut rom = ines(0, 0x00, 1, 0);
+        let prg_rom = &mut rom[16..];
+        prg_rom[..program.len()].copy_from_slice(&program);
+        prg_rom[0x3FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&rom).unwrap();
+        nes.input(ControllerPort::One, 0xAA);
+        nes.run_one_frame().unwrap();
+        nes.run_one_frame().unwrap();
+        nes.read_memory_at(0x0002).unwrap()
+    }
+}
+
+mod dmc_address_wrap {
+    use crate::common::ines;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn sample_across_banks() {
+        let with_bank_0 = play_wrapping_sample(0);
+        let with_bank_1 = play_wrapping_sample(1);
+
+        // The last byte of the sample is read at 0x8000 in the bank selected by the mapper,
+        // it is made of 8 cleared bits in bank 0 and 8 set bits in bank 1
+        assert_eq!(with_bank_1 - with_bank_0, 32);
+    }
+
+    // Play a sample from 0xFFC0 to 0x8000 and return the final DMC output level
+    fn play_wrapping_sample(bank: u8) -> u8 {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&build_mmc1()).unwrap();
+
+        // Select the bank at 0x8000 through the MMC1 shift register
+        for i in 0..5 {
+            nes.write_memory_at(0xE000, (bank >> i) & 0x01).unwrap();
+        }
+
+        nes.write_memory_at(0x4011, 100).unwrap();
+        nes.write_memory_at(0x4010, 0x0F).unwrap();
+        nes.write_memory_at(0x4012, 0xFF).unwrap();
+        nes.write_memory_at(0x4013, 0x04).unwrap();
+        nes.write_memory_at(0x4015, 0x10).unwrap();
+        nes.clock_n(300_000).unwrap();
+        nes.get_apu_channel_outputs().dmc
+    }
+
+    // 4 PRG ROM banks, the last one being fixed at 0xC000
+    fn build_mmc1() -> Vec<u8> {
+        let mut rom = ines(1, 0x00, 4, 0);
+        let prg_rom = &mut rom[16..];
+        prg_rom[0x4000] = 0xFF;
+        let last_bank = &mut prg_rom[3 * 0x4000..];
+        // JMP $C000
+        last_bank[..3].copy_from_slice(&[0x4C, 0x00, 0xC0]);
+        // Bits alternating between 1 and 0 do not change the output level
+        last_bank[0x3FC0..].fill(0x55);
+        last_bank[0x3FFA..].copy_from_slice(&[0x00, 0xC0, 0x00, 0xC0, 0x00, 0xC0]);
+        rom
+    }
+}
@@ -8,6 +8,26 @@ const TEST_RUNNING_ADDRESSES: [u16; 3] = [0x6001, 0x6002, 0x6003];
 const TEST_RUNNING_BYTES: [u8; 3] = [0xDE, 0xB0, 0x61];
 const RESET_DELAY: u32 = 1_000_000;
 
+// Build an iNES cartridge with zeroed PRG and CHR ROM banks
+// The mapper number goes in the upper nibbles of the flags 6 and 7
+pub fn ines(mapper: u8, flags_6: u8, n_prg_rom: u8, n_chr_rom: u8) -> Vec<u8> {
+    let mut rom = vec![
+        0x4E,
+        0x45,
+        0x53,
+        0x1A,
+        n_prg_rom,
+        n_chr_rom,
+        (mapper << 4) | flags_6,
+        mapper & 0xF0,
+    ];
+    rom.resize(
+        16 + n_prg_rom as usize * 0x4000 + n_chr_rom as usize * 0x2000,
+        0,
+    );
+    rom
+}
+
 pub fn run_rom(rom_path: &str) {
     let mut nes = NES::new();
     nes.insert_cartdrige(rom_path).unwrap();
@@ -23,7 +43,7 @@ pub fn run_rom(rom_path: &str) {
             should_reset = false;
         }
 
-        nes.clock();
+        nes.clock().unwrap();
 
         // Check if the data at 0x6000 has a valid value
         // This happens when 0x6001-0x6003 = [0xDE, 0xB0, 0x61]
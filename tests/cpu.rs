@@ -1,5 +1,10 @@
 mod common;
 
+use common::ROM_PATH_PREFIX;
+use nesmulator_core::{nes::NES, Config};
+
+const PROGRAM_ADDRESS: u16 = 0x0200;
+
 mod nes_instr_test {
     use crate::common::run_rom;
     use crate::common::ROM_PATH_PREFIX;
@@ -123,6 +128,8 @@ mod instr_misc {
 mod cpu_reset {
     use crate::common::run_rom;
     use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+    use nesmulator_core::{Config, RamPattern};
 
     const DIR_PATH: &str = "cpu_reset/";
 
@@ -136,6 +143,35 @@ mod cpu_reset {
         run_rom(&get_path("ram_after_reset.nes"))
     }
 
+    #[test]
+    fn ram_after_power_on() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&get_path("registers.nes")).unwrap();
+        nes.write_memory_at(0x0300, 0x55).unwrap();
+
+        nes.reset();
+        assert_eq!(nes.read_memory_at(0x0300).unwrap(), 0x55);
+
+        nes.power_on();
+        assert_eq!(nes.read_memory_at(0x0300).unwrap(), 0x00);
+    }
+
+    #[test]
+    fn ram_startup_pattern() {
+        let mut nes = NES::from_config(Config {
+            ram_startup_pattern: RamPattern::Pattern(vec![0x00, 0xFF, 0x42]),
+            ..Config::default()
+        });
+        nes.insert_cartdrige(&get_path("registers.nes")).unwrap();
+        assert_eq!(nes.read_memory_at(0x0001).unwrap(), 0xFF);
+        assert_eq!(nes.read_memory_at(0x0002).unwrap(), 0x42);
+        assert_eq!(nes.read_memory_at(0x07FF).unwrap(), 0xFF);
+
+        nes.write_memory_at(0x0002, 0x00).unwrap();
+        nes.power_on();
+        assert_eq!(nes.read_memory_at(0x0002).unwrap(), 0x42);
+    }
+
     fn get_path(rom: &str) -> String {
         format!("{}{}{}", ROM_PATH_PREFIX, DIR_PATH, rom)
     }
@@ -204,3 +240,1362 @@ mod cpu_interrupts_v2 {
         format!("{}{}{}", ROM_PATH_PREFIX, DIR_PATH, rom)
     }
 }
+
+mod zero_page_y {
+    use crate::load_program;
+
+    // LDY #$10, LDX $F8,Y, STX $F9,Y, JMP $0206
+    const PROGRAM: [u8; 9] = [0xA0, 0x10, 0xB6, 0xF8, 0x96, 0xF9, 0x4C, 0x06, 0x02];
+
+    #[test]
+    fn zero_page_y_wrap() {
+        let mut nes = load_program(&PROGRAM);
+        nes.write_memory_at(0x0008, 0x42).unwrap();
+        nes.write_memory_at(0x0108, 0x99).unwrap();
+
+        for _ in 0..200 {
+            nes.clock().unwrap();
+        }
+
+        // The indexed address wraps around the zero page instead of crossing to page 1
+        assert_eq!(nes.get_cpu_registers().x, 0x42);
+        assert_eq!(nes.read_memory_at(0x0009).unwrap(), 0x42);
+        assert_eq!(nes.read_memory_at(0x0109).unwrap(), 0x00);
+    }
+}
+
+mod input_recording {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+    use nesmulator_core::utils::ControllerPort;
+
+    const A_BUTTON: u8 = 0x80;
+
+    #[test]
+    fn replay_input_log() {
+        let mut nes = new_nes();
+        nes.begin_input_recording();
+        for _ in 0..100 {
+            nes.clock().unwrap();
+        }
+        nes.input(ControllerPort::One, A_BUTTON);
+        let log = nes.export_input_log();
+        assert_eq!(log, vec![(100, ControllerPort::One, A_BUTTON)]);
+
+        let mut nes = new_nes();
+        nes.replay_input_log(log);
+        for _ in 0..100 {
+            nes.clock().unwrap();
+        }
+        assert!(!is_a_pressed(&mut nes));
+        nes.clock().unwrap();
+        assert!(is_a_pressed(&mut nes));
+    }
+
+    fn new_nes() -> NES {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+        nes
+    }
+
+    // The A button is the first bit read from the controller after a strobe
+    fn is_a_pressed(nes: &mut NES) -> bool {
+        nes.write_memory_at(0x4016, 0x01).unwrap();
+        nes.read_memory_at(0x4016).unwrap() & 0x01 > 0
+    }
+}
+
+mod controller_strobe {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+    use nesmulator_core::utils::{Button, ControllerInput, ControllerPort};
+
+    #[test]
+    fn strobe_latch() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+        // A and Start pressed
+        nes.input(ControllerPort::One, 0x90);
+
+        // While the strobe is high, reads keep returning the current A button state
+        nes.write_memory_at(0x4016, 0x01).unwrap();
+        for _ in 0..4 {
+            assert_eq!(nes.read_memory_at(0x4016).unwrap() & 0x01, 1);
+        }
+        nes.input(ControllerPort::One, 0x10);
+        assert_eq!(nes.read_memory_at(0x4016).unwrap() & 0x01, 0);
+
+        // The buttons are latched on the falling edge, then shifted out on each read
+        nes.write_memory_at(0x4016, 0x00).unwrap();
+        nes.input(ControllerPort::One, 0xFF);
+        let buttons: Vec<u8> = (0..8)
+            .map(|_| nes.read_memory_at(0x4016).unwrap() & 0x01)
+            .collect();
+        assert_eq!(buttons, vec![0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+    #[test]
+    fn named_buttons() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+
+        nes.set_button(ControllerPort::Two, Button::A, true);
+        nes.set_button(ControllerPort::Two, Button::Start, true);
+        nes.set_button(ControllerPort::Two, Button::Left, true);
+        nes.set_button(ControllerPort::Two, Button::A, false);
+        assert_eq!(
+            nes.get_controller_state(ControllerPort::Two),
+            ControllerInput::Start as u8 | ControllerInput::Left as u8
+        );
+        assert_eq!(nes.get_controller_state(ControllerPort::One), 0x00);
+
+        // Buttons are read in the order A, B, Select, Start, Up, Down, Left, Right
+        nes.write_memory_at(0x4016, 0x01).unwrap();
+        nes.write_memory_at(0x4016, 0x00).unwrap();
+        let buttons: Vec<u8> = (0..8)
+            .map(|_| nes.read_memory_at(0x4017).unwrap() & 0x01)
+            .collect();
+        assert_eq!(buttons, vec![0, 0, 0, 1, 0, 0, 1, 0]);
+
+        assert_eq!(ControllerPort::try_from(1).unwrap(), ControllerPort::Two);
+        assert!(ControllerPort::try_from(2).is_err());
+    }
+}
+
+mod zapper {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn unplug_zapper() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+
+        // No light is detected before the first frame, and the trigger is pulled
+        nes.set_zapper_state(0, 0, true);
+        assert_eq!(nes.read_memory_at(0x4017).unwrap() & 0x18, 0x18);
+
+        // The standard controller does not use these bits
+        nes.unplug_zapper();
+        assert_eq!(nes.read_memory_at(0x4017).unwrap() & 0x18, 0x00);
+    }
+
+    #[test]
+    fn kept_on_state_load() {
+        let rom_path = format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX);
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&rom_path).unwrap();
+        let state = nes.serialize_state().unwrap();
+
+        // The zapper is a host input, loading a state saved without it must not unplug it
+        nes.set_zapper_state(0, 0, true);
+        nes.deserialize_state(&state, &rom_path).unwrap();
+        assert_eq!(nes.read_memory_at(0x4017).unwrap() & 0x18, 0x18);
+    }
+}
+
+mod open_bus {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn unmapped_reads() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+
+        nes.write_memory_at(0x0000, 0xA5).unwrap();
+        assert_eq!(nes.read_memory_at(0x4018).unwrap(), 0xA5);
+        // NROM has nothing mapped at 0x5000
+        assert_eq!(nes.read_memory_at(0x5000).unwrap(), 0xA5);
+        // Only the lowest bits of the controller ports are driven
+        assert_eq!(nes.read_memory_at(0x4016).unwrap() & 0xE0, 0xA0);
+
+        nes.write_memory_at(0x0000, 0x00).unwrap();
+        assert_eq!(nes.read_memory_at(0x4018).unwrap(), 0x00);
+    }
+}
+
+mod access_profiling {
+    use crate::common::ROM_PATH_PREFIX;
+    use crate::load_program;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn access_histogram() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+
+        nes.clock_n(1_000).unwrap();
+        assert!(nes.get_access_histogram().iter().all(|&a| a == (0, 0)));
+
+        nes.enable_access_profiling(true);
+        nes.write_memory_at(0x0305, 0x01).unwrap();
+        nes.write_memory_at(0x03FF, 0x02).unwrap();
+        nes.read_memory_at(0x0300).unwrap();
+        assert_eq!(nes.get_access_histogram()[0x03], (1, 2));
+
+        // The program is read from the cartridge
+        nes.clock_n(1_000).unwrap();
+        let histogram = nes.get_access_histogram();
+        assert!(histogram[0x80..].iter().any(|&(reads, _)| reads > 0));
+
+        nes.enable_access_profiling(false);
+        nes.clock_n(1_000).unwrap();
+        assert_eq!(nes.get_access_histogram(), histogram);
+    }
+
+    #[test]
+    fn zero_page_indexed_dummy_read() {
+        // LDX #$10, LDY #$10, LDA $F8,X, LDX $F8,Y
+        let mut nes = load_program(&[0xA2, 0x10, 0xA0, 0x10, 0xB5, 0xF8, 0xB6, 0xF8]);
+        nes.write_memory_at(0x0008, 0x42).unwrap();
+        nes.step_cpu_instruction().unwrap();
+        nes.step_cpu_instruction().unwrap();
+
+        // The base address is read once while adding the index, then the indexed
+        // address wraps around the zero page instead of crossing to page 1
+        nes.enable_access_profiling(true);
+        nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_access_histogram()[0x00], (2, 0));
+        assert_eq!(nes.get_access_histogram()[0x01], (0, 0));
+        nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_access_histogram()[0x00], (4, 0));
+        assert_eq!(nes.get_access_histogram()[0x01], (0, 0));
+
+        let registers = nes.get_cpu_registers();
+        assert_eq!((registers.a, registers.x), (0x42, 0x42));
+    }
+}
+
+mod disassemble_range {
+    use crate::load_program;
+    use nesmulator_core::{nes::NES, NesError};
+
+    #[test]
+    fn decode_program() {
+        // LDA #$10, STA $4015
+        let nes = load_program(&[0xA9, 0x10, 0x8D, 0x15, 0x40]);
+        let instructions = nes.disassemble_range(0x0200, 2).unwrap();
+        assert_eq!(instructions[0].mnemonic, "LDA");
+        assert_eq!(instructions[0].operand, "#$10");
+        assert_eq!(instructions[1].address, 0x0202);
+        assert_eq!(instructions[1].bytes, vec![0x8D, 0x15, 0x40]);
+        assert_eq!(instructions[1].operand, "$4015");
+    }
+
+    #[test]
+    fn no_cartridge() {
+        let nes = NES::new();
+        assert!(matches!(
+            nes.disassemble_range(0x8000, 4),
+            Err(NesError::NoCartridge)
+        ));
+    }
+}
+
+mod step_cpu_instruction {
+    use crate::{load_program, load_program_with_config};
+    use nesmulator_core::{Config, EmulationError, StatusFlags};
+
+    #[test]
+    fn step_over_dma() {
+        // LDA #$02, STA $4014, NOP
+        let mut nes = load_program(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0xEA]);
+
+        // The reset sequence completes before LDA is executed
+        nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_cpu_registers().pc, 0x0202);
+        assert_eq!(nes.get_cpu_registers().a, 0x02);
+
+        // STA takes 4 cycles, then the OAM DMA stalls the CPU for 513 or 514 cycles
+        let ppu_cycles = nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_cpu_registers().pc, 0x0205);
+        assert!((517 * 3..=518 * 3).contains(&ppu_cycles), "{}", ppu_cycles);
+
+        // NOP takes 2 cycles
+        assert_eq!(nes.step_cpu_instruction().unwrap(), 2 * 3);
+        assert_eq!(nes.get_cpu_registers().pc, 0x0206);
+    }
+
+    #[test]
+    fn step_over_dmc_dma() {
+        // LDA #$10, STA $4015, NOP
+        let mut nes = load_program(&[0xA9, 0x10, 0x8D, 0x15, 0x40, 0xEA]);
+        // 1 byte long sample
+        nes.write_memory_at(0x4013, 0x00).unwrap();
+        nes.step_cpu_instruction().unwrap();
+
+        // Enabling the DMC makes it fetch a sample byte, the CPU is halted on a get cycle
+        // so the sample byte is read after the dummy cycle, without an alignment cycle
+        assert_eq!(nes.step_cpu_instruction().unwrap(), (4 + 3) * 3);
+        assert_eq!(nes.step_cpu_instruction().unwrap(), 2 * 3);
+    }
+
+    #[test]
+    fn step_over_unofficial_nops() {
+        let mut nes = load_program(&[
+            0xA9, 0x20, 0x8D, 0x06, 0x20, // LDA #$20, STA $2006
+            0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+            0x0C, 0x07, 0x20, // NOP $2007
+            0xA2, 0x10, // LDX #$10
+            0x1C, 0xF7, 0x20, // NOP $20F7,X
+            0xA9, 0x55, 0x8D, 0x07, 0x20, // LDA #$55, STA $2007
+        ]);
+        for _ in 0..4 {
+            nes.step_cpu_instruction().unwrap();
+        }
+
+        // The absolute NOP takes 4 cycles and reads $2007, incrementing the VRAM address
+        assert_eq!(nes.step_cpu_instruction().unwrap(), 4 * 3);
+        assert_eq!(nes.get_cpu_registers().pc, 0x020D);
+        nes.step_cpu_instruction().unwrap();
+
+        // Crossing a page adds a cycle and a dummy read at $2007 before reading $2107
+        assert_eq!(nes.step_cpu_instruction().unwrap(), 5 * 3);
+        assert_eq!(nes.get_cpu_registers().pc, 0x0212);
+        nes.step_cpu_instruction().unwrap();
+        nes.step_cpu_instruction().unwrap();
+
+        assert_eq!(nes.read_ppu_memory_at(0x2003).unwrap(), 0x55);
+    }
+
+    #[test]
+    fn instruction_boundary() {
+        // NOP, LDA $0300, NOP
+        let mut nes = load_program(&[0xEA, 0xAD, 0x00, 0x03, 0xEA]);
+        nes.step_cpu_instruction().unwrap();
+        assert!(nes.is_cpu_at_instruction_boundary());
+
+        // LDA takes 4 cycles, the boundary is reached again after the last one
+        for _ in 0..3 {
+            nes.clock_n(3).unwrap();
+            assert!(!nes.is_cpu_at_instruction_boundary());
+        }
+        nes.clock_n(3).unwrap();
+        assert!(nes.is_cpu_at_instruction_boundary());
+        assert_eq!(nes.get_cpu_registers().pc, 0x0204);
+    }
+
+    #[test]
+    fn invalid_opcode_with_logs() {
+        // NOP, JAM
+        let config = Config {
+            display_cpu_logs: true,
+            ..Config::default()
+        };
+        let mut nes = load_program_with_config(&[0xEA, 0x02], config);
+        nes.step_cpu_instruction().unwrap();
+
+        // Logging the invalid opcode does not stop the error from being returned
+        assert_eq!(
+            nes.step_cpu_instruction(),
+            Err(EmulationError::InvalidOpcode {
+                opcode: 0x02,
+                address: 0x0201
+            })
+        );
+    }
+
+    #[test]
+    fn run_until_pc() {
+        // LDX #$00, INX, BNE -3, JMP $0205
+        let mut nes = load_program(&[0xA2, 0x00, 0xE8, 0xD0, 0xFD, 0x4C, 0x05, 0x02]);
+
+        nes.run_until_pc(0x0205, 10_000).unwrap();
+        assert_eq!(nes.get_cpu_registers().pc, 0x0205);
+        assert_eq!(nes.get_cpu_registers().x, 0x00);
+
+        // The loop is never entered again, the CPU stays on the JMP
+        assert_eq!(
+            nes.run_until_pc(0x0202, 1_000),
+            Err(EmulationError::Timeout(1_000))
+        );
+    }
+
+    #[test]
+    fn unstable_opcode_magic() {
+        let program = [
+            0xA9, 0xF0, 0xA2, 0x00, 0xAB, 0x13, // LDA #$F0, LDX #$00, LXA #$13
+            0xA9, 0x00, 0xA2, 0xFF, 0x8B, 0x13, // LDA #$00, LDX #$FF, ANE #$13
+        ];
+
+        // With the default magic value, A | magic is 0xFF and the operand is loaded as is
+        let mut nes = load_program(&program);
+        for _ in 0..3 {
+            nes.step_cpu_instruction().unwrap();
+        }
+        assert_eq!(nes.get_cpu_registers().a, 0x13);
+        assert_eq!(nes.get_cpu_registers().x, 0x13);
+        for _ in 0..3 {
+            nes.step_cpu_instruction().unwrap();
+        }
+        assert_eq!(nes.get_cpu_registers().a, 0x13);
+
+        let config = Config {
+            unstable_opcode_magic: 0xEE,
+            ..Config::default()
+        };
+        let mut nes = load_program_with_config(&program, config);
+        for _ in 0..3 {
+            nes.step_cpu_instruction().unwrap();
+        }
+        assert_eq!(nes.get_cpu_registers().a, 0x12);
+        assert_eq!(nes.get_cpu_registers().x, 0x12);
+        for _ in 0..3 {
+            nes.step_cpu_instruction().unwrap();
+        }
+        assert_eq!(nes.get_cpu_registers().a, 0x02);
+    }
+
+    #[test]
+    fn lxa_magic_values() {
+        // LDA #$5A, LXA #$F3
+        let program = [0xA9, 0x5A, 0xAB, 0xF3];
+
+        // Magic values observed on the different consoles
+        for magic in [0x00, 0xEE, 0xEF, 0xFE, 0xFF] {
+            let config = Config {
+                unstable_opcode_magic: magic,
+                ..Config::default()
+            };
+            let mut nes = load_program_with_config(&program, config);
+            nes.step_cpu_instruction().unwrap();
+            nes.step_cpu_instruction().unwrap();
+
+            let expected = (0x5A | magic) & 0xF3;
+            let registers = nes.get_cpu_registers();
+            assert_eq!(registers.a, expected, "magic {:02X}", magic);
+            assert_eq!(registers.x, expected, "magic {:02X}", magic);
+            let flags = registers.status_flags();
+            assert_eq!(flags.zero, expected == 0);
+            assert_eq!(flags.negative, expected & 0x80 != 0);
+        }
+    }
+
+    #[test]
+    fn status_flags() {
+        // SEC, SED, LDA #$80
+        let mut nes = load_program(&[0x38, 0xF8, 0xA9, 0x80]);
+        for _ in 0..3 {
+            nes.step_cpu_instruction().unwrap();
+        }
+        let flags = nes.get_cpu_registers().status_flags();
+        assert!(flags.carry && flags.decimal && flags.negative);
+        assert!(!flags.zero && !flags.overflow);
+
+        assert_eq!(StatusFlags::from_u8(0xFF).to_u8(), 0xFF);
+        // The unused bit is always set
+        assert_eq!(StatusFlags::from_u8(0x00).to_u8(), 0x20);
+    }
+
+    #[test]
+    fn decimal_mode_ignored() {
+        let mut nes = load_program(&[
+            0xF8, 0x18, 0xA9, 0x09, 0x69, 0x01, // SED, CLC, LDA #$09, ADC #$01
+            0xA9, 0x99, 0x69, 0x01, // LDA #$99, ADC #$01
+            0x38, 0xA9, 0x10, 0xE9, 0x01, // SEC, LDA #$10, SBC #$01
+        ]);
+        let mut step = |n_instructions: usize| {
+            for _ in 0..n_instructions {
+                nes.step_cpu_instruction().unwrap();
+            }
+            nes.get_cpu_registers()
+        };
+
+        // The 2A03 has no decimal mode, the additions and subtractions stay binary
+        let registers = step(4);
+        assert_eq!(registers.a, 0x0A);
+        assert!(registers.status_flags().decimal);
+        let registers = step(2);
+        assert_eq!(registers.a, 0x9A);
+        let flags = registers.status_flags();
+        assert!(flags.negative && !flags.carry && !flags.zero && !flags.overflow);
+        let registers = step(3);
+        assert_eq!(registers.a, 0x0F);
+        let flags = registers.status_flags();
+        assert!(flags.carry && flags.decimal && !flags.negative && !flags.zero);
+    }
+}
+
+mod dmc_dma {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::load_program;
+
+    // Position of the write cycles in the loop of the program
+    const LOOP_WRITE_CYCLES: [u64; 3] = [2, 6, 7];
+    const LOOP_CYCLES: u64 = 13;
+
+    #[test]
+    fn stall_lengths() {
+        let mut nes = load_program(&[
+            0xA9, 0x4F, 0x8D, 0x10, 0x40, // LDA #$4F, STA $4010
+            0xA9, 0x10, 0x8D, 0x15, 0x40, // LDA #$10, STA $4015
+            0x85, 0x00, // STA $00
+            0xE6, 0x00, // INC $00
+            0xEA, // NOP
+            0x4C, 0x0A, 0x02, // JMP $020A
+        ]);
+        // 1 byte long sample
+        nes.write_memory_at(0x4013, 0x00).unwrap();
+        // The looping sample is played at the highest rate, the DMC output unit is clocked
+        // on the cycle its sample buffer is emptied, requesting a new sample byte
+        let outputs = Arc::new(AtomicUsize::new(0));
+        let outputs_clone = Arc::clone(&outputs);
+        nes.set_dmc_sample_callback(Box::new(move |_| {
+            outputs_clone.fetch_add(1, Ordering::Relaxed);
+        }));
+        for _ in 0..4 {
+            nes.step_cpu_instruction().unwrap();
+        }
+        let loop_start = nes.get_cpu_registers().total_clock;
+
+        // Is the CPU halted, is it a get cycle, is it a write cycle of the program, is the DMC
+        // output unit clocked
+        let mut cycles = vec![];
+        for _ in 0..20_000 {
+            let is_get_cycle = nes.total_clock() % 2 == 0;
+            let cpu_clock = nes.get_cpu_registers().total_clock;
+            let n_outputs = outputs.load(Ordering::Relaxed);
+            nes.clock_n(3).unwrap();
+
+            let halted = nes.get_cpu_registers().total_clock == cpu_clock;
+            let loop_cycle = (cpu_clock - loop_start) % LOOP_CYCLES;
+            let is_write_cycle = !halted && LOOP_WRITE_CYCLES.contains(&loop_cycle);
+            let output = outputs.load(Ordering::Relaxed) != n_outputs;
+            cycles.push((halted, is_get_cycle, is_write_cycle, output));
+        }
+
+        let mut write_cycles_before_halt = vec![];
+        let mut start = 1;
+        while start < cycles.len() {
+            if !cycles[start].0 || cycles[start - 1].0 {
+                start += 1;
+                continue;
+            }
+            let end = (start..cycles.len()).find(|&c| !cycles[c].0).unwrap();
+            // The first request is done while the DMC output is silenced
+            if let Some(request) = (0..start).rev().find(|&c| cycles[c].3) {
+                // The CPU keeps running its write cycles, then it is halted on a read cycle
+                // and the sample byte is read on a get cycle after a dummy cycle
+                assert!(cycles[request + 1..start].iter().all(|c| c.2));
+                let stall = if cycles[start].1 { 3 } else { 4 };
+                assert_eq!(end - start, stall);
+                write_cycles_before_halt.push(start - request - 1);
+            }
+            start = end;
+        }
+
+        // A request on a read cycle, on the only or second write and on the first of 2 writes
+        for write_cycles in 0..=2 {
+            assert!(write_cycles_before_halt.contains(&write_cycles));
+        }
+    }
+}
+
+mod trigger_interrupts {
+    use crate::common::ROM_PATH_PREFIX;
+    use crate::{load_program, PROGRAM_ADDRESS};
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn trigger_nmi() {
+        // SEI, NOP
+        let mut nes = load_program(&[0x78, 0xEA]);
+        nes.step_cpu_instruction().unwrap();
+        nes.step_cpu_instruction().unwrap();
+
+        // The NMI is taken even if interrupts are disabled
+        nes.trigger_nmi();
+        nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_cpu_registers().pc, read_vector(&mut nes, 0xFFFA));
+    }
+
+    #[test]
+    fn trigger_irq() {
+        // SEI, NOP, CLI, NOP
+        let mut nes = load_program(&[0x78, 0xEA, 0x58, 0xEA]);
+        nes.step_cpu_instruction().unwrap();
+
+        // The IRQ is ignored while interrupts are disabled
+        nes.trigger_irq();
+        nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_cpu_registers().pc, PROGRAM_ADDRESS + 2);
+
+        nes.step_cpu_instruction().unwrap();
+        nes.trigger_irq();
+        nes.step_cpu_instruction().unwrap();
+        let registers = nes.get_cpu_registers();
+        assert_eq!(registers.pc, read_vector(&mut nes, 0xFFFE));
+        assert!(registers.status_flags().interrupt_disable);
+    }
+
+    #[test]
+    fn irq_saved_in_state() {
+        // CLI, NOP
+        let mut nes = load_program(&[0x58, 0xEA]);
+        nes.step_cpu_instruction().unwrap();
+        nes.trigger_irq();
+        let state = nes.serialize_state().unwrap();
+
+        // The pending IRQ is taken after the state is loaded
+        let mut nes = load_program(&[0x58, 0xEA]);
+        nes.deserialize_state(
+            &state,
+            &format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX),
+        )
+        .unwrap();
+        nes.step_cpu_instruction().unwrap();
+        assert_eq!(nes.get_cpu_registers().pc, read_vector(&mut nes, 0xFFFE));
+    }
+
+    fn read_vector(nes: &mut NES, address: u16) -> u16 {
+        u16::from_le_bytes([
+            nes.read_memory_at(address).unwrap(),
+            nes.read_memory_at(address + 1).unwrap(),
+        ])
+    }
+}
+
+mod clock_n {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+
+    const CLOCKS: u64 = 100_000;
+
+    #[test]
+    fn same_as_clock() {
+        let mut batched = new_nes();
+        let mut single = new_nes();
+
+        batched.clock_n(CLOCKS).unwrap();
+        for _ in 0..CLOCKS {
+            single.clock().unwrap();
+        }
+
+        assert_eq!(batched.get_cpu_registers(), single.get_cpu_registers());
+    }
+
+    #[test]
+    fn total_clock() {
+        let mut nes = new_nes();
+        assert_eq!(nes.total_clock(), 0);
+
+        nes.clock_n(CLOCKS).unwrap();
+        nes.clock().unwrap();
+        assert_eq!(nes.total_clock(), CLOCKS + 1);
+
+        // The counter is kept across resets
+        nes.reset();
+        assert_eq!(nes.total_clock(), CLOCKS + 1);
+    }
+
+    fn new_nes() -> NES {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+        nes
+    }
+}
+
+mod rewind {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::{nes::NES, NesError};
+
+    #[test]
+    fn rewind_one_frame() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+        nes.enable_rewind(2);
+
+        let mut frames = vec![];
+        for _ in 0..3 {
+            nes.run_one_frame().unwrap();
+            frames.push((nes.total_clock(), nes.get_cpu_registers()));
+        }
+
+        // The end of the current frame is skipped as nothing happened since
+        nes.rewind_one_frame().unwrap();
+        assert_eq!((nes.total_clock(), nes.get_cpu_registers()), frames[1]);
+
+        // Going back to the end of the last frame if it is not complete
+        nes.clock_n(1_000).unwrap();
+        nes.rewind_one_frame().unwrap();
+        assert_eq!((nes.total_clock(), nes.get_cpu_registers()), frames[1]);
+
+        // The emulation goes on the same way from the restored state
+        nes.run_one_frame().unwrap();
+        assert_eq!((nes.total_clock(), nes.get_cpu_registers()), frames[2]);
+
+        // Only 2 frames are kept, the first frame is lost
+        nes.rewind_one_frame().unwrap();
+        assert_eq!((nes.total_clock(), nes.get_cpu_registers()), frames[1]);
+        assert!(matches!(
+            nes.rewind_one_frame(),
+            Err(NesError::InvalidState(_))
+        ));
+    }
+}
+
+mod state_diff {
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::{nes::NES, NesError};
+
+    #[test]
+    fn state_diff() {
+        let mut nes = NES::new();
+        assert!(matches!(nes.state_diff(&[]), Err(NesError::NoCartridge)));
+        nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+            .unwrap();
+        nes.run_one_frame().unwrap();
+
+        let state = nes.serialize_state().unwrap();
+        assert!(nes.state_diff(&state).unwrap().is_empty());
+        assert!(matches!(
+            nes.state_diff(&[0x01]),
+            Err(NesError::InvalidState(_))
+        ));
+
+        // Consecutive RAM bytes are reported as a single range
+        nes.write_memory_at(0x0710, 0x01).unwrap();
+        nes.write_memory_at(0x0711, 0x02).unwrap();
+        nes.write_memory_at(0x0713, 0x03).unwrap();
+        let diffs = nes.state_diff(&state).unwrap();
+        let ram_ranges: Vec<_> = diffs
+            .iter()
+            .filter(|d| d.path == "bus.cpu_ram")
+            .map(|d| d.o_range.clone().unwrap())
+            .collect();
+        assert_eq!(ram_ranges, vec![0x710..0x712, 0x713..0x714]);
+        assert!(diffs.iter().all(|d| !d.path.starts_with("cpu.")));
+
+        nes.clock_n(30).unwrap();
+        let diffs = nes.state_diff(&state).unwrap();
+        assert!(diffs.iter().any(|d| d.path == "cpu.pc"));
+        assert!(diffs.iter().any(|d| d.path == "total_clock"));
+    }
+}
+
+mod cartridge_from_bytes {
+    use crate::common::ines;
+    use std::fs;
+
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn insert_cartdrige_from_bytes() {
+        let rom = fs::read(format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX)).unwrap();
+        let mut nes = NES::new();
+
+        assert!(nes
+            .insert_cartdrige_from_bytes(&rom[..rom.len() - 1])
+            .is_err());
+        nes.insert_cartdrige_from_bytes(&rom).unwrap();
+        let info = nes.get_cartridge_info().unwrap();
+        assert_eq!(info.mapper_number, 0);
+        assert_eq!(info.n_prg_rom, rom[4]);
+    }
+
+    #[test]
+    fn trainer() {
+        let mut rom = ines(0, 0x04, 1, 0);
+        rom[16] = 0x42;
+        rom.splice(16..16, (0..512).map(|i| i as u8));
+
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&rom).unwrap();
+        assert_eq!(nes.read_memory_at(0x7000).unwrap(), 0x00);
+        assert_eq!(nes.read_memory_at(0x71FF).unwrap(), 0xFF);
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x42);
+
+        // The trainer is counted in the size required by the header
+        assert!(nes
+            .insert_cartdrige_from_bytes(&rom[..rom.len() - 1])
+            .is_err());
+    }
+
+    #[test]
+    fn rom_hash() {
+        let rom = fs::read(format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX)).unwrap();
+        let mut nes = NES::new();
+        assert_eq!(nes.rom_hash(), None);
+
+        // The header is not part of the hash
+        nes.insert_cartdrige_from_bytes(&rom).unwrap();
+        assert_eq!(nes.rom_hash().unwrap().crc32, 0x0BF0_B94A);
+    }
+}
+
+mod custom_mapper {
+    use std::{any::Any, error::Error, fs};
+
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::{nes::NES, INesHeader, Mapper, MapperState, Mirroring};
+    use serde::{Deserialize, Serialize};
+
+    const MAPPER_NUMBER: u16 = 255;
+
+    // Same as NROM, with the writes to the ROM stored in a register
+    struct CustomMapper {
+        header: INesHeader,
+        prg_rom: Vec<[u8; 0x4000]>,
+        chr_rom: Vec<[u8; 0x2000]>,
+        register: u8,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct CustomMapperState {
+        register: u8,
+    }
+
+    #[typetag::serde]
+    impl MapperState for CustomMapperState {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    impl Mapper for CustomMapper {
+        fn prg_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+            match address {
+                0x6000 => Ok(self.register),
+                0x8000..=0xFFFF => {
+                    let bank = (address as usize - 0x8000) / 0x4000 % self.prg_rom.len();
+                    Ok(self.prg_rom[bank][(address & 0x3FFF) as usize])
+                }
+                _ => Err(format!("Invalid read at {:#X}", address).into()),
+            }
+        }
+
+        fn prg_rom_write(&mut self, address: u16, value: u8) -> Result<(), Box<dyn Error>> {
+            self.register = value;
+            match address {
+                0x8000..=0xFFFF => Ok(()),
+                _ => Err(format!("Invalid write at {:#X}", address).into()),
+            }
+        }
+
+        fn chr_rom_read(&self, address: u16) -> Result<u8, Box<dyn Error>> {
+            Ok(self.chr_rom[0][address as usize])
+        }
+
+        fn chr_rom_write(&mut self, address: u16, _value: u8) -> Result<(), Box<dyn Error>> {
+            Err(format!("Invalid write at {:#X}", address).into())
+        }
+
+        fn get_header(&self) -> &INesHeader {
+            &self.header
+        }
+
+        fn get_mirroring(&self) -> Mirroring {
+            self.header.mirroring
+        }
+
+        fn get_mapper_state(&self) -> Box<dyn MapperState> {
+            Box::new(CustomMapperState {
+                register: self.register,
+            })
+        }
+
+        fn set_mapper_state(&mut self, state: &dyn MapperState) {
+            let state = state.as_any().downcast_ref::<CustomMapperState>().unwrap();
+            self.register = state.register;
+        }
+    }
+
+    #[test]
+    fn register_mapper() {
+        let mut rom = fs::read(format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX)).unwrap();
+        rom[6] |= 0xF0;
+        rom[7] |= 0xF0;
+
+        let mut nes = NES::new();
+        nes.register_mapper(
+            MAPPER_NUMBER,
+            Box::new(|prg_rom, chr_rom, header| {
+                Box::new(CustomMapper {
+                    header,
+                    prg_rom,
+                    chr_rom,
+                    register: 0,
+                })
+            }),
+        );
+        nes.insert_cartdrige_from_bytes(&rom).unwrap();
+        assert_eq!(
+            nes.get_cartridge_info().unwrap().mapper_number,
+            MAPPER_NUMBER
+        );
+
+        nes.write_memory_at(0x8000, 0x42).unwrap();
+        assert_eq!(nes.read_memory_at(0x6000).unwrap(), 0x42);
+        // The cartridge space starts at 0x4020
+        nes.write_memory_at(0x4020, 0x24).unwrap();
+        assert_eq!(nes.read_memory_at(0x6000).unwrap(), 0x24);
+        // The reset vector is read from the last PRG ROM bank
+        let vector = &rom[16 + rom[4] as usize * 0x4000 - 4..][..2];
+        assert_eq!(nes.read_memory_at(0xFFFC).unwrap(), vector[0]);
+        assert_eq!(nes.read_memory_at(0xFFFD).unwrap(), vector[1]);
+        for _ in 0..10 {
+            nes.run_one_frame().unwrap();
+        }
+
+        // The registered mappers are kept by a restart
+        nes.restart();
+        nes.insert_cartdrige_from_bytes(&rom).unwrap();
+        assert_eq!(
+            nes.get_cartridge_info().unwrap().mapper_number,
+            MAPPER_NUMBER
+        );
+    }
+}
+
+mod mapper_bus_conflicts {
+    use crate::common::ines;
+    use nesmulator_core::nes::NES;
+
+    const BUS_CONFLICTS_SUBMAPPER: u8 = 2;
+
+    #[test]
+    fn bus_conflicts() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&build_uxrom(BUS_CONFLICTS_SUBMAPPER))
+            .unwrap();
+
+        // 0x03 is ANDed with the 0x01 byte of the ROM at 0xF000
+        nes.write_memory_at(0xF000, 0x03).unwrap();
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x11);
+    }
+
+    #[test]
+    fn no_bus_conflicts() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&build_uxrom(0)).unwrap();
+
+        nes.write_memory_at(0xF000, 0x03).unwrap();
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x13);
+    }
+
+    // NES 2.0 UxROM cartridge with 4 PRG ROM banks starting with 0x10 + their index
+    fn build_uxrom(submapper: u8) -> Vec<u8> {
+        let mut rom = ines(2, 0x00, 4, 0);
+        rom[7] |= 0x08;
+        rom[8] = submapper << 4;
+        for (bank, prg_rom) in rom[16..].chunks_mut(0x4000).enumerate() {
+            prg_rom[0] = 0x10 + bank as u8;
+            prg_rom[0x3000] = 0x01;
+        }
+        rom
+    }
+}
+
+mod bank_state {
+    use crate::common::ines;
+    use nesmulator_core::{nes::NES, MapperBankInfo};
+
+    #[test]
+    fn no_cartridge() {
+        assert_eq!(NES::new().get_bank_state(), None);
+    }
+
+    #[test]
+    fn uxrom() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&ines(2, 0x00, 4, 0))
+            .unwrap();
+
+        nes.write_memory_at(0x8000, 0x02).unwrap();
+        assert_eq!(
+            nes.get_bank_state(),
+            Some(MapperBankInfo {
+                o_lo_prg_bank: Some(2),
+                o_hi_prg_bank: Some(3),
+                o_lo_chr_bank: Some(0),
+                o_hi_chr_bank: Some(1),
+            })
+        );
+    }
+
+    #[test]
+    fn cnrom() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&ines(3, 0x00, 2, 4))
+            .unwrap();
+
+        nes.write_memory_at(0x8000, 0x03).unwrap();
+        assert_eq!(
+            nes.get_bank_state(),
+            Some(MapperBankInfo {
+                o_lo_prg_bank: Some(0),
+                o_hi_prg_bank: Some(1),
+                o_lo_chr_bank: Some(6),
+                o_hi_chr_bank: Some(7),
+            })
+        );
+    }
+
+    #[test]
+    fn mmc1() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&ines(1, 0x00, 4, 4))
+            .unwrap();
+
+        // Select 4KB CHR banks, then the PRG bank 1 and the CHR banks 3 and 5
+        write_mmc1(&mut nes, 0x8000, 0x1C);
+        write_mmc1(&mut nes, 0xE000, 0x01);
+        write_mmc1(&mut nes, 0xA000, 0x03);
+        write_mmc1(&mut nes, 0xC000, 0x05);
+        assert_eq!(
+            nes.get_bank_state(),
+            Some(MapperBankInfo {
+                o_lo_prg_bank: Some(1),
+                o_hi_prg_bank: Some(3),
+                o_lo_chr_bank: Some(3),
+                o_hi_chr_bank: Some(5),
+            })
+        );
+
+        // In 32KB PRG mode, the bank number ignores its low bit
+        write_mmc1(&mut nes, 0x8000, 0x10);
+        write_mmc1(&mut nes, 0xE000, 0x03);
+        let bank_state = nes.get_bank_state().unwrap();
+        assert_eq!(bank_state.o_lo_prg_bank, Some(2));
+        assert_eq!(bank_state.o_hi_prg_bank, Some(3));
+    }
+
+    // Load a value in a MMC1 register through its shift register
+    fn write_mmc1(nes: &mut NES, address: u16, value: u8) {
+        for i in 0..5 {
+            nes.write_memory_at(address, (value >> i) & 0x01).unwrap();
+        }
+    }
+}
+
+mod mmc5 {
+    use crate::common::ines;
+    use nesmulator_core::nes::NES;
+
+    #[test]
+    fn prg_banking() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&build_mmc5()).unwrap();
+
+        // The last bank is mapped at 0xE000 on power up
+        assert_eq!(nes.read_memory_at(0xE000).unwrap(), 0x17);
+
+        // 8KB mode
+        nes.write_memory_at(0x5114, 0x82).unwrap();
+        nes.write_memory_at(0x5115, 0x85).unwrap();
+        nes.write_memory_at(0x5116, 0x81).unwrap();
+        nes.write_memory_at(0x5117, 0x03).unwrap();
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x12);
+        assert_eq!(nes.read_memory_at(0xA000).unwrap(), 0x15);
+        assert_eq!(nes.read_memory_at(0xC000).unwrap(), 0x11);
+        assert_eq!(nes.read_memory_at(0xE000).unwrap(), 0x13);
+
+        // 16KB mode, the low bit of the bank number is ignored
+        nes.write_memory_at(0x5100, 0x01).unwrap();
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x14);
+        assert_eq!(nes.read_memory_at(0xA000).unwrap(), 0x15);
+        assert_eq!(nes.read_memory_at(0xC000).unwrap(), 0x12);
+        assert_eq!(nes.read_memory_at(0xE000).unwrap(), 0x13);
+
+        // 32KB mode
+        nes.write_memory_at(0x5100, 0x00).unwrap();
+        nes.write_memory_at(0x5117, 0x05).unwrap();
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x14);
+        assert_eq!(nes.read_memory_at(0xE000).unwrap(), 0x17);
+    }
+
+    #[test]
+    fn prg_ram() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&build_mmc5()).unwrap();
+
+        // The RAM is write protected until 0x5102 and 0x5103 are set
+        nes.write_memory_at(0x6000, 0x42).unwrap();
+        assert_eq!(nes.read_memory_at(0x6000).unwrap(), 0x00);
+        nes.write_memory_at(0x5102, 0x02).unwrap();
+        nes.write_memory_at(0x5103, 0x01).unwrap();
+        nes.write_memory_at(0x6000, 0x42).unwrap();
+        assert_eq!(nes.read_memory_at(0x6000).unwrap(), 0x42);
+
+        // The RAM can be mapped in the PRG ROM area
+        nes.write_memory_at(0x5114, 0x00).unwrap();
+        assert_eq!(nes.read_memory_at(0x8000).unwrap(), 0x42);
+
+        // ExRAM and multiplier
+        nes.write_memory_at(0x5C10, 0x24).unwrap();
+        assert_eq!(nes.read_memory_at(0x5C10).unwrap(), 0x24);
+        nes.write_memory_at(0x5205, 0x30).unwrap();
+        nes.write_memory_at(0x5206, 0x20).unwrap();
+        assert_eq!(nes.read_memory_at(0x5205).unwrap(), 0x00);
+        assert_eq!(nes.read_memory_at(0x5206).unwrap(), 0x06);
+    }
+
+    #[test]
+    fn chr_banking() {
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&build_mmc5()).unwrap();
+
+        // 1KB mode
+        nes.write_memory_at(0x5120, 0x05).unwrap();
+        nes.write_memory_at(0x5127, 0x02).unwrap();
+        assert_eq!(nes.read_ppu_memory_at(0x0000).unwrap(), 0x25);
+        assert_eq!(nes.read_ppu_memory_at(0x1C00).unwrap(), 0x22);
+
+        // 8KB mode, selected by the last register
+        nes.write_memory_at(0x5101, 0x00).unwrap();
+        nes.write_memory_at(0x5127, 0x00).unwrap();
+        assert_eq!(nes.read_ppu_memory_at(0x0400).unwrap(), 0x21);
+        assert_eq!(nes.read_ppu_memory_at(0x1C00).unwrap(), 0x27);
+    }
+
+    // MMC5 cartridge with 8KB PRG ROM banks starting with 0x10 + their index
+    // and 1KB CHR ROM banks starting with 0x20 + their index
+    fn build_mmc5() -> Vec<u8> {
+        let mut rom = ines(5, 0x00, 4, 1);
+        for (bank, prg_rom) in rom[16..16 + 0x10000].chunks_mut(0x2000).enumerate() {
+            prg_rom[0] = 0x10 + bank as u8;
+        }
+        for (bank, chr_rom) in rom[16 + 0x10000..].chunks_mut(0x0400).enumerate() {
+            chr_rom[0] = 0x20 + bank as u8;
+        }
+        rom
+    }
+}
+
+mod mmc3 {
+    use crate::common::ines;
+    use nesmulator_core::{nes::NES, Config};
+
+    const IRQ_HANDLER: u16 = 0xE030;
+
+    #[test]
+    fn scanline_irq() {
+        for latch in [1, 19, 100] {
+            let mut nes = NES::from_config(Config {
+                display_cpu_logs: true,
+                ..Config::default()
+            });
+            nes.insert_cartdrige_from_bytes(&build_mmc3(latch)).unwrap();
+
+            // The counter is reloaded on the pre-render scanline, then clocked once per
+            // scanline when the sprites are fetched from 0x1000
+            let mut irq_scanline = None;
+            for _ in 0..100_000 {
+                nes.step_cpu_instruction().unwrap();
+                let log = nes.get_last_cpu_log().unwrap();
+                if log.pc == IRQ_HANDLER {
+                    irq_scanline = Some(log.scanline);
+                    break;
+                }
+            }
+            assert_eq!(irq_scanline, Some(latch as u16 - 1), "latch {}", latch);
+        }
+    }
+
+    // MMC3 cartridge enabling the scanline IRQ with the given latch during the vertical blank
+    fn build_mmc3(latch: u8) -> Vec<u8> {
+        let program = [
+            0xA9, 0x40, 0x8D, 0x17, 0x40, // LDA #$40, STA $4017 (no APU frame IRQ)
+            0xA9, 0x08, 0x8D, 0x00, 0x20, // LDA #$08, STA $2000 (sprites at 0x1000)
+            0xA9, 0x1E, 0x8D, 0x01, 0x20, // LDA #$1E, STA $2001
+            0x2C, 0x02, 0x20, 0x10, 0xFB, // BIT $2002, BPL -5
+            0xA9, latch, 0x8D, 0x00, 0xC0, // LDA #latch, STA $C000
+            0x8D, 0x01, 0xC0, 0x8D, 0x01, 0xE0, // STA $C001, STA $E001
+            0x58, 0x4C, 0x20, 0xE0, // CLI, JMP $E020
+        ];
+        // STA $E000 (acknowledges and disables the IRQ), RTI
+        let irq_handler = [0x8D, 0x00, 0xE0, 0x40];
+
+        let mut rom = ines(4, 0x00, 2, 1);
+        // The last 8KB bank is fixed at 0xE000
+        let last_bank = &mut rom[16 + 0x6000..16 + 0x8000];
+        last_bank[..program.len()].copy_from_slice(&program);
+        last_bank[0x30..0x34].copy_from_slice(&irq_handler);
+        last_bank[0x1FFA..].copy_from_slice(&[0x20, 0xE0, 0x00, 0xE0, 0x30, 0xE0]);
+        rom
+    }
+}
+
+mod battery_backed_ram {
+    use crate::common::ines;
+    use std::{fs, path::Path};
+
+    use nesmulator_core::nes::NES;
+
+    const BATTERY: u8 = 0x02;
+    const NROM: u8 = 0;
+    const MMC1: u8 = 1;
+
+    #[test]
+    fn battery_backed_ram() {
+        let mut nes = NES::new();
+        assert!(!nes.has_battery_backed_ram());
+
+        nes.insert_cartdrige_from_bytes(&ines(MMC1, BATTERY, 2, 1))
+            .unwrap();
+        assert!(nes.has_battery_backed_ram());
+        nes.insert_cartdrige_from_bytes(&ines(MMC1, 0x00, 2, 1))
+            .unwrap();
+        assert!(!nes.has_battery_backed_ram());
+
+        // The NROM mapper does not save its RAM
+        nes.insert_cartdrige_from_bytes(&ines(NROM, BATTERY, 2, 1))
+            .unwrap();
+        assert!(!nes.has_battery_backed_ram());
+    }
+
+    #[test]
+    fn autosave() {
+        let save_path = std::env::temp_dir().join("nesmulator_autosave.sav");
+        let save_path = save_path.to_str().unwrap().to_owned();
+        let _ = fs::remove_file(&save_path);
+
+        // Without battery, nothing is saved
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&ines(MMC1, 0x00, 2, 1))
+            .unwrap();
+        nes.enable_autosave(save_path.clone(), 2);
+        run_frames(&mut nes, 3);
+        assert!(!Path::new(&save_path).exists());
+
+        let mut nes = NES::new();
+        nes.insert_cartdrige_from_bytes(&ines(MMC1, BATTERY, 2, 1))
+            .unwrap();
+        nes.enable_autosave(save_path.clone(), 2);
+        nes.write_memory_at(0x6000, 0xEA).unwrap();
+        run_frames(&mut nes, 1);
+        assert!(!Path::new(&save_path).exists());
+        run_frames(&mut nes, 1);
+        let save = fs::read(&save_path).unwrap();
+        assert_eq!(save[0], 0xEA);
+
+        // Disabled
+        fs::remove_file(&save_path).unwrap();
+        nes.enable_autosave(save_path.clone(), 0);
+        run_frames(&mut nes, 2);
+        assert!(!Path::new(&save_path).exists());
+    }
+
+    fn run_frames(nes: &mut NES, frames: u64) {
+        let frame = nes.frame_count() + frames;
+        while nes.frame_count() < frame {
+            nes.clock().unwrap();
+        }
+    }
+}
+
+mod nes_errors {
+    use std::fs;
+
+    use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::{nes::NES, utils::ControllerPort, NesError};
+
+    #[test]
+    fn error_kinds() {
+        let rom_path = format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX);
+        let mut nes = NES::new();
+
+        assert!(matches!(
+            nes.read_memory_at(0x8000),
+            Err(NesError::NoCartridge)
+        ));
+        assert!(matches!(
+            nes.insert_cartdrige("not_a_rom.nes"),
+            Err(NesError::Io(_))
+        ));
+        assert!(matches!(
+            nes.insert_cartdrige_from_bytes(&[0; 16]),
+            Err(NesError::InvalidRom(_))
+        ));
+
+        // Truncated file, and header announcing more banks than the file contains
+        let mut rom = fs::read(&rom_path).unwrap();
+        assert!(matches!(
+            nes.insert_cartdrige_from_bytes(&rom[..rom.len() - 0x2000]),
+            Err(NesError::InvalidRom(_))
+        ));
+        rom[4] = 0xFF;
+        assert!(matches!(
+            nes.insert_cartdrige_from_bytes(&rom),
+            Err(NesError::InvalidRom(_))
+        ));
+
+        // Mapper 255
+        let mut rom = fs::read(&rom_path).unwrap();
+        rom[6] |= 0xF0;
+        rom[7] |= 0xF0;
+        assert!(matches!(
+            nes.insert_cartdrige_from_bytes(&rom),
+            Err(NesError::UnsupportedMapper(255))
+        ));
+
+        nes.insert_cartdrige(&rom_path).unwrap();
+        assert!(matches!(
+            nes.save("registers.sav"),
+            Err(NesError::NoPersistentMemory)
+        ));
+        assert!(matches!(
+            ControllerPort::try_from(2),
+            Err(NesError::InvalidArgument(_))
+        ));
+    }
+}
+
+mod instruction_set {
+    use nesmulator_core::{all_instructions, instruction_info, AddressingMode};
+
+    #[test]
+    fn describe_opcode() {
+        let lda = instruction_info(0xBD);
+        assert_eq!(lda.name, "LDA");
+        assert_eq!(lda.addressing_mode, AddressingMode::AbsoluteX);
+        assert_eq!(lda.bytes, 3);
+        assert_eq!(lda.cycles, 4);
+        assert!(lda.official);
+
+        let nop = instruction_info(0x1C);
+        assert_eq!(nop.name, "NOP");
+        assert_eq!(nop.bytes, 3);
+        assert!(!nop.official);
+    }
+
+    #[test]
+    fn describe_instruction_set() {
+        let instructions = all_instructions();
+        for (opcode, instruction) in instructions.iter().enumerate() {
+            assert_eq!(instruction.opcode as usize, opcode);
+        }
+        assert_eq!(instructions.iter().filter(|i| i.official).count(), 151);
+    }
+}
+
+// Load the program in RAM and point the CPU to it
+// The first step completes the reset sequence and executes the first instruction
+fn load_program(program: &[u8]) -> NES {
+    load_program_with_config(program, Config::default())
+}
+
+fn load_program_with_config(program: &[u8], config: Config) -> NES {
+    let mut nes = NES::from_config(config);
+    nes.insert_cartdrige(&format!("{}cpu_reset/registers.nes", ROM_PATH_PREFIX))
+        .unwrap();
+    for (i, byte) in program.iter().enumerate() {
+        nes.write_memory_at(PROGRAM_ADDRESS + i as u16, *byte)
+            .unwrap();
+    }
+    nes.set_program_counter_at(PROGRAM_ADDRESS);
+    nes
+}
@@ -1,5 +1,8 @@
 mod common;
 
+use common::ines;
+use nesmulator_core::{nes::NES, utils::ARGBColor, Config};
+
 mod ppu_vbl_nmi {
     use crate::common::run_rom;
     use crate::common::ROM_PATH_PREFIX;
@@ -22,13 +25,11 @@ mod ppu_vbl_nmi {
     }
 
     #[test]
-    #[ignore]
     fn nmi_control() {
         run_rom(&get_path("04-nmi_control.nes"));
     }
 
     #[test]
-    #[ignore]
     fn nmi_timing() {
         run_rom(&get_path("05-nmi_timing.nes"));
     }
@@ -39,7 +40,6 @@ mod ppu_vbl_nmi {
     }
 
     #[test]
-    #[ignore]
     fn nmi_on_timing() {
         run_rom(&get_path("07-nmi_on_timing.nes"));
     }
@@ -101,6 +101,8 @@ mod ppu_open_bus {
 mod oam_read {
     use crate::common::run_rom;
     use crate::common::ROM_PATH_PREFIX;
+    use nesmulator_core::nes::NES;
+    use nesmulator_core::SpriteEntry;
 
     const DIR_PATH: &str = "oam_read/";
 
@@ -109,6 +111,26 @@ mod oam_read {
         run_rom(&get_path("oam_read.nes"));
     }
 
+    #[test]
+    fn get_oam() {
+        let mut nes = NES::new();
+        nes.write_memory_at(0x2003, 0x04).unwrap();
+        for value in [0x10, 0x20, 0xFF, 0x30] {
+            nes.write_memory_at(0x2004, value).unwrap();
+        }
+
+        let oam = nes.get_oam();
+        assert_eq!(
+            oam[1],
+            SpriteEntry {
+                y: 0x10,
+                tile_id: 0x20,
+                attributes: 0xE3,
+                x: 0x30,
+            }
+        );
+    }
+
     fn get_path(rom: &str) -> String {
         format!("{}{}{}", ROM_PATH_PREFIX, DIR_PATH, rom)
     }
@@ -129,3 +151,924 @@ mod oam_stress {
         format!("{}{}{}", ROM_PATH_PREFIX, DIR_PATH, rom)
     }
 }
+
+mod four_screen_mirroring {
+    use nesmulator_core::{nes::NES, Mirroring};
+
+    use crate::load_nrom;
+
+    const NAME_TABLES_HI_ADDRESSES: [u8; 4] = [0x20, 0x24, 0x28, 0x2C];
+    const VALUES: [u8; 4] = [0x11, 0x22, 0x33, 0x44];
+    const DONE_ADDRESS: u16 = 0x0010;
+    const MAX_CLOCKS: u32 = 100_000;
+
+    #[test]
+    fn cartridge_info() {
+        assert!(NES::new().get_cartridge_info().is_none());
+        let nes = load_nrom("four_screen_info", 0x08, &[]);
+        let info = nes.get_cartridge_info().unwrap();
+        assert_eq!(info.mapper_number, 0);
+        assert_eq!(info.mirroring, Mirroring::FourScreens);
+    }
+
+    #[test]
+    fn four_screen_mirroring() {
+        let mut nes = load_nrom("four_screen_mirroring", 0x08, &get_program());
+        for _ in 0..MAX_CLOCKS {
+            nes.clock().unwrap();
+            if nes.read_memory_at(DONE_ADDRESS).unwrap() == 0x01 {
+                break;
+            }
+        }
+
+        assert_eq!(nes.read_memory_at(DONE_ADDRESS).unwrap(), 0x01);
+        for (i, value) in VALUES.iter().enumerate() {
+            assert_eq!(nes.read_memory_at(i as u16).unwrap(), *value);
+        }
+    }
+
+    // Write a distinct value in each name table and copy them back to the CPU RAM at 0x0000
+    fn get_program() -> Vec<u8> {
+        let mut program = vec![];
+        for (hi, value) in NAME_TABLES_HI_ADDRESSES.iter().zip(VALUES.iter()) {
+            program.extend([0xAD, 0x02, 0x20]); // LDA $2002
+            program.extend([0xA9, *hi, 0x8D, 0x06, 0x20]); // LDA #hi, STA $2006
+            program.extend([0xA9, 0x00, 0x8D, 0x06, 0x20]); // LDA #$00, STA $2006
+            program.extend([0xA9, *value, 0x8D, 0x07, 0x20]); // LDA #value, STA $2007
+        }
+        for (i, hi) in NAME_TABLES_HI_ADDRESSES.iter().enumerate() {
+            program.extend([0xAD, 0x02, 0x20]); // LDA $2002
+            program.extend([0xA9, *hi, 0x8D, 0x06, 0x20]); // LDA #hi, STA $2006
+            program.extend([0xA9, 0x00, 0x8D, 0x06, 0x20]); // LDA #$00, STA $2006
+            program.extend([0xAD, 0x07, 0x20]); // LDA $2007 (fills the read buffer)
+            program.extend([0xAD, 0x07, 0x20, 0x85, i as u8]); // LDA $2007, STA $i
+        }
+        program.extend([0xA9, 0x01, 0x85, DONE_ADDRESS as u8]); // LDA #$01, STA $10
+        program
+    }
+}
+
+mod grayscale {
+    use nesmulator_core::{nes::NES, utils::ARGBColor};
+
+    use crate::{load_nrom, to_tuple};
+
+    const RED: u8 = 0x16;
+    const GRAY: u8 = RED & 0x30;
+    const GRAYSCALE_MASK: u8 = 0x01;
+
+    #[test]
+    fn grayscale() {
+        let gray = render_backdrop("grayscale_gray", GRAY, 0x00);
+        let red = render_backdrop("grayscale_red", RED, 0x00);
+        let grayscale_red = render_backdrop("grayscale_grayscale_red", RED, GRAYSCALE_MASK);
+
+        assert_ne!(to_tuple(red), to_tuple(gray));
+        assert_eq!(to_tuple(grayscale_red), to_tuple(gray));
+    }
+
+    #[test]
+    fn palette_read() {
+        let (mut nes, grayscale_red) = run_backdrop("grayscale_palette_read", RED, GRAYSCALE_MASK);
+        let gray = render_backdrop("grayscale_palette_read_gray", GRAY, 0x00);
+        assert_eq!(to_tuple(grayscale_red), to_tuple(gray));
+
+        // Reading the palette gives the stored color, not the rendered one
+        nes.read_memory_at(0x2002).unwrap();
+        nes.write_memory_at(0x2006, 0x3F).unwrap();
+        nes.write_memory_at(0x2006, 0x00).unwrap();
+        assert_eq!(nes.read_memory_at(0x2007).unwrap() & 0x3F, RED);
+    }
+
+    // Get the color rendered in the middle of the screen with the given backdrop color and mask
+    fn render_backdrop(name: &str, color: u8, mask: u8) -> ARGBColor {
+        run_backdrop(name, color, mask).1
+    }
+
+    fn run_backdrop(name: &str, color: u8, mask: u8) -> (NES, ARGBColor) {
+        let mut program = vec![];
+        program.extend([0xAD, 0x02, 0x20]); // LDA $2002
+        program.extend([0xA9, 0x3F, 0x8D, 0x06, 0x20]); // LDA #$3F, STA $2006
+        program.extend([0xA9, 0x00, 0x8D, 0x06, 0x20]); // LDA #$00, STA $2006
+        program.extend([0xA9, color, 0x8D, 0x07, 0x20]); // LDA #color, STA $2007
+        program.extend([0xA9, mask, 0x8D, 0x01, 0x20]); // LDA #mask, STA $2001
+        let mut nes = load_nrom(name, 0x00, &program);
+        // The first frame may have been rendered before the palette was written
+        nes.run_one_frame().unwrap();
+        let color = nes.run_one_frame().unwrap()[256 * 120 + 128];
+
+        (nes, color)
+    }
+}
+
+mod one_screen_mirroring {
+    use nesmulator_core::{nes::NES, Mirroring};
+
+    use crate::{load_nrom, write_ppu};
+
+    // Mapper 7 (AxROM) selects the name table used for the whole screen with bit 4
+    const AXROM: u8 = 0x70;
+
+    #[test]
+    fn one_screen_mirroring() {
+        let mut nes = load_nrom("one_screen_mirroring", AXROM, &[]);
+
+        nes.write_memory_at(0x8000, 0x00).unwrap();
+        write_ppu(&mut nes, 0x2000, &[0x11]);
+        nes.write_memory_at(0x8000, 0x10).unwrap();
+        write_ppu(&mut nes, 0x2000, &[0x22]);
+
+        assert_eq!(read_ppu(&mut nes, 0x2C00), 0x22);
+        nes.write_memory_at(0x8000, 0x00).unwrap();
+        assert_eq!(read_ppu(&mut nes, 0x2C00), 0x11);
+    }
+
+    #[test]
+    fn mirroring_override() {
+        let mut nes = load_nrom("mirroring_override", 0x00, &[]);
+
+        // The cartridge uses horizontal mirroring
+        write_ppu(&mut nes, 0x2000, &[0x11]);
+        write_ppu(&mut nes, 0x2800, &[0x22]);
+        assert_eq!(read_ppu(&mut nes, 0x2400), 0x11);
+
+        nes.override_mirroring(Mirroring::Vertical);
+        assert_eq!(read_ppu(&mut nes, 0x2400), 0x22);
+        nes.override_mirroring(Mirroring::OneScreenLower);
+        assert_eq!(read_ppu(&mut nes, 0x2C00), 0x11);
+
+        nes.clear_mirroring_override();
+        assert_eq!(read_ppu(&mut nes, 0x2C00), 0x22);
+    }
+
+    fn read_ppu(nes: &mut NES, address: u16) -> u8 {
+        nes.write_memory_at(0x2006, (address >> 8) as u8).unwrap();
+        nes.write_memory_at(0x2006, address as u8).unwrap();
+        // The first read only fills the read buffer
+        nes.read_memory_at(0x2007).unwrap();
+        nes.read_memory_at(0x2007).unwrap()
+    }
+}
+
+mod nametable_viewer {
+    use crate::{load_nrom, to_tuple, write_ppu};
+
+    const VERTICAL_MIRRORING: u8 = 0x01;
+
+    #[test]
+    fn nametable_viewer() {
+        let mut nes = load_nrom("nametable_viewer", VERTICAL_MIRRORING, &[]);
+
+        // Black backdrop, white as the first color of the first palette
+        write_ppu(&mut nes, 0x3F00, &[0x0F, 0x30]);
+        // Tile 1 only uses the first color
+        write_ppu(&mut nes, 0x0010, &[0xFF; 8]);
+        // Top left tile of the first nametable
+        write_ppu(&mut nes, 0x2000, &[0x01]);
+
+        let nametable_0 = nes.get_nametable(0).unwrap();
+        let nametable_1 = nes.get_nametable(1).unwrap();
+        let nametable_2 = nes.get_nametable(2).unwrap();
+        let palette = nes.get_palette().unwrap();
+
+        assert_eq!(nametable_0.len(), 256 * 240);
+        assert_eq!(to_tuple(nametable_0[256 * 7 + 7]), to_tuple(palette[1]));
+        assert_eq!(to_tuple(nametable_0[8]), to_tuple(palette[0]));
+        assert_eq!(to_tuple(nametable_1[0]), to_tuple(palette[0]));
+        assert_eq!(to_tuple(nametable_2[0]), to_tuple(palette[1]));
+        assert!(nes.get_nametable(4).is_err());
+    }
+
+    #[test]
+    fn nametable_tiles() {
+        let mut nes = load_nrom("nametable_tiles", VERTICAL_MIRRORING, &[]);
+
+        // Last tile of the second row, and last tile of the nametable
+        write_ppu(&mut nes, 0x243F, &[0x12]);
+        write_ppu(&mut nes, 0x27BF, &[0x34]);
+
+        let tiles = nes.dump_nametable_tiles(1).unwrap();
+        assert_eq!(tiles[1][31], 0x12);
+        assert_eq!(tiles[29][31], 0x34);
+        assert_eq!(tiles[0][0], 0x00);
+        // The nametable 3 is mirrored on the nametable 1
+        assert_eq!(nes.dump_nametable_tiles(3).unwrap(), tiles);
+        assert!(nes.dump_nametable_tiles(4).is_err());
+    }
+
+    #[test]
+    fn pattern_table_with_palette() {
+        let mut nes = load_nrom("pattern_table_with_palette", VERTICAL_MIRRORING, &[]);
+
+        // White as the first color of the first palette, red for the third palette
+        write_ppu(&mut nes, 0x3F01, &[0x30]);
+        write_ppu(&mut nes, 0x3F09, &[0x16]);
+        // Tile 1 only uses the first color
+        write_ppu(&mut nes, 0x0010, &[0xFF; 8]);
+
+        let palette = nes.get_palette().unwrap();
+        let with_palette_0 = nes.get_pattern_table_with_palette(0, 0).unwrap();
+        let with_palette_2 = nes.get_pattern_table_with_palette(0, 2).unwrap();
+        assert_eq!(to_tuple(with_palette_0[8]), to_tuple(palette[1]));
+        assert_eq!(to_tuple(with_palette_2[8]), to_tuple(palette[9]));
+
+        // The debug palette is left unchanged
+        let pattern_table = nes.get_pattern_table(0).unwrap();
+        assert_eq!(to_tuple(pattern_table[8]), to_tuple(palette[1]));
+
+        assert!(nes.get_pattern_table_with_palette(0, 8).is_err());
+        assert!(nes.get_pattern_table_with_palette(2, 0).is_err());
+    }
+
+    #[test]
+    fn palette_indices() {
+        let mut nes = load_nrom("palette_indices", VERTICAL_MIRRORING, &[]);
+
+        write_ppu(&mut nes, 0x3F01, &[0x30, 0xD6]);
+        // The backdrop color of the sprite palette 0 is a mirror of the background one
+        write_ppu(&mut nes, 0x3F10, &[0x0F]);
+
+        let indices = nes.get_palette_indices();
+        assert_eq!(indices[0x00], 0x0F);
+        assert_eq!(indices[0x01], 0x30);
+        assert_eq!(indices[0x02], 0x16);
+        assert_eq!(indices[0x10], 0x0F);
+        assert_eq!(indices[0x1F], 0x00);
+    }
+}
+
+mod palette_data {
+    use nesmulator_core::Config;
+
+    use crate::load_nrom_with_config;
+
+    #[test]
+    fn palette_data() {
+        // Each color is a gray level equal to its index
+        let palette_data: Vec<u8> = (0..64).flat_map(|i| [i, i, i]).collect();
+        let mut nes = load_nrom_with_config(
+            "palette_data",
+            0x00,
+            &[],
+            Config {
+                palette_path: Some("./not_a_palette.pal".to_string()),
+                palette_data: Some(palette_data),
+                ..Config::default()
+            },
+        );
+
+        nes.write_memory_at(0x2006, 0x3F).unwrap();
+        nes.write_memory_at(0x2006, 0x00).unwrap();
+        nes.write_memory_at(0x2007, 0x21).unwrap();
+
+        let color = nes.get_palette().unwrap()[0];
+        assert_eq!((color.red, color.green, color.blue), (0x21, 0x21, 0x21));
+    }
+
+    #[test]
+    fn emphasis() {
+        const RED: u8 = 0x16;
+        // Emphasize red, then all the components
+        for (mask, palette_id) in [(0x00, 0), (0x20, 1), (0xE0, 7)] {
+            let mut program = vec![];
+            program.extend([0xAD, 0x02, 0x20]); // LDA $2002
+            program.extend([0xA9, 0x3F, 0x8D, 0x06, 0x20]); // LDA #$3F, STA $2006
+            program.extend([0xA9, 0x00, 0x8D, 0x06, 0x20]); // LDA #$00, STA $2006
+            program.extend([0xA9, RED, 0x8D, 0x07, 0x20]); // LDA #RED, STA $2007
+            program.extend([0xA9, mask, 0x8D, 0x01, 0x20]); // LDA #mask, STA $2001
+
+            // The green component of each color is the index of its palette
+            let palette_data: Vec<u8> = (0..8)
+                .flat_map(|palette| (0..64).flat_map(move |i| [i, palette, 0]))
+                .collect();
+            let mut nes = load_nrom_with_config(
+                "palette_data_emphasis",
+                0x00,
+                &program,
+                Config {
+                    palette_data: Some(palette_data),
+                    ..Config::default()
+                },
+            );
+            // The first frame may have been rendered before the mask was written
+            nes.run_one_frame().unwrap();
+            let color = nes.run_one_frame().unwrap()[256 * 120 + 128];
+            assert_eq!((color.red, color.green), (RED, palette_id));
+        }
+    }
+}
+
+mod frame_callback {
+    use std::{
+        fs,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use nesmulator_core::nes::{NES, NES_HEIGHT, NES_WIDTH};
+
+    use crate::{build_nrom, load_nrom};
+
+    #[test]
+    fn frame_callback() {
+        let mut nes = load_nrom("frame_callback", 0x00, &[]);
+
+        let frames = Arc::new(AtomicUsize::new(0));
+        let callback_frames = Arc::clone(&frames);
+        nes.set_frame_callback(Box::new(move |frame| {
+            assert_eq!(frame.len(), NES_WIDTH * NES_HEIGHT);
+            callback_frames.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        for _ in 0..3 {
+            nes.run_one_frame().unwrap();
+        }
+        assert_eq!(frames.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn frame_count() {
+        let rom_path = build_nrom("frame_count", 0x00, &[]);
+        let mut nes = NES::new();
+        nes.insert_cartdrige(rom_path.to_str().unwrap()).unwrap();
+
+        for _ in 0..3 {
+            nes.run_one_frame().unwrap();
+        }
+        assert_eq!(nes.frame_count(), 3);
+
+        // The frame count is part of the save states
+        let state = nes.serialize_state().unwrap();
+        let mut loaded = NES::new();
+        loaded
+            .deserialize_state(&state, rom_path.to_str().unwrap())
+            .unwrap();
+        fs::remove_file(&rom_path).unwrap();
+        assert_eq!(loaded.frame_count(), 3);
+
+        loaded.restart();
+        assert_eq!(loaded.frame_count(), 0);
+    }
+}
+
+mod scanline_callback {
+    use std::sync::{Arc, Mutex};
+
+    use crate::load_nrom;
+
+    #[test]
+    fn scanline_callback() {
+        let mut nes = load_nrom("scanline_callback", 0x00, &[]);
+        nes.run_one_frame().unwrap();
+
+        let scanlines = Arc::new(Mutex::new(vec![]));
+        let callback_scanlines = Arc::clone(&scanlines);
+        nes.set_scanline_callback(Box::new(move |scanline| {
+            callback_scanlines.lock().unwrap().push(scanline);
+        }));
+
+        nes.run_one_frame().unwrap();
+        assert_eq!(*scanlines.lock().unwrap(), (0..240).collect::<Vec<u16>>());
+    }
+}
+
+mod nmi_callback {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use nesmulator_core::nes::NES;
+
+    use crate::load_nrom;
+
+    #[test]
+    fn nmi_callback() {
+        let nmis = Arc::new(AtomicUsize::new(0));
+
+        // The callback is not called when the NMI is disabled
+        let mut nes = build_nes("nmi_callback_disabled", &[], &nmis);
+        for _ in 0..3 {
+            nes.run_one_frame().unwrap();
+        }
+        assert_eq!(nmis.load(Ordering::SeqCst), 0);
+
+        let program = [0xA9, 0x80, 0x8D, 0x00, 0x20]; // LDA #$80, STA $2000
+        let mut nes = build_nes("nmi_callback", &program, &nmis);
+        for _ in 0..3 {
+            nes.run_one_frame().unwrap();
+        }
+        assert_eq!(nmis.load(Ordering::SeqCst), 3);
+    }
+
+    fn build_nes(name: &str, program: &[u8], nmis: &Arc<AtomicUsize>) -> NES {
+        let mut nes = load_nrom(name, 0x00, program);
+
+        let callback_nmis = Arc::clone(nmis);
+        nes.set_nmi_callback(Box::new(move || {
+            callback_nmis.fetch_add(1, Ordering::SeqCst);
+        }));
+        nes
+    }
+}
+
+mod ppu_reset {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use crate::load_nrom;
+
+    #[test]
+    fn registers_cleared() {
+        let mut nes = load_nrom("ppu_reset", 0x00, &[]);
+        let nmis = Arc::new(AtomicUsize::new(0));
+        let callback_nmis = Arc::clone(&nmis);
+        nes.set_nmi_callback(Box::new(move || {
+            callback_nmis.fetch_add(1, Ordering::SeqCst);
+        }));
+
+        // Fill the name table and the palette RAM
+        nes.write_memory_at(0x2006, 0x20).unwrap();
+        nes.write_memory_at(0x2006, 0x00).unwrap();
+        nes.write_memory_at(0x2007, 0x55).unwrap();
+        nes.write_memory_at(0x2006, 0x3F).unwrap();
+        nes.write_memory_at(0x2006, 0x01).unwrap();
+        nes.write_memory_at(0x2007, 0x2A).unwrap();
+
+        nes.write_memory_at(0x2000, 0x82).unwrap();
+        nes.run_one_frame().unwrap();
+        assert_eq!(nmis.load(Ordering::SeqCst), 1);
+        nes.write_memory_at(0x2005, 0x7D).unwrap();
+        let vram_address = nes.get_ppu_scroll_state().vram_address;
+
+        nes.reset();
+        let scroll = nes.get_ppu_scroll_state();
+        assert!(!scroll.write_toggle);
+        assert_eq!(scroll.tmp_vram_address, 0);
+        assert_eq!(scroll.fine_x, 0);
+        assert_eq!(scroll.vram_address, vram_address);
+        assert_eq!(nes.read_ppu_memory_at(0x2000).unwrap(), 0x55);
+        assert_eq!(nes.read_ppu_memory_at(0x3F01).unwrap(), 0x2A);
+
+        // The NMI is disabled
+        for _ in 0..2 {
+            nes.run_one_frame().unwrap();
+        }
+        assert_eq!(nmis.load(Ordering::SeqCst), 1);
+    }
+}
+
+mod sprite_overflow_bug {
+    use crate::load_nrom;
+
+    #[test]
+    fn false_positive() {
+        // The tile id of sprite 9 is read as its y coordinate
+        let mut oam = eight_sprites_then_hidden_one();
+        oam[9 * 4..9 * 4 + 2].copy_from_slice(&[0xF0, 0x10]);
+        assert!(is_overflow_set("overflow_false_positive", &oam));
+    }
+
+    #[test]
+    fn false_negative() {
+        // Sprite 9 is on the scanline but its tile id is read as its y coordinate
+        let mut oam = eight_sprites_then_hidden_one();
+        oam[9 * 4..9 * 4 + 2].copy_from_slice(&[0x10, 0xF0]);
+        assert!(!is_overflow_set("overflow_false_negative", &oam));
+    }
+
+    #[test]
+    fn nine_sprites() {
+        let mut oam = eight_sprites_then_hidden_one();
+        oam[8 * 4] = 0x10;
+        assert!(is_overflow_set("overflow_nine_sprites", &oam));
+    }
+
+    // 8 sprites on scanline 0x10, followed by a sprite out of the scanline
+    fn eight_sprites_then_hidden_one() -> [u8; 256] {
+        let mut oam = [0xFF; 256];
+        for sprite in 0..8 {
+            oam[sprite * 4] = 0x10;
+        }
+        oam[8 * 4] = 0xF0;
+        oam
+    }
+
+    fn is_overflow_set(name: &str, oam: &[u8; 256]) -> bool {
+        let mut nes = load_nrom(name, 0x00, &[]);
+
+        nes.write_memory_at(0x2003, 0x00).unwrap();
+        for byte in oam {
+            nes.write_memory_at(0x2004, *byte).unwrap();
+        }
+        // Show background and sprites
+        nes.write_memory_at(0x2001, 0x18).unwrap();
+
+        nes.run_one_frame().unwrap();
+        for _ in 0..341 * 100 {
+            nes.clock().unwrap();
+        }
+        nes.read_memory_at(0x2002).unwrap() & 0x20 > 0
+    }
+}
+
+mod sprite_0_hit_prediction {
+    use nesmulator_core::nes::NES;
+
+    use crate::{load_nrom, write_ppu};
+
+    #[test]
+    fn no_scroll() {
+        let mut nes = setup("sprite_0_hit_no_scroll", 0);
+        assert_eq!(nes.sprite_0_hit_scanline(), Some(88));
+        assert_eq!(run_until_hit(&mut nes), Some(88));
+    }
+
+    #[test]
+    fn vertical_scroll() {
+        // The background tile moves 8 pixels up, on the first scanlines of the sprite
+        let mut nes = setup("sprite_0_hit_vertical_scroll", 8);
+        assert_eq!(nes.sprite_0_hit_scanline(), Some(81));
+        assert_eq!(run_until_hit(&mut nes), Some(81));
+    }
+
+    #[test]
+    fn no_hit() {
+        let mut nes = setup("sprite_0_hit_none", 0);
+        // Move the sprite away from the background tile
+        nes.write_memory_at(0x2003, 0x03).unwrap();
+        nes.write_memory_at(0x2004, 0xA0).unwrap();
+        assert_eq!(nes.sprite_0_hit_scanline(), None);
+        assert_eq!(run_until_hit(&mut nes), None);
+    }
+
+    #[test]
+    fn rendering_disabled() {
+        let mut nes = setup("sprite_0_hit_rendering_disabled", 0);
+        nes.write_memory_at(0x2001, 0x00).unwrap();
+        assert_eq!(nes.sprite_0_hit_scanline(), None);
+    }
+
+    // Sprite 0 covers the pixels from (64, 81) to (71, 88) and an opaque background tile
+    // covers the pixels from (64, 88) to (71, 95)
+    fn setup(name: &str, scroll_y: u8) -> NES {
+        let mut nes = load_nrom(name, 0x00, &[]);
+
+        // Tile 1 is opaque
+        write_ppu(&mut nes, 0x0010, &[0xFF; 8]);
+        write_ppu(&mut nes, 0x2000 + 11 * 32 + 8, &[0x01]);
+
+        nes.write_memory_at(0x2003, 0x00).unwrap();
+        for byte in [0x50, 0x01, 0x00, 0x40] {
+            nes.write_memory_at(0x2004, byte).unwrap();
+        }
+
+        nes.write_memory_at(0x2000, 0x00).unwrap();
+        nes.write_memory_at(0x2005, 0x00).unwrap();
+        nes.write_memory_at(0x2005, scroll_y).unwrap();
+        // Show background and sprites
+        nes.write_memory_at(0x2001, 0x18).unwrap();
+        nes
+    }
+
+    // Run a frame and return the scanline on which the sprite 0 hit flag is set
+    fn run_until_hit(nes: &mut NES) -> Option<u16> {
+        nes.run_one_frame().unwrap();
+        for scanline in 0..240 {
+            nes.clock_n(341).unwrap();
+            if nes.read_memory_at(0x2002).unwrap() & 0x40 > 0 {
+                return Some(scanline);
+            }
+        }
+        None
+    }
+}
+
+mod sprite_limit {
+    use std::fs;
+
+    use nesmulator_core::nes::NES;
+
+    use crate::{build_nrom, load_nrom, write_ppu};
+
+    #[test]
+    fn hardware_limit() {
+        let mut nes = setup("sprite_limit_hardware");
+        let (drawn, overflow) = render(&mut nes);
+        assert_eq!(drawn, 8);
+        assert!(overflow);
+    }
+
+    #[test]
+    fn raised_limit() {
+        let mut nes = setup("sprite_limit_raised");
+        nes.set_sprite_limit(Some(9));
+        let (drawn, overflow) = render(&mut nes);
+        assert_eq!(drawn, 9);
+        assert!(overflow);
+    }
+
+    #[test]
+    fn no_limit() {
+        let mut nes = setup("sprite_limit_none");
+        nes.set_sprite_limit(None);
+        let (drawn, overflow) = render(&mut nes);
+        assert_eq!(drawn, 10);
+        // The overflow flag is still set as by the hardware
+        assert!(overflow);
+
+        // The limit is kept when loading a state
+        let state = nes.serialize_state().unwrap();
+        let rom_path = build_nrom("sprite_limit_state", 0x00, &[]);
+        nes.deserialize_state(&state, rom_path.to_str().unwrap())
+            .unwrap();
+        fs::remove_file(&rom_path).unwrap();
+        assert_eq!(render(&mut nes).0, 10);
+    }
+
+    // 10 opaque white sprites on the same scanlines, 16 pixels apart
+    fn setup(name: &str) -> NES {
+        let mut nes = load_nrom(name, 0x00, &[]);
+
+        write_ppu(&mut nes, 0x0010, &[0xFF; 8]);
+        write_ppu(&mut nes, 0x3F00, &[0x0F]);
+        write_ppu(&mut nes, 0x3F11, &[0x30]);
+
+        nes.write_memory_at(0x2003, 0x00).unwrap();
+        for sprite in 0..64 {
+            let (y, x) = if sprite < 10 {
+                (0x20, sprite * 16)
+            } else {
+                (0xFF, 0)
+            };
+            for byte in [y, 0x01, 0x00, x] {
+                nes.write_memory_at(0x2004, byte).unwrap();
+            }
+        }
+        // Show sprites
+        nes.write_memory_at(0x2001, 0x14).unwrap();
+        nes
+    }
+
+    // Return the number of sprites drawn and the sprite overflow flag
+    fn render(nes: &mut NES) -> (usize, bool) {
+        nes.run_one_frame().unwrap();
+        let frame = nes.run_one_frame().unwrap();
+        let drawn = (0..10)
+            .filter(|sprite| frame[256 * 0x24 + sprite * 16 + 4].red > 0x80)
+            .count();
+        // The flag is cleared at the end of the vertical blank, render the sprites again
+        nes.clock_n(341 * 0x30).unwrap();
+        let overflow = nes.read_memory_at(0x2002).unwrap() & 0x20 > 0;
+        (drawn, overflow)
+    }
+}
+
+mod frame_buffer {
+    use crate::load_nrom;
+
+    #[test]
+    fn borrow_frame_buffer() {
+        let mut nes = load_nrom("frame_buffer", 0x00, &[]);
+
+        while nes.frame_buffer().is_none() {
+            nes.clock().unwrap();
+        }
+        // The frame has been consumed by the borrow
+        assert!(nes.frame_buffer().is_none());
+        assert!(nes.get_frame_buffer().is_none());
+    }
+}
+
+mod render_enabled {
+    use nesmulator_core::utils::ARGBColor;
+
+    use crate::{load_nrom, to_tuple};
+
+    #[test]
+    fn render_enabled() {
+        // Set a red backdrop color and wait for the vblank flag in a loop
+        let mut program = vec![];
+        program.extend([0xAD, 0x02, 0x20]); // LDA $2002
+        program.extend([0xA9, 0x3F, 0x8D, 0x06, 0x20]); // LDA #$3F, STA $2006
+        program.extend([0xA9, 0x00, 0x8D, 0x06, 0x20]); // LDA #$00, STA $2006
+        program.extend([0xA9, 0x16, 0x8D, 0x07, 0x20]); // LDA #$16, STA $2007
+        program.extend([0xAD, 0x02, 0x20]); // LDA $2002
+        program.extend([0x10, 0xFB]); // BPL -5
+        program.extend([0xE6, 0x00]); // INC $00
+        program.extend([0x4C, 0x12, 0x80]); // JMP $8012
+        let mut nes = load_nrom("render_enabled", 0x00, &program);
+
+        // The frames are not rendered, but the game still sees the vblanks
+        nes.set_render_enabled(false);
+        for _ in 0..3 {
+            nes.run_one_frame().unwrap();
+        }
+        let frame = nes.run_one_frame().unwrap();
+        assert_eq!(
+            to_tuple(frame[256 * 120 + 128]),
+            to_tuple(ARGBColor::black())
+        );
+        assert!(nes.read_memory_at(0x0000).unwrap() >= 3);
+
+        nes.set_render_enabled(true);
+        let frame = nes.run_one_frame().unwrap();
+        assert_ne!(
+            to_tuple(frame[256 * 120 + 128]),
+            to_tuple(ARGBColor::black())
+        );
+    }
+}
+
+mod overscan {
+    use nesmulator_core::{Config, Overscan};
+
+    use crate::load_nrom_with_config;
+
+    #[test]
+    fn cropped_frame_buffer() {
+        let overscan = Overscan {
+            top: 8,
+            bottom: 8,
+            left: 4,
+            right: 12,
+        };
+        assert_eq!(overscan.width(), 240);
+        assert_eq!(overscan.height(), 224);
+
+        let mut nes = load_nrom_with_config(
+            "overscan",
+            0x00,
+            &[],
+            Config {
+                overscan,
+                ..Config::default()
+            },
+        );
+
+        assert!(nes.get_cropped_frame_buffer().is_none());
+        let cropped = loop {
+            nes.clock().unwrap();
+            if let Some(cropped) = nes.get_cropped_frame_buffer() {
+                break cropped;
+            }
+        };
+        assert_eq!(cropped.len(), 240 * 224);
+        // The frame has been consumed
+        assert!(nes.get_frame_buffer().is_none());
+    }
+}
+
+mod chr_ram {
+    use crate::common::ines;
+    use nesmulator_core::nes::NES;
+
+    use crate::write_ppu;
+
+    // NROM, CNROM, MMC5 and GxROM
+    const MAPPERS: [u8; 5] = [0, 3, 4, 5, 66];
+
+    #[test]
+    fn chr_ram_is_writable() {
+        for mapper in MAPPERS {
+            let mut nes = NES::new();
+            nes.insert_cartdrige_from_bytes(&ines(mapper, 0x00, 1, 0))
+                .unwrap();
+
+            write_ppu(&mut nes, 0x0010, &[0x55]);
+            assert_eq!(nes.read_ppu_memory_at(0x0010).unwrap(), 0x55);
+        }
+    }
+
+    #[test]
+    fn chr_rom_is_read_only() {
+        for mapper in MAPPERS {
+            // One bank of PRG ROM and one bank of CHR ROM
+            let mut rom = ines(mapper, 0x00, 1, 1);
+            rom[16 + 0x4000..].fill(0xAA);
+            let mut nes = NES::new();
+            nes.insert_cartdrige_from_bytes(&rom).unwrap();
+
+            write_ppu(&mut nes, 0x0010, &[0x55]);
+            assert_eq!(nes.read_ppu_memory_at(0x0010).unwrap(), 0xAA);
+        }
+    }
+}
+
+mod scroll_state {
+    use crate::load_nrom;
+
+    #[test]
+    fn decode_scroll_registers() {
+        let mut nes = load_nrom("scroll_state", 0x00, &[]);
+
+        nes.write_memory_at(0x2000, 0x02).unwrap();
+        nes.write_memory_at(0x2005, 0x7D).unwrap();
+        assert!(nes.get_ppu_scroll_state().write_toggle);
+        nes.write_memory_at(0x2005, 0x5E).unwrap();
+        let scroll = nes.get_ppu_scroll_state();
+        assert!(!scroll.write_toggle);
+        assert_eq!(scroll.fine_x, 5);
+        // Fine Y 6, nametable 2, coarse Y 11, coarse X 15
+        assert_eq!(scroll.tmp_vram_address, 0x696F);
+
+        // The current VRAM address is set by the second write to 0x2006
+        nes.write_memory_at(0x2006, 0x2D).unwrap();
+        nes.write_memory_at(0x2006, 0xAF).unwrap();
+        let scroll = nes.get_ppu_scroll_state();
+        assert_eq!(scroll.vram_address, 0x2DAF);
+        assert_eq!(scroll.coarse_x, 15);
+        assert_eq!(scroll.coarse_y, 13);
+        assert_eq!(scroll.fine_x, 5);
+        assert_eq!(scroll.fine_y, 2);
+        assert_eq!(scroll.nametable, 3);
+    }
+}
+
+mod ppu_memory {
+    use crate::load_nrom;
+
+    const VERTICAL_MIRRORING: u8 = 0x01;
+
+    #[test]
+    fn read_ppu_memory_at() {
+        let mut nes = load_nrom("ppu_memory", VERTICAL_MIRRORING, &[]);
+
+        for (address, value) in [(0x0010, 0x11), (0x2401, 0x22), (0x3F01, 0x33)] {
+            nes.write_memory_at(0x2006, (address >> 8) as u8).unwrap();
+            nes.write_memory_at(0x2006, address as u8).unwrap();
+            nes.write_memory_at(0x2007, value).unwrap();
+        }
+
+        assert_eq!(nes.read_ppu_memory_at(0x0010).unwrap(), 0x11);
+        assert_eq!(nes.read_ppu_memory_at(0x2401).unwrap(), 0x22);
+        // Vertical mirroring and name tables mirrors
+        assert_eq!(nes.read_ppu_memory_at(0x2C01).unwrap(), 0x22);
+        assert_eq!(nes.read_ppu_memory_at(0x3401).unwrap(), 0x22);
+        assert_eq!(nes.read_ppu_memory_at(0x3F01).unwrap(), 0x33);
+        assert_eq!(nes.read_ppu_memory_at(0x3F21).unwrap(), 0x33);
+        assert!(nes.read_ppu_memory_at(0x4000).is_err());
+    }
+}
+
+// Write a NROM cartridge running the program followed by an infinite loop in the temporary
+// directory and return its path
+// NES with a NROM cartridge running the program, see `build_nrom`
+fn load_nrom(name: &str, flags_6: u8, program: &[u8]) -> NES {
+    let mut nes = NES::new();
+    insert_nrom(&mut nes, name, flags_6, program);
+    nes
+}
+
+fn load_nrom_with_config(name: &str, flags_6: u8, program: &[u8], config: Config) -> NES {
+    let mut nes = NES::from_config(config);
+    insert_nrom(&mut nes, name, flags_6, program);
+    nes
+}
+
+fn insert_nrom(nes: &mut NES, name: &str, flags_6: u8, program: &[u8]) {
+    let rom_path = build_nrom(name, flags_6, program);
+    nes.insert_cartdrige(rom_path.to_str().unwrap()).unwrap();
+    std::fs::remove_file(&rom_path).unwrap();
+}
+
+// Compare colors, ARGBColor does not implement PartialEq
+fn to_tuple(color: ARGBColor) -> (u8, u8, u8, u8) {
+    (color.alpha, color.red, color.green, color.blue)
+}
+
+// Write the data to the PPU bus through the PPUADDR and PPUDATA registers
+fn write_ppu(nes: &mut NES, address: u16, data: &[u8]) {
+    nes.write_memory_at(0x2006, (address >> 8) as u8).unwrap();
+    nes.write_memory_at(0x2006, address as u8).unwrap();
+    for byte in data {
+        nes.write_memory_at(0x2007, *byte).unwrap();
+    }
+}
+
+fn build_nrom(name: &str, flags_6: u8, program: &[u8]) -> std::path::PathBuf {
+    let mut program = program.to_vec();
+    let end_address = 0x8000 + program.len() as u16;
+    program.extend([0x4C, end_address as u8, (end_address >> 8) as u8]); // JMP end
+
+    let mut rom = ines(0, flags_6, 1, 0);
+    let prg_rom = &mut rom[16..];
+    prg_rom[..program.len()].copy_from_slice(&program);
+    // NMI, reset and IRQ vectors
+    prg_rom[0x3FFA..].copy_from_slice(&[
+        end_address as u8,
+        (end_address >> 8) as u8,
+        0x00,
+        0x80,
+        end_address as u8,
+        (end_address >> 8) as u8,
+    ]);
+
+    let rom_path = std::env::temp_dir().join(format!("nesmulator_{}.nes", name));
+    std::fs::write(&rom_path, rom).unwrap();
+    rom_path
+}